@@ -0,0 +1,158 @@
+use std::collections::HashMap;
+use std::ops::Range;
+
+/// How the machine accessed a byte of memory.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Access {
+    Read,
+    Write,
+    /// An instruction word was read to be executed, the value being its first byte.
+    Fetch,
+    /// The word following a 4-byte instruction was read as its operand, the value being its
+    /// first byte.
+    Operand,
+}
+
+/// Observes the memory accesses made by the machine, see `Ram::set_hook`.
+pub trait AccessHook {
+    fn access(&mut self, address: usize, access: Access, value: u8);
+}
+
+/// Accesses a watchpoint triggers on.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Watch {
+    Read,
+    Write,
+    ReadWrite,
+    /// Instructions executed from the range, operands included.
+    Fetch,
+}
+
+impl Watch {
+    fn matches(self, access: Access) -> bool {
+        match access {
+            Access::Read => self == Watch::Read || self == Watch::ReadWrite,
+            Access::Write => self == Watch::Write || self == Watch::ReadWrite,
+            Access::Fetch | Access::Operand => self == Watch::Fetch,
+        }
+    }
+}
+
+/// An access to a watched range of memory.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct WatchHit {
+    pub address: usize,
+    pub access: Access,
+    pub value: u8,
+    /// Address of the instruction that made the access.
+    pub pc: usize,
+}
+
+/// A byte executed after the program wrote it.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct SelfModification {
+    pub address: usize,
+    /// Address of the instruction that wrote the byte.
+    pub written_by: usize,
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub struct AccessCounts {
+    pub reads: u32,
+    pub writes: u32,
+    pub fetches: u32,
+}
+
+/// Counts the accesses made to every address, records watchpoint hits
+/// and flags self-modifying code.
+#[derive(Default)]
+pub struct AccessTracker {
+    counts: Vec<AccessCounts>,
+    watchpoints: Vec<(Range<usize>, Watch)>,
+    hits: Vec<WatchHit>,
+    // address of the instruction being executed, which is the latest one fetched
+    pc: usize,
+    // bytes written since they were last executed, along with the instruction that wrote them
+    written: HashMap<usize, usize>,
+    self_modifications: Vec<SelfModification>,
+}
+
+impl AccessTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records accesses of the given kind to `range` as hits.
+    pub fn add_watchpoint(&mut self, range: Range<usize>, watch: Watch) {
+        self.watchpoints.push((range, watch));
+    }
+
+    /// Accesses made to `address` so far.
+    pub fn counts(&self, address: usize) -> AccessCounts {
+        self.counts.get(address).copied().unwrap_or_default()
+    }
+
+    /// Removes and returns the watchpoint hits recorded since the last call.
+    pub fn take_hits(&mut self) -> Vec<WatchHit> {
+        std::mem::take(&mut self.hits)
+    }
+
+    /// Every byte found executed after being written, in the order they were executed.
+    /// A byte is flagged again only once it is written anew.
+    pub fn self_modifications(&self) -> &[SelfModification] {
+        &self.self_modifications
+    }
+
+    fn count(&mut self, address: usize) -> &mut AccessCounts {
+        if address >= self.counts.len() {
+            self.counts.resize(address + 1, AccessCounts::default());
+        }
+        &mut self.counts[address]
+    }
+
+    fn executed(&mut self, address: usize) {
+        self.count(address).fetches += 1;
+        if let Some(written_by) = self.written.remove(&address) {
+            self.self_modifications.push(SelfModification {
+                address,
+                written_by,
+            });
+        }
+    }
+}
+
+impl AccessHook for AccessTracker {
+    fn access(&mut self, address: usize, access: Access, value: u8) {
+        match access {
+            Access::Read => self.count(address).reads += 1,
+            Access::Write => {
+                self.count(address).writes += 1;
+                self.written.insert(address, self.pc);
+            }
+            Access::Fetch => {
+                // both bytes of the instruction are executed
+                self.pc = address;
+                self.executed(address);
+                self.executed(address + 1);
+            }
+            Access::Operand => {
+                // executed as part of the instruction fetched before it
+                self.executed(address);
+                self.executed(address + 1);
+            }
+        }
+        let pc = self.pc;
+        let hit = self
+            .watchpoints
+            .iter()
+            .any(|(range, watch)| range.contains(&address) && watch.matches(access));
+        if hit {
+            self.hits.push(WatchHit {
+                address,
+                access,
+                value,
+                pc,
+            });
+        }
+    }
+}
@@ -0,0 +1,687 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use crate::instruction::{decode, Instruction};
+use crate::ram::PROGRAM_START;
+
+/// How deep includes and macro expansions can nest, to stop recursive ones.
+const MAX_NESTING: usize = 16;
+
+const MNEMONICS: &[&str] = &[
+    "CLS", "RET", "SCD", "SCU", "SCR", "SCL", "EXIT", "LOW", "HIGH", "JP", "CALL", "SE", "SNE",
+    "SAVE", "LOAD", "LD", "ADD", "OR", "AND", "XOR", "SUB", "SHR", "SUBN", "SHL", "RND", "DRW",
+    "SKP", "SKNP", "PLANE", "AUDIO", "PITCH",
+];
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct AsmError {
+    pub file: String,
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.line == 0 {
+            write!(f, "{}: {}", self.file, self.message)
+        } else {
+            write!(
+                f,
+                "{}:{}:{}: {}",
+                self.file, self.line, self.column, self.message
+            )
+        }
+    }
+}
+
+impl Error for AsmError {}
+
+/// Assembles a program into a binary loaded at `PROGRAM_START`.
+///
+/// The source uses the mnemonics printed by the disassembler, one statement per line,
+/// with `;` starting a comment:
+///
+/// ```text
+/// define SPEED 2
+///
+/// macro move register amount
+///     ADD register, amount
+/// endm
+///
+/// start:  LD I, sprite
+///         DRW V0, V1, 5
+///         move V0, SPEED
+///         JP start
+/// sprite: db 0xF0, 0x90, 0x90, 0x90, 0xF0
+/// include "more.asm"
+/// ```
+///
+/// Includes are looked up relative to the current directory.
+pub fn assemble(source: &str) -> Result<Vec<u8>, AsmError> {
+    let mut preprocessor = Preprocessor::default();
+    preprocessor.source(&Rc::from("<source>"), Path::new("."), source, 0)?;
+    Assembler::default().assemble(&preprocessor.lines)
+}
+
+/// Assembles the program in `path`, includes being looked up relative to the including file.
+pub fn assemble_file<P: AsRef<Path>>(path: P) -> Result<Vec<u8>, AsmError> {
+    let path = path.as_ref();
+    let source = fs::read_to_string(path).map_err(|error| AsmError {
+        file: path.display().to_string(),
+        line: 0,
+        column: 0,
+        message: error.to_string(),
+    })?;
+    let mut preprocessor = Preprocessor::default();
+    preprocessor.source(
+        &Rc::from(path.display().to_string()),
+        &parent_directory(path),
+        &source,
+        0,
+    )?;
+    Assembler::default().assemble(&preprocessor.lines)
+}
+
+fn parent_directory(path: &Path) -> PathBuf {
+    path.parent()
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|| PathBuf::from("."))
+}
+
+/// A line of source, once includes and macros have been expanded.
+#[derive(Clone)]
+struct Line {
+    file: Rc<str>,
+    number: usize,
+    text: String,
+}
+
+impl Line {
+    fn error(&self, column: usize, message: String) -> AsmError {
+        AsmError {
+            file: self.file.to_string(),
+            line: self.number,
+            column,
+            message,
+        }
+    }
+
+    /// Column of `part`, a slice of the text of the line, or the first column for any other text.
+    fn column(&self, part: &str) -> usize {
+        (part.as_ptr() as usize)
+            .checked_sub(self.text.as_ptr() as usize)
+            .filter(|&offset| offset <= self.text.len())
+            .map_or(1, |offset| offset + 1)
+    }
+}
+
+struct Macro {
+    parameters: Vec<String>,
+    body: Vec<Line>,
+    directory: PathBuf,
+}
+
+/// Expands includes and macros into a flat list of lines.
+#[derive(Default)]
+struct Preprocessor {
+    macros: HashMap<String, Macro>,
+    lines: Vec<Line>,
+}
+
+impl Preprocessor {
+    fn source(
+        &mut self,
+        file: &Rc<str>,
+        directory: &Path,
+        source: &str,
+        depth: usize,
+    ) -> Result<(), AsmError> {
+        let mut lines = source.lines().enumerate().map(|(index, text)| Line {
+            file: file.clone(),
+            number: index + 1,
+            text: strip_comment(text).to_string(),
+        });
+        while let Some(line) = lines.next() {
+            let (_, statement) = split_label(&line.text);
+            let mut words = statement.split_whitespace();
+            if !words
+                .next()
+                .map_or(false, |word| word.eq_ignore_ascii_case("macro"))
+            {
+                self.line(line, directory, depth)?;
+                continue;
+            }
+            if statement.len() != line.text.trim_start().len() {
+                return Err(line.error(1, "a macro cannot be labelled".to_string()));
+            }
+            let name = match words.next() {
+                Some(name) if is_identifier(name) => name.to_string(),
+                Some(name) => {
+                    return Err(
+                        line.error(line.column(name), format!("invalid macro name `{}`", name))
+                    )
+                }
+                None => return Err(line.error(1, "missing macro name".to_string())),
+            };
+            if self.macros.contains_key(&name) {
+                return Err(line.error(1, format!("macro `{}` is already defined", name)));
+            }
+            let mut parameters = Vec::new();
+            for parameter in words
+                .flat_map(|word| word.split(','))
+                .filter(|p| !p.is_empty())
+            {
+                if !is_identifier(parameter) {
+                    return Err(line.error(
+                        line.column(parameter),
+                        format!("invalid macro parameter `{}`", parameter),
+                    ));
+                }
+                parameters.push(parameter.to_string());
+            }
+            let mut body = Vec::new();
+            loop {
+                let body_line = lines
+                    .next()
+                    .ok_or_else(|| line.error(1, format!("macro `{}` is missing `endm`", name)))?;
+                match body_line.text.split_whitespace().next() {
+                    Some(word) if word.eq_ignore_ascii_case("endm") => break,
+                    Some(word) if word.eq_ignore_ascii_case("macro") => {
+                        return Err(body_line.error(
+                            body_line.column(word),
+                            "macros cannot be defined inside macros".to_string(),
+                        ))
+                    }
+                    _ => body.push(body_line),
+                }
+            }
+            self.macros.insert(
+                name,
+                Macro {
+                    parameters,
+                    body,
+                    directory: directory.to_path_buf(),
+                },
+            );
+        }
+        Ok(())
+    }
+
+    fn line(&mut self, line: Line, directory: &Path, depth: usize) -> Result<(), AsmError> {
+        let (label, statement) = split_label(&line.text);
+        let mut words = statement.splitn(2, char::is_whitespace);
+        let word = words.next().unwrap_or("");
+        let operands = words.next().unwrap_or("").trim();
+        if word.eq_ignore_ascii_case("endm") {
+            return Err(line.error(line.column(word), "`endm` without `macro`".to_string()));
+        }
+        let is_include = word.eq_ignore_ascii_case("include");
+        if !is_include && !self.macros.contains_key(word) {
+            self.lines.push(line);
+            return Ok(());
+        }
+        if depth >= MAX_NESTING {
+            return Err(line.error(
+                line.column(word),
+                "includes or macros nested too deeply".to_string(),
+            ));
+        }
+        if let Some(label) = label {
+            // keep the label where it is, the expansion follows it
+            self.lines.push(Line {
+                text: format!("{}:", label),
+                ..line.clone()
+            });
+        }
+
+        if is_include {
+            let path = operands
+                .strip_prefix('"')
+                .and_then(|path| path.strip_suffix('"'))
+                .ok_or_else(|| {
+                    line.error(
+                        line.column(word),
+                        "expected a quoted path after `include`".to_string(),
+                    )
+                })?;
+            let path = directory.join(path);
+            let source = fs::read_to_string(&path).map_err(|error| {
+                line.error(
+                    line.column(operands),
+                    format!("cannot include {}: {}", path.display(), error),
+                )
+            })?;
+            let file = Rc::from(path.display().to_string());
+            return self.source(&file, &parent_directory(&path), &source, depth + 1);
+        }
+
+        let definition = &self.macros[word];
+        let arguments: Vec<&str> = if operands.is_empty() {
+            Vec::new()
+        } else {
+            operands.split(',').map(str::trim).collect()
+        };
+        if arguments.len() != definition.parameters.len() {
+            return Err(line.error(
+                line.column(word),
+                format!(
+                    "macro `{}` takes {} arguments, {} given",
+                    word,
+                    definition.parameters.len(),
+                    arguments.len()
+                ),
+            ));
+        }
+        let macro_directory = definition.directory.clone();
+        let expansion: Vec<Line> = definition
+            .body
+            .iter()
+            .map(|body_line| Line {
+                text: substitute(&body_line.text, &definition.parameters, &arguments),
+                ..body_line.clone()
+            })
+            .collect();
+        for expanded in expansion {
+            self.line(expanded, &macro_directory, depth + 1)?;
+        }
+        Ok(())
+    }
+}
+
+/// Replaces every parameter of a macro by its argument.
+fn substitute(text: &str, parameters: &[String], arguments: &[&str]) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find(is_identifier_start) {
+        result.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let end = rest
+            .find(|c: char| !is_identifier_char(c))
+            .unwrap_or(rest.len());
+        let word = &rest[..end];
+        match parameters.iter().position(|parameter| parameter == word) {
+            Some(index) => result.push_str(arguments[index]),
+            None => result.push_str(word),
+        }
+        rest = &rest[end..];
+    }
+    result.push_str(rest);
+    result
+}
+
+fn strip_comment(text: &str) -> &str {
+    text.split(';').next().unwrap()
+}
+
+/// Splits a line into its label, if any, and the statement that follows it.
+fn split_label(text: &str) -> (Option<&str>, &str) {
+    let trimmed = text.trim_start();
+    if let Some(end) = trimmed.find(':') {
+        let label = &trimmed[..end];
+        if is_identifier(label) {
+            return (Some(label), trimmed[end + 1..].trim());
+        }
+    }
+    (None, trimmed.trim_end())
+}
+
+fn is_identifier_start(c: char) -> bool {
+    c.is_ascii_alphabetic() || c == '_'
+}
+
+fn is_identifier_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_' || c == '.'
+}
+
+fn is_identifier(word: &str) -> bool {
+    word.starts_with(is_identifier_start) && word.chars().all(is_identifier_char)
+}
+
+/// A number along with the column it was written at.
+#[derive(Clone, Copy, PartialEq, Debug)]
+struct Number {
+    value: i64,
+    column: usize,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Operand {
+    V(u8),
+    I,
+    Dt,
+    St,
+    K,
+    F,
+    Hf,
+    B,
+    R,
+    Memory,
+    Long(Number),
+    Value(Number),
+}
+
+/// A statement whose address is known, assembled once every label is.
+struct Statement<'a> {
+    line: &'a Line,
+    mnemonic: &'a str,
+    operands: Vec<&'a str>,
+}
+
+#[derive(Default)]
+struct Assembler {
+    symbols: HashMap<String, i64>,
+}
+
+impl Assembler {
+    fn assemble(&mut self, lines: &[Line]) -> Result<Vec<u8>, AsmError> {
+        // first pass: lay out statements to find the address of every label
+        let mut statements = Vec::new();
+        let mut size = 0;
+        for line in lines {
+            let (label, statement) = split_label(&line.text);
+            if let Some(label) = label {
+                self.define(line, label, (PROGRAM_START + size) as i64)?;
+            }
+            if statement.is_empty() {
+                continue;
+            }
+            let mut words = statement.splitn(2, char::is_whitespace);
+            let mnemonic = words.next().unwrap();
+            let rest = words.next().unwrap_or("").trim();
+            if mnemonic.eq_ignore_ascii_case("define") {
+                let mut words = rest.splitn(2, char::is_whitespace);
+                let name = words
+                    .next()
+                    .filter(|name| !name.is_empty())
+                    .ok_or_else(|| {
+                        line.error(line.column(mnemonic), "missing constant name".to_string())
+                    })?;
+                let expression = words.next().unwrap_or("").trim();
+                let value = self.evaluate(line, expression)?;
+                self.define(line, name, value)?;
+                continue;
+            }
+            let operands: Vec<&str> = if rest.is_empty() {
+                Vec::new()
+            } else {
+                rest.split(',').map(str::trim).collect()
+            };
+            size += if mnemonic.eq_ignore_ascii_case("db") {
+                operands.len()
+            } else if mnemonic.eq_ignore_ascii_case("dw") {
+                2 * operands.len()
+            } else if operands
+                .iter()
+                .any(|operand| long_address(operand).is_some())
+            {
+                4
+            } else {
+                2
+            };
+            statements.push(Statement {
+                line,
+                mnemonic,
+                operands,
+            });
+        }
+
+        // second pass: encode every statement
+        let mut program = Vec::with_capacity(size);
+        for statement in &statements {
+            let line = statement.line;
+            if statement.mnemonic.eq_ignore_ascii_case("db") {
+                for operand in &statement.operands {
+                    let value = self.number(line, operand)?;
+                    program.push(in_range(line, value, -0x80, 0xFF, "a byte")? as u8);
+                }
+            } else if statement.mnemonic.eq_ignore_ascii_case("dw") {
+                for operand in &statement.operands {
+                    let value = self.number(line, operand)?;
+                    let word = in_range(line, value, -0x8000, 0xFFFF, "a word")? as u16;
+                    program.extend_from_slice(&word.to_be_bytes());
+                }
+            } else {
+                let (instruction, long_address) = self.instruction(statement)?;
+                program.extend_from_slice(&instruction.encode().to_be_bytes());
+                if let Some(address) = long_address {
+                    program.extend_from_slice(&address.to_be_bytes());
+                }
+            }
+        }
+        Ok(program)
+    }
+
+    fn define(&mut self, line: &Line, name: &str, value: i64) -> Result<(), AsmError> {
+        if !is_identifier(name) || parse_register(name).is_some() {
+            return Err(line.error(line.column(name), format!("invalid name `{}`", name)));
+        }
+        if self.symbols.insert(name.to_string(), value).is_some() {
+            return Err(line.error(line.column(name), format!("`{}` is already defined", name)));
+        }
+        Ok(())
+    }
+
+    fn instruction(&self, statement: &Statement) -> Result<(Instruction, Option<u16>), AsmError> {
+        use Instruction::*;
+        use Operand::*;
+
+        let line = statement.line;
+        let operands = statement
+            .operands
+            .iter()
+            .map(|operand| self.operand(line, operand))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mnemonic = statement.mnemonic.to_ascii_uppercase();
+        let mut long = None;
+        let instruction = match (mnemonic.as_str(), operands.as_slice()) {
+            ("CLS", []) => Cls,
+            ("RET", []) => Ret,
+            ("SCD", [Value(n)]) => ScrollDown(nibble(line, *n)?),
+            ("SCU", [Value(n)]) => ScrollUp(nibble(line, *n)?),
+            ("SCR", []) => ScrollRight,
+            ("SCL", []) => ScrollLeft,
+            ("EXIT", []) => Exit,
+            ("LOW", []) => Low,
+            ("HIGH", []) => High,
+            ("JP", [Value(nnn)]) => Jp(address(line, *nnn)?),
+            ("JP", [V(0), Value(nnn)]) => JpV0(address(line, *nnn)?),
+            ("CALL", [Value(nnn)]) => Call(address(line, *nnn)?),
+            ("SE", [V(x), Value(kk)]) => SeByte(*x, byte(line, *kk)?),
+            ("SE", [V(x), V(y)]) => SeRegister(*x, *y),
+            ("SNE", [V(x), Value(kk)]) => SneByte(*x, byte(line, *kk)?),
+            ("SNE", [V(x), V(y)]) => SneRegister(*x, *y),
+            ("SAVE", [V(x), V(y)]) => Save(*x, *y),
+            ("LOAD", [V(x), V(y)]) => Load(*x, *y),
+            ("LD", [V(x), Value(kk)]) => LdByte(*x, byte(line, *kk)?),
+            ("LD", [V(x), V(y)]) => LdRegister(*x, *y),
+            ("LD", [I, Value(nnn)]) => LdI(address(line, *nnn)?),
+            ("LD", [I, Long(nnnn)]) => {
+                long = Some(in_range(line, *nnnn, 0, 0xFFFF, "an address")? as u16);
+                LdILong
+            }
+            ("LD", [V(x), Dt]) => LdVxDt(*x),
+            ("LD", [V(x), K]) => LdVxK(*x),
+            ("LD", [Dt, V(x)]) => LdDtVx(*x),
+            ("LD", [St, V(x)]) => LdStVx(*x),
+            ("LD", [F, V(x)]) => LdF(*x),
+            ("LD", [Hf, V(x)]) => LdHf(*x),
+            ("LD", [B, V(x)]) => LdB(*x),
+            ("LD", [Memory, V(x)]) => LdMemoryVx(*x),
+            ("LD", [V(x), Memory]) => LdVxMemory(*x),
+            ("LD", [R, V(x)]) => LdRVx(*x),
+            ("LD", [V(x), R]) => LdVxR(*x),
+            ("ADD", [V(x), Value(kk)]) => AddByte(*x, byte(line, *kk)?),
+            ("ADD", [V(x), V(y)]) => AddRegister(*x, *y),
+            ("ADD", [I, V(x)]) => AddI(*x),
+            ("OR", [V(x), V(y)]) => Or(*x, *y),
+            ("AND", [V(x), V(y)]) => And(*x, *y),
+            ("XOR", [V(x), V(y)]) => Xor(*x, *y),
+            ("SUB", [V(x), V(y)]) => Sub(*x, *y),
+            ("SHR", [V(x), V(y)]) => Shr(*x, *y),
+            ("SHR", [V(x)]) => Shr(*x, *x),
+            ("SUBN", [V(x), V(y)]) => Subn(*x, *y),
+            ("SHL", [V(x), V(y)]) => Shl(*x, *y),
+            ("SHL", [V(x)]) => Shl(*x, *x),
+            ("RND", [V(x), Value(kk)]) => Rnd(*x, byte(line, *kk)?),
+            ("DRW", [V(x), V(y), Value(n)]) => Drw(*x, *y, nibble(line, *n)?),
+            ("SKP", [V(x)]) => Skp(*x),
+            ("SKNP", [V(x)]) => Sknp(*x),
+            ("PLANE", [Value(n)]) => Plane(nibble(line, *n)?),
+            ("AUDIO", []) => Audio,
+            ("PITCH", [V(x)]) => Pitch(*x),
+            (mnemonic, _) => {
+                let message = if MNEMONICS.contains(&mnemonic) {
+                    format!("invalid operands for `{}`", statement.mnemonic)
+                } else {
+                    format!("unknown instruction `{}`", statement.mnemonic)
+                };
+                return Err(line.error(line.column(statement.mnemonic), message));
+            }
+        };
+        // the operands are range checked already, so this only trips on a mismatch with the decoder
+        if decode(instruction.encode()) != Ok(instruction) {
+            return Err(line.error(
+                line.column(statement.mnemonic),
+                format!("`{}` cannot be encoded", instruction),
+            ));
+        }
+        Ok((instruction, long))
+    }
+
+    fn operand(&self, line: &Line, operand: &str) -> Result<Operand, AsmError> {
+        if let Some(x) = parse_register(operand) {
+            return Ok(Operand::V(x));
+        }
+        let keyword = match operand.to_ascii_uppercase().as_str() {
+            "I" => Some(Operand::I),
+            "DT" => Some(Operand::Dt),
+            "ST" => Some(Operand::St),
+            "K" => Some(Operand::K),
+            "F" => Some(Operand::F),
+            "HF" => Some(Operand::Hf),
+            "B" => Some(Operand::B),
+            "R" => Some(Operand::R),
+            "[I]" => Some(Operand::Memory),
+            _ => None,
+        };
+        if let Some(keyword) = keyword {
+            return Ok(keyword);
+        }
+        match long_address(operand) {
+            Some(address) => Ok(Operand::Long(self.number(line, address)?)),
+            None => Ok(Operand::Value(self.number(line, operand)?)),
+        }
+    }
+
+    fn number(&self, line: &Line, expression: &str) -> Result<Number, AsmError> {
+        Ok(Number {
+            value: self.evaluate(line, expression)?,
+            column: line.column(expression),
+        })
+    }
+
+    /// Evaluates a sum of numbers and symbols, e.g. `sprite + 5`.
+    fn evaluate(&self, line: &Line, expression: &str) -> Result<i64, AsmError> {
+        let mut total: i64 = 0;
+        let mut negative = false;
+        let mut rest = expression.trim_start();
+        if let Some(stripped) = rest.strip_prefix('-') {
+            negative = true;
+            rest = stripped.trim_start();
+        }
+        loop {
+            let end = rest
+                .find(|c: char| !is_identifier_char(c))
+                .unwrap_or(rest.len());
+            let term = &rest[..end];
+            if term.is_empty() {
+                return Err(line.error(line.column(rest), "expected a value".to_string()));
+            }
+            let value = self.term(line, term)?;
+            total = if negative {
+                total.checked_sub(value)
+            } else {
+                total.checked_add(value)
+            }
+            .ok_or_else(|| line.error(line.column(term), "value out of range".to_string()))?;
+
+            rest = rest[end..].trim_start();
+            let mut characters = rest.chars();
+            match characters.next() {
+                None => return Ok(total),
+                Some('+') => negative = false,
+                Some('-') => negative = true,
+                Some(c) => return Err(line.error(line.column(rest), format!("unexpected `{}`", c))),
+            }
+            rest = characters.as_str().trim_start();
+        }
+    }
+
+    fn term(&self, line: &Line, term: &str) -> Result<i64, AsmError> {
+        if term.starts_with(|c: char| c.is_ascii_digit()) {
+            let lowercase = term.to_ascii_lowercase();
+            let parsed = if let Some(digits) = lowercase.strip_prefix("0x") {
+                i64::from_str_radix(digits, 16)
+            } else if let Some(digits) = lowercase.strip_prefix("0b") {
+                i64::from_str_radix(digits, 2)
+            } else {
+                lowercase.parse()
+            };
+            parsed.map_err(|_| line.error(line.column(term), format!("invalid number `{}`", term)))
+        } else {
+            self.symbols
+                .get(term)
+                .copied()
+                .ok_or_else(|| line.error(line.column(term), format!("unknown symbol `{}`", term)))
+        }
+    }
+}
+
+/// The address of a `LONG address` operand.
+fn long_address(operand: &str) -> Option<&str> {
+    let mut words = operand.splitn(2, char::is_whitespace);
+    if words.next()?.eq_ignore_ascii_case("long") {
+        words.next().map(str::trim)
+    } else {
+        None
+    }
+}
+
+fn parse_register(operand: &str) -> Option<u8> {
+    let mut characters = operand.chars();
+    match (characters.next(), characters.next(), characters.next()) {
+        (Some('V'), Some(digit), None) | (Some('v'), Some(digit), None) => {
+            digit.to_digit(16).map(|x| x as u8)
+        }
+        _ => None,
+    }
+}
+
+fn in_range(line: &Line, number: Number, min: i64, max: i64, what: &str) -> Result<i64, AsmError> {
+    if (min..=max).contains(&number.value) {
+        Ok(number.value)
+    } else {
+        Err(line.error(
+            number.column,
+            format!("{} does not fit in {}", number.value, what),
+        ))
+    }
+}
+
+fn nibble(line: &Line, number: Number) -> Result<u8, AsmError> {
+    Ok(in_range(line, number, 0, 0xF, "a nibble")? as u8)
+}
+
+fn byte(line: &Line, number: Number) -> Result<u8, AsmError> {
+    // negative bytes are two's complement, e.g. to decrement with `ADD Vx, -1`
+    Ok(in_range(line, number, -0x80, 0xFF, "a byte")? as u8)
+}
+
+fn address(line: &Line, number: Number) -> Result<u16, AsmError> {
+    Ok(in_range(line, number, 0, 0xFFF, "an address")? as u16)
+}
@@ -1,5 +1,191 @@
+use std::f32::consts::PI;
+
+use crate::rng::Rng;
+
+pub const PATTERN_SIZE: usize = 16;
+pub const DEFAULT_PITCH: u8 = 64;
+const PATTERN_BITS: f32 = 128.0;
+
+/// 128 one-bit samples played in a loop by XO-CHIP programs.
+pub type Pattern = [u8; PATTERN_SIZE];
+
+/// Rate, in samples per second, at which the bits of a pattern are played for a given pitch.
+pub fn playback_rate(pitch: u8) -> f32 {
+    4000.0 * 2f32.powf((pitch as f32 - 64.0) / 48.0)
+}
+
+/// What the buzzer plays while the sound timer runs.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Sound {
+    /// The pattern loaded by XO-CHIP programs, which replaces the tone.
+    pub pattern: Option<Pattern>,
+    pub pitch: u8,
+}
+
+/// A change of the buzzer, stamped with the emulated time it happens at.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct AudioEvent {
+    /// Frame, counted from the start of the run, at the start of which the change happens.
+    pub frame: u64,
+    /// What the buzzer plays from then on, nothing once it stops.
+    pub sound: Option<Sound>,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Waveform {
+    Square,
+    Triangle,
+    Sine,
+    /// Random levels, changing at the frequency of the tone.
+    Noise,
+}
+
+impl Waveform {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "square" => Some(Waveform::Square),
+            "triangle" => Some(Waveform::Triangle),
+            "sine" => Some(Waveform::Sine),
+            "noise" => Some(Waveform::Noise),
+            _ => None,
+        }
+    }
+}
+
+/// How the buzzer sounds. XO-CHIP patterns replace the waveform and frequency, but keep the
+/// volume and envelope.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Tone {
+    pub waveform: Waveform,
+    /// In Hz.
+    pub frequency: f32,
+    /// From 0 to 1.
+    pub volume: f32,
+    /// Seconds taken by the volume to rise when the buzzer starts, and to fall when it stops,
+    /// which avoids clicks.
+    pub attack: f32,
+    pub release: f32,
+}
+
+impl Default for Tone {
+    fn default() -> Self {
+        Tone {
+            waveform: Waveform::Square,
+            frequency: 440.0,
+            volume: 0.25,
+            attack: 0.005,
+            release: 0.005,
+        }
+    }
+}
 
 pub trait Audio {
-    fn play(&self);
-    fn stop(&self);
-}
\ No newline at end of file
+    /// Sets how the buzzer sounds from then on.
+    fn set_tone(&mut self, tone: Tone);
+    /// Queues changes of the buzzer, which are played as far apart as the frames they are
+    /// stamped with, see `Chip8::take_audio_events`.
+    fn queue(&mut self, events: &[AudioEvent]);
+    /// Silences the buzzer at once and drops the queued changes, e.g. when the emulation
+    /// pauses or goes back in time.
+    fn stop(&mut self);
+}
+
+/// Generates the samples of the buzzer, for live playback as well as offline rendering.
+pub struct Synth {
+    sample_rate: f32,
+    tone: Tone,
+    phase: f32,
+    // XO-CHIP programs replace the waveform with their own looping pattern
+    pattern: Option<Pattern>,
+    pitch: u8,
+    playing: bool,
+    // the envelope, from 0 when silent to 1 once the attack is over
+    level: f32,
+    noise: Rng,
+    noise_sample: f32,
+}
+
+impl Synth {
+    pub fn new(sample_rate: u32, tone: Tone) -> Self {
+        Synth {
+            sample_rate: sample_rate as f32,
+            tone,
+            phase: 0.0,
+            pattern: None,
+            pitch: DEFAULT_PITCH,
+            playing: false,
+            level: 0.0,
+            noise: Rng::new(0),
+            noise_sample: 0.0,
+        }
+    }
+
+    pub fn set_tone(&mut self, tone: Tone) {
+        self.tone = tone;
+    }
+
+    /// Starts, changes or stops the buzzer, the volume ramping up or down from the next sample.
+    pub fn set_sound(&mut self, sound: Option<Sound>) {
+        // a stopped buzzer fades out with the sound it played
+        if let Some(sound) = sound {
+            self.pattern = sound.pattern;
+            self.pitch = sound.pitch;
+        }
+        self.playing = sound.is_some();
+    }
+
+    /// Next sample, between -1 and 1.
+    pub fn sample(&mut self) -> f32 {
+        self.update_level();
+        if self.level == 0.0 {
+            return 0.0;
+        }
+        let (wave, phase_inc) = match &self.pattern {
+            Some(pattern) => {
+                let bit = (self.phase * PATTERN_BITS) as usize;
+                let high = pattern[bit / 8] & (0x80 >> (bit % 8)) != 0;
+                let wave = if high { 1.0 } else { -1.0 };
+                (
+                    wave,
+                    playback_rate(self.pitch) / PATTERN_BITS / self.sample_rate,
+                )
+            }
+            None => (self.wave(), self.tone.frequency / self.sample_rate),
+        };
+        let phase = self.phase + phase_inc;
+        if phase >= 1.0 {
+            self.noise_sample = self.noise.next_u8() as f32 / 127.5 - 1.0;
+        }
+        self.phase = phase % 1.0;
+        wave * self.tone.volume * self.level
+    }
+
+    fn wave(&self) -> f32 {
+        match self.tone.waveform {
+            Waveform::Square => {
+                if self.phase <= 0.5 {
+                    1.0
+                } else {
+                    -1.0
+                }
+            }
+            Waveform::Triangle => 4.0 * (self.phase - 0.5).abs() - 1.0,
+            Waveform::Sine => (2.0 * PI * self.phase).sin(),
+            Waveform::Noise => self.noise_sample,
+        }
+    }
+
+    fn update_level(&mut self) {
+        let (target, seconds) = if self.playing {
+            (1.0, self.tone.attack)
+        } else {
+            (0.0, self.tone.release)
+        };
+        let step = 1.0 / (seconds * self.sample_rate).max(1.0);
+        self.level = if self.level < target {
+            (self.level + step).min(target)
+        } else {
+            (self.level - step).max(target)
+        };
+    }
+}
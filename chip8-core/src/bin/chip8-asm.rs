@@ -0,0 +1,55 @@
+use chip8::asm::assemble_file;
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process;
+
+const USAGE: &str = "usage: chip8-asm [--output rom_path] source_path";
+
+struct Options {
+    source_path: String,
+    output_path: Option<String>,
+}
+
+fn main() {
+    let options = match parse_arguments(env::args().skip(1)) {
+        Some(options) => options,
+        None => {
+            println!("{}", USAGE);
+            process::exit(2);
+        }
+    };
+    if let Err(message) = run(&options) {
+        eprintln!("chip8-asm: {}", message);
+        process::exit(1);
+    }
+}
+
+fn run(options: &Options) -> Result<(), String> {
+    let program = assemble_file(&options.source_path).map_err(|error| error.to_string())?;
+    // by default, the ROM is written next to its source
+    let output_path = match &options.output_path {
+        Some(path) => path.clone(),
+        None => Path::new(&options.source_path)
+            .with_extension("ch8")
+            .display()
+            .to_string(),
+    };
+    fs::write(&output_path, program)
+        .map_err(|error| format!("cannot write {}: {}", output_path, error))
+}
+
+fn parse_arguments<I: Iterator<Item = String>>(mut arguments: I) -> Option<Options> {
+    let mut source_path = None;
+    let mut output_path = None;
+    while let Some(argument) = arguments.next() {
+        match argument.as_str() {
+            "--output" => output_path = Some(arguments.next()?),
+            _ => source_path = Some(argument),
+        }
+    }
+    Some(Options {
+        source_path: source_path?,
+        output_path,
+    })
+}
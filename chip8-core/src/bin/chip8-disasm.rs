@@ -0,0 +1,107 @@
+use chip8::instruction::{decode, Extension, Instruction};
+use chip8::ram::PROGRAM_START;
+use std::env;
+use std::fs;
+use std::io::{self, BufWriter, Write};
+use std::process;
+
+const USAGE: &str = "usage: chip8-disasm [--schip|--xo] rom_path";
+
+struct Options {
+    rom_file_path: String,
+    extension: Extension,
+}
+
+fn main() {
+    let options = match parse_arguments(env::args().skip(1)) {
+        Some(options) => options,
+        None => {
+            println!("{}", USAGE);
+            process::exit(2);
+        }
+    };
+    let program = match fs::read(&options.rom_file_path) {
+        Ok(program) => program,
+        Err(error) => {
+            eprintln!(
+                "chip8-disasm: cannot read {}: {}",
+                options.rom_file_path, error
+            );
+            process::exit(1);
+        }
+    };
+    let stdout = io::stdout();
+    let mut writer = BufWriter::new(stdout.lock());
+    if let Err(error) = disassemble(&program, options.extension, &mut writer) {
+        eprintln!("chip8-disasm: {}", error);
+        process::exit(1);
+    }
+}
+
+/// Prints the address, raw bytes and mnemonic of every instruction.
+/// Bytes that do not decode to an instruction of the enabled extensions are printed as data.
+fn disassemble<W: Write>(program: &[u8], extension: Extension, writer: &mut W) -> io::Result<()> {
+    let mut offset = 0;
+    while offset < program.len() {
+        let address = PROGRAM_START + offset;
+        if offset + 1 == program.len() {
+            writeln!(
+                writer,
+                "{:04X}  {:02X}         DB 0x{:02X}",
+                address, program[offset], program[offset]
+            )?;
+            break;
+        }
+        let opcode = u16::from_be_bytes([program[offset], program[offset + 1]]);
+        let instruction = decode(opcode)
+            .ok()
+            .filter(|instruction| instruction.extension() <= extension)
+            .filter(|instruction| offset + instruction.size() <= program.len());
+        match instruction {
+            Some(Instruction::LdILong) => {
+                let long_address = u16::from_be_bytes([program[offset + 2], program[offset + 3]]);
+                writeln!(
+                    writer,
+                    "{:04X}  {:04X} {:04X}  {} 0x{:04X}",
+                    address,
+                    opcode,
+                    long_address,
+                    Instruction::LdILong,
+                    long_address
+                )?;
+            }
+            Some(instruction) => {
+                writeln!(
+                    writer,
+                    "{:04X}  {:04X}       {}",
+                    address, opcode, instruction
+                )?;
+            }
+            None => {
+                writeln!(
+                    writer,
+                    "{:04X}  {:04X}       DW 0x{:04X}",
+                    address, opcode, opcode
+                )?;
+            }
+        }
+        offset += instruction.map_or(2, |instruction| instruction.size());
+    }
+    writer.flush()
+}
+
+fn parse_arguments<I: Iterator<Item = String>>(arguments: I) -> Option<Options> {
+    let mut rom_file_path = None;
+    let mut extension = Extension::Chip8;
+    for argument in arguments {
+        match argument.as_str() {
+            "--schip" => extension = Extension::SuperChip,
+            "--xo" => extension = Extension::XoChip,
+            _ => rom_file_path = Some(argument),
+        }
+    }
+    Some(Options {
+        rom_file_path: rom_file_path?,
+        extension,
+    })
+}
@@ -0,0 +1,544 @@
+use chip8::access::{Access, AccessTracker, Watch};
+use chip8::audio::{Tone, Waveform};
+use chip8::difftest::{compare, parse_reference};
+use chip8::gdb::GdbServer;
+use chip8::hash::fnv1a;
+use chip8::image::{self, ImageFormat, Palette, DEFAULT_PALETTE};
+use chip8::keyboard::{KeyState, Keys, NUM_KEYS};
+use chip8::movie::Movie;
+use chip8::script::KeyScript;
+use chip8::trace::{TraceFormat, Tracer};
+use chip8::video::{VideoFormat, VideoRecorder};
+use chip8::wav::{AudioRecorder, DEFAULT_SAMPLE_RATE};
+use chip8::{Chip8, Quirks, State, DEFAULT_CYCLES_PER_FRAME};
+use std::cell::RefCell;
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Write};
+use std::ops::Range;
+use std::process;
+use std::rc::Rc;
+
+const USAGE: &str =
+    "usage: chip8-headless [--quirks vip|chip48|schip|octo] [--cycles cycles_per_frame] \
+[--seed seed] [--frames frames] [--keys key_script] [--output dump_path] [--gdb port] \
+[--watch first[-last][:r|w|rw|x]]... [--memory-report report_path] \
+[--trace trace_path] [--trace-format text|binary] [--trace-range first[-last]] \
+[--reference trace_path] [--record movie_path | --play movie_path] \
+[--screenshot png_or_ppm_path] [--video gif_or_y4m_path] [--audio wav_path] [--scale scale] \
+[--palette colors] [--waveform square|triangle|sine|noise] [--tone frequency] [--volume volume] \
+[--attack milliseconds] [--release milliseconds] rom_path";
+
+struct Options {
+    rom_file_path: String,
+    quirks: Quirks,
+    cycles_per_frame: usize,
+    seed: u64,
+    frames: usize,
+    key_script_path: Option<String>,
+    output_path: Option<String>,
+    gdb_port: Option<u16>,
+    watchpoints: Vec<(Range<usize>, Watch)>,
+    memory_report_path: Option<String>,
+    trace_path: Option<String>,
+    trace_format: TraceFormat,
+    trace_range: Option<Range<usize>>,
+    reference_path: Option<String>,
+    record_path: Option<String>,
+    play_path: Option<String>,
+    screenshot_path: Option<String>,
+    video_path: Option<String>,
+    audio_path: Option<String>,
+    scale: usize,
+    palette: Palette,
+    tone: Tone,
+}
+
+/// Where the keys held on every frame come from.
+enum Input {
+    Script(KeyScript),
+    Movie(Movie),
+}
+
+impl Input {
+    fn keys(&self, frame: usize) -> Keys {
+        match self {
+            Input::Script(script) => script.keys(frame),
+            // nothing is held once the movie is over
+            Input::Movie(movie) => movie.keys(frame).unwrap_or([KeyState::Up; NUM_KEYS]),
+        }
+    }
+}
+
+fn main() {
+    let options = match parse_arguments(env::args().skip(1)) {
+        Some(options) => options,
+        None => {
+            println!("{}", USAGE);
+            process::exit(2);
+        }
+    };
+    if let Err(message) = run(&options) {
+        eprintln!("chip8-headless: {}", message);
+        process::exit(1);
+    }
+}
+
+fn run(options: &Options) -> Result<(), String> {
+    let program = fs::read(&options.rom_file_path)
+        .map_err(|error| format!("cannot read {}: {}", options.rom_file_path, error))?;
+    let input = match (&options.key_script_path, &options.play_path) {
+        (Some(path), _) => {
+            let source = fs::read_to_string(path)
+                .map_err(|error| format!("cannot read {}: {}", path, error))?;
+            Input::Script(
+                KeyScript::parse(&source).map_err(|error| format!("{}: {}", path, error))?,
+            )
+        }
+        (None, Some(path)) => {
+            let movie = File::open(path)
+                .and_then(|file| Movie::read(BufReader::new(file)))
+                .map_err(|error| format!("cannot read {}: {}", path, error))?;
+            if !movie.matches(&program) {
+                return Err(format!("{} was recorded with another ROM", path));
+            }
+            Input::Movie(movie)
+        }
+        (None, None) => Input::Script(KeyScript::default()),
+    };
+
+    // a movie brings the settings it was recorded with
+    let (mut computer, frames) = match &input {
+        Input::Movie(movie) => (
+            movie.machine(&program).map_err(|error| error.to_string())?,
+            movie.len(),
+        ),
+        Input::Script(_) => {
+            let mut computer = Chip8::new(&program, options.quirks, options.seed)
+                .map_err(|error| error.to_string())?;
+            computer.set_cycles_per_frame(options.cycles_per_frame);
+            (computer, options.frames)
+        }
+    };
+    let mut recording = options
+        .record_path
+        .as_ref()
+        .map(|_| Movie::new(&program, &computer, options.seed));
+    let mut video = match &options.video_path {
+        Some(path) => {
+            let file =
+                File::create(path).map_err(|error| format!("cannot create {}: {}", path, error))?;
+            let format = VideoFormat::from_path(path).unwrap();
+            let video =
+                VideoRecorder::new(BufWriter::new(file), format, options.scale, options.palette)
+                    .map_err(|error| format!("cannot write {}: {}", path, error))?;
+            Some(video)
+        }
+        None => None,
+    };
+    let mut audio = options
+        .audio_path
+        .as_ref()
+        .map(|_| AudioRecorder::new(DEFAULT_SAMPLE_RATE, options.tone, &computer));
+    let tracker = if options.watchpoints.is_empty() && options.memory_report_path.is_none() {
+        None
+    } else {
+        let mut tracker = AccessTracker::new();
+        for (range, watch) in &options.watchpoints {
+            tracker.add_watchpoint(range.clone(), *watch);
+        }
+        let tracker = Rc::new(RefCell::new(tracker));
+        computer.state.ram.set_hook(Some(tracker.clone()));
+        Some(tracker)
+    };
+    let mut monitor = tracker.as_ref().map(|tracker| MemoryMonitor {
+        tracker: tracker.clone(),
+        reported: 0,
+    });
+
+    let tracer = match &options.trace_path {
+        Some(path) => {
+            let file =
+                File::create(path).map_err(|error| format!("cannot create {}: {}", path, error))?;
+            let mut tracer = Tracer::new(BufWriter::new(file), options.trace_format);
+            if let Some(range) = &options.trace_range {
+                tracer.set_range(range.clone());
+            }
+            let tracer = Rc::new(RefCell::new(tracer));
+            computer.set_tracer(Some(tracer.clone()));
+            Some(tracer)
+        }
+        None => None,
+    };
+
+    let result = match (options.gdb_port, &options.reference_path) {
+        // the client decides how long the program runs
+        (Some(port), _) => debug(&mut computer, &input, port),
+        // and the reference trace does otherwise
+        (None, Some(path)) => check_reference(&mut computer, &input, path),
+        (None, None) => run_frames(
+            &mut computer,
+            &input,
+            frames,
+            &mut monitor,
+            &mut recording,
+            &mut video,
+            &mut audio,
+        ),
+    };
+    if let (Some(path), Some(movie)) = (&options.record_path, &recording) {
+        File::create(path)
+            .and_then(|file| {
+                let mut writer = BufWriter::new(file);
+                movie.write(&mut writer)?;
+                writer.flush()
+            })
+            .map_err(|error| format!("cannot write {}: {}", path, error))?;
+    }
+    if let (Some(path), Some(video)) = (&options.video_path, video) {
+        video
+            .finish()
+            .map_err(|error| format!("cannot write {}: {}", path, error))?;
+    }
+    if let (Some(path), Some(audio)) = (&options.audio_path, &audio) {
+        File::create(path)
+            .and_then(|file| {
+                let mut writer = BufWriter::new(file);
+                audio.write(&mut writer)?;
+                writer.flush()
+            })
+            .map_err(|error| format!("cannot write {}: {}", path, error))?;
+    }
+    if let Some(monitor) = &mut monitor {
+        monitor.report(None);
+    }
+    if let (Some(path), Some(tracer)) = (&options.trace_path, &tracer) {
+        tracer
+            .borrow_mut()
+            .finish()
+            .map_err(|error| format!("cannot write {}: {}", path, error))?;
+    }
+    if let (Some(path), Some(tracker)) = (&options.memory_report_path, &tracker) {
+        File::create(path)
+            .and_then(|file| {
+                write_memory_report(
+                    &tracker.borrow(),
+                    computer.state.ram.len(),
+                    &mut BufWriter::new(file),
+                )
+            })
+            .map_err(|error| format!("cannot write {}: {}", path, error))?;
+    }
+
+    if let Some(path) = &options.screenshot_path {
+        image::save(
+            path,
+            &computer.state.display_buffer,
+            options.scale,
+            &options.palette,
+        )
+        .map_err(|error| format!("cannot write {}: {}", path, error))?;
+    }
+
+    // the machine is dumped even when it crashed, to help finding out why
+    let written = match &options.output_path {
+        Some(path) => File::create(path)
+            .and_then(|file| dump(&computer.state, &mut BufWriter::new(file)))
+            .map_err(|error| format!("cannot write {}: {}", path, error)),
+        None => dump(&computer.state, &mut io::stdout().lock())
+            .map_err(|error| format!("cannot write dump: {}", error)),
+    };
+    result.and(written)
+}
+
+fn run_frames(
+    computer: &mut Chip8,
+    input: &Input,
+    frames: usize,
+    monitor: &mut Option<MemoryMonitor>,
+    recording: &mut Option<Movie>,
+    video: &mut Option<VideoRecorder<BufWriter<File>>>,
+    audio: &mut Option<AudioRecorder>,
+) -> Result<(), String> {
+    for frame in 0..frames {
+        let keys = input.keys(frame);
+        if let Some(movie) = recording {
+            movie.record(&keys);
+        }
+        let result = computer.run_frame(&keys);
+        if let Some(monitor) = monitor {
+            monitor.report(Some(frame));
+        }
+        result.map_err(|error| format!("frame {}: {}", frame, error))?;
+        if let Some(video) = video {
+            video
+                .frame(&computer.state.display_buffer)
+                .map_err(|error| format!("frame {}: cannot write the video: {}", frame, error))?;
+        }
+        if let Some(audio) = audio {
+            audio.frame(&computer.take_audio_events());
+        }
+        if computer.state.halted {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Runs the program against a trace recorded by another emulator, failing at the first divergence.
+fn check_reference(computer: &mut Chip8, input: &Input, path: &str) -> Result<(), String> {
+    let source =
+        fs::read_to_string(path).map_err(|error| format!("cannot read {}: {}", path, error))?;
+    let reference = parse_reference(&source).map_err(|error| format!("{}: {}", path, error))?;
+    match compare(computer, &reference, |frame| input.keys(frame)) {
+        Some(divergence) => Err(format!("{}: {}", path, divergence)),
+        None => Ok(()),
+    }
+}
+
+fn debug(computer: &mut Chip8, input: &Input, port: u16) -> Result<(), String> {
+    let server = GdbServer::bind(port).map_err(|error| format!("cannot listen: {}", error))?;
+    let address = server
+        .local_addr()
+        .map_err(|error| format!("cannot listen: {}", error))?;
+    eprintln!("chip8-headless: waiting for gdb on {}", address);
+    let mut session = server
+        .accept()
+        .map_err(|error| format!("cannot accept: {}", error))?;
+    session
+        .serve(computer, |frame| input.keys(frame))
+        .map_err(|error| format!("gdb: {}", error))
+}
+
+/// Reports watchpoint hits and self-modifying code as they happen.
+struct MemoryMonitor {
+    tracker: Rc<RefCell<AccessTracker>>,
+    // self-modifications reported so far
+    reported: usize,
+}
+
+impl MemoryMonitor {
+    fn report(&mut self, frame: Option<usize>) {
+        let prefix = match frame {
+            Some(frame) => format!("chip8-headless: frame {}", frame),
+            None => "chip8-headless".to_string(),
+        };
+        let mut tracker = self.tracker.borrow_mut();
+        for hit in tracker.take_hits() {
+            let access = match hit.access {
+                Access::Read => "read",
+                Access::Write => "write",
+                Access::Fetch => "fetch",
+                Access::Operand => "operand",
+            };
+            eprintln!(
+                "{}: {} {:02X} at {:04X} by {:04X}",
+                prefix, access, hit.value, hit.address, hit.pc
+            );
+        }
+        for modification in &tracker.self_modifications()[self.reported..] {
+            eprintln!(
+                "{}: executing {:04X}, written by {:04X}",
+                prefix, modification.address, modification.written_by
+            );
+        }
+        self.reported = tracker.self_modifications().len();
+    }
+}
+
+/// Lists the accesses to every address that was accessed, then the self-modifying code.
+fn write_memory_report<W: Write>(
+    tracker: &AccessTracker,
+    memory_size: usize,
+    writer: &mut W,
+) -> io::Result<()> {
+    writeln!(writer, "# address reads writes fetches")?;
+    for address in 0..memory_size {
+        let counts = tracker.counts(address);
+        if counts.reads + counts.writes + counts.fetches > 0 {
+            writeln!(
+                writer,
+                "{:04X} {} {} {}",
+                address, counts.reads, counts.writes, counts.fetches
+            )?;
+        }
+    }
+    writeln!(writer, "# self-modifying code: address written_by")?;
+    for modification in tracker.self_modifications() {
+        writeln!(
+            writer,
+            "{:04X} {:04X}",
+            modification.address, modification.written_by
+        )?;
+    }
+    writer.flush()
+}
+
+fn dump<W: Write>(state: &State, writer: &mut W) -> io::Result<()> {
+    for row in state.display_buffer.rows() {
+        let line: String = row
+            .iter()
+            .map(|&pixel| match pixel {
+                0 => '.',
+                1 => '#',
+                _ => std::char::from_digit(pixel as u32, 10).unwrap(),
+            })
+            .collect();
+        writeln!(writer, "{}", line)?;
+    }
+    for (index, value) in state.registers.iter().enumerate() {
+        writeln!(writer, "V{:X} = {:02X}", index, value)?;
+    }
+    writeln!(writer, "I = {:04X}", state.i)?;
+    writeln!(writer, "PC = {:04X}", state.pc)?;
+    writeln!(writer, "DT = {:02X}", state.delay_timer)?;
+    writeln!(writer, "ST = {:02X}", state.sound_timer)?;
+    writeln!(writer, "RAM = {:016X}", fnv1a(state.ram.as_slice()))?;
+    writer.flush()
+}
+
+fn parse_arguments<I: Iterator<Item = String>>(mut arguments: I) -> Option<Options> {
+    let mut rom_file_path = None;
+    let mut quirks = Quirks::default();
+    let mut cycles_per_frame = DEFAULT_CYCLES_PER_FRAME;
+    let mut seed = 0;
+    let mut frames = 60;
+    let mut key_script_path = None;
+    let mut output_path = None;
+    let mut gdb_port = None;
+    let mut watchpoints = Vec::new();
+    let mut memory_report_path = None;
+    let mut trace_path = None;
+    let mut trace_format = TraceFormat::Text;
+    let mut trace_range = None;
+    let mut reference_path = None;
+    let mut record_path = None;
+    let mut play_path = None;
+    let mut screenshot_path = None;
+    let mut video_path = None;
+    let mut audio_path = None;
+    let mut scale = 1;
+    let mut palette = DEFAULT_PALETTE;
+    let mut tone = Tone::default();
+    while let Some(argument) = arguments.next() {
+        match argument.as_str() {
+            "--quirks" => quirks = Quirks::preset(&arguments.next()?)?,
+            "--cycles" => cycles_per_frame = arguments.next()?.parse().ok()?,
+            "--seed" => seed = arguments.next()?.parse().ok()?,
+            "--frames" => frames = arguments.next()?.parse().ok()?,
+            "--keys" => key_script_path = Some(arguments.next()?),
+            "--output" => output_path = Some(arguments.next()?),
+            "--gdb" => gdb_port = Some(arguments.next()?.parse().ok()?),
+            "--watch" => watchpoints.push(parse_watchpoint(&arguments.next()?)?),
+            "--memory-report" => memory_report_path = Some(arguments.next()?),
+            "--trace" => trace_path = Some(arguments.next()?),
+            "--trace-format" => {
+                trace_format = match arguments.next()?.as_str() {
+                    "text" => TraceFormat::Text,
+                    "binary" => TraceFormat::Binary,
+                    _ => return None,
+                }
+            }
+            "--trace-range" => trace_range = Some(parse_range(&arguments.next()?)?),
+            "--reference" => reference_path = Some(arguments.next()?),
+            "--record" => record_path = Some(arguments.next()?),
+            "--play" => play_path = Some(arguments.next()?),
+            "--screenshot" => {
+                let path = arguments.next()?;
+                ImageFormat::from_path(&path)?;
+                screenshot_path = Some(path);
+            }
+            "--video" => {
+                let path = arguments.next()?;
+                VideoFormat::from_path(&path)?;
+                video_path = Some(path);
+            }
+            "--audio" => audio_path = Some(arguments.next()?),
+            "--scale" => scale = arguments.next()?.parse().ok().filter(|&scale| scale > 0)?,
+            "--palette" => palette = image::parse_palette(&arguments.next()?)?,
+            "--waveform" => tone.waveform = Waveform::from_name(&arguments.next()?)?,
+            "--tone" => tone.frequency = arguments.next()?.parse().ok().filter(|&hz| hz > 0.0)?,
+            "--volume" => {
+                tone.volume = arguments
+                    .next()?
+                    .parse()
+                    .ok()
+                    .filter(|volume| (0.0..=1.0).contains(volume))?
+            }
+            "--attack" => tone.attack = parse_milliseconds(&arguments.next()?)?,
+            "--release" => tone.release = parse_milliseconds(&arguments.next()?)?,
+            _ => rom_file_path = Some(argument),
+        }
+    }
+    // a movie replaces the key script, and movies, videos and audio are only recorded over
+    // a fixed number of frames
+    let recorded = (record_path.is_some() || video_path.is_some() || audio_path.is_some())
+        && (gdb_port.is_some() || reference_path.is_some());
+    if recorded || (play_path.is_some() && (key_script_path.is_some() || record_path.is_some())) {
+        return None;
+    }
+    Some(Options {
+        rom_file_path: rom_file_path?,
+        quirks,
+        cycles_per_frame,
+        seed,
+        frames,
+        key_script_path,
+        output_path,
+        gdb_port,
+        watchpoints,
+        memory_report_path,
+        trace_path,
+        trace_format,
+        trace_range,
+        reference_path,
+        record_path,
+        play_path,
+        screenshot_path,
+        video_path,
+        audio_path,
+        scale,
+        palette,
+        tone,
+    })
+}
+
+/// Parses a duration in milliseconds, returning seconds.
+fn parse_milliseconds(milliseconds: &str) -> Option<f32> {
+    let milliseconds: f32 = milliseconds.parse().ok()?;
+    Some(milliseconds / 1000.0).filter(|&seconds| seconds >= 0.0)
+}
+
+/// Parses `first[-last][:r|w|rw|x]`, hexadecimal addresses watched for writes by default.
+fn parse_watchpoint(argument: &str) -> Option<(Range<usize>, Watch)> {
+    let mut parts = argument.splitn(2, ':');
+    let range = parse_range(parts.next()?)?;
+    let watch = match parts.next() {
+        None | Some("w") => Watch::Write,
+        Some("r") => Watch::Read,
+        Some("rw") => Watch::ReadWrite,
+        Some("x") => Watch::Fetch,
+        Some(_) => return None,
+    };
+    Some((range, watch))
+}
+
+/// Parses `first[-last]`, an inclusive range of hexadecimal addresses.
+fn parse_range(argument: &str) -> Option<Range<usize>> {
+    let mut bounds = argument.splitn(2, '-');
+    let first = parse_address(bounds.next()?)?;
+    let last = match bounds.next() {
+        Some(last) => parse_address(last)?,
+        None => first,
+    };
+    if first <= last {
+        Some(first..last + 1)
+    } else {
+        None
+    }
+}
+
+fn parse_address(address: &str) -> Option<usize> {
+    let digits = address.strip_prefix("0x").unwrap_or(address);
+    usize::from_str_radix(digits, 16).ok()
+}
@@ -0,0 +1,33 @@
+use chip8::trace::{read_header, TraceRecord};
+use std::env;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Write};
+use std::process;
+
+const USAGE: &str = "usage: chip8-trace binary_trace_path";
+
+fn main() {
+    let trace_path = match env::args().nth(1) {
+        Some(path) => path,
+        None => {
+            println!("{}", USAGE);
+            process::exit(2);
+        }
+    };
+    if let Err(error) = print_trace(&trace_path) {
+        eprintln!("chip8-trace: {}: {}", trace_path, error);
+        process::exit(1);
+    }
+}
+
+/// Prints a binary trace in the text format, so traces can be diffed whatever their format.
+fn print_trace(trace_path: &str) -> io::Result<()> {
+    let mut reader = BufReader::new(File::open(trace_path)?);
+    read_header(&mut reader)?;
+    let stdout = io::stdout();
+    let mut writer = BufWriter::new(stdout.lock());
+    while let Some(record) = TraceRecord::read_binary(&mut reader)? {
+        writeln!(writer, "{}", record)?;
+    }
+    writer.flush()
+}
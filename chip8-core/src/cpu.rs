@@ -1,35 +1,165 @@
-use rand::prelude::*;
-
-use crate::keyboard::{KeyState, Keys};
-use crate::state::{State, DISPLAY_HEIGHT, DISPLAY_WIDTH};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::audio::{AudioEvent, Sound, PATTERN_SIZE};
+use crate::display::{ALL_PLANES, NUM_PLANES};
+use crate::error::{ExecError, LoadError};
+use crate::instruction::{decode, Instruction};
+use crate::keyboard::{KeyState, Keys, NUM_KEYS};
+use crate::quirks::{MemoryQuirk, Quirks};
+use crate::ram::{BIG_FONT_START, FONT_START};
+use crate::state::{
+    State, DISPLAY_HEIGHT, DISPLAY_WIDTH, HIRES_DISPLAY_HEIGHT, HIRES_DISPLAY_WIDTH,
+};
+use crate::trace::Tracer;
+
+/// Rate at which frames are run and the timers count down.
+pub const FRAME_RATE: u32 = 60;
+pub const DEFAULT_CYCLES_PER_FRAME: usize = 11;
+/// Audio events kept for frontends which do not take them, the oldest being dropped.
+const MAX_AUDIO_EVENTS: usize = 1024;
 
 #[derive(Clone)]
 pub struct Chip8 {
     pub state: State, // initial program state
+    pub quirks: Quirks,
+    cycles_per_frame: usize,
+    tracer: Option<Rc<RefCell<Tracer>>>,
+    // frames run so far, which stamp the audio events
+    pub(crate) frame: u64,
+    pub(crate) sound: Option<Sound>,
+    audio_events: Vec<AudioEvent>,
 }
 
 impl Chip8 {
-    pub fn new(program: &[u8]) -> Self {
+    /// Loads a program, `seed` initializing the random numbers generated by `Cxkk`.
+    pub fn new(program: &[u8], quirks: Quirks, seed: u64) -> Result<Self, LoadError> {
+        let state = State::new(program, quirks.memory_size, seed)?;
+        Ok(Chip8::from_state(state, quirks))
+    }
+
+    /// Runs from a state built with `StateBuilder`, whose memory size should match the quirks.
+    pub fn from_state(state: State, quirks: Quirks) -> Self {
         Chip8 {
-            state: State::new(program),
+            state,
+            quirks,
+            cycles_per_frame: DEFAULT_CYCLES_PER_FRAME,
+            tracer: None,
+            frame: 0,
+            sound: None,
+            audio_events: Vec::new(),
+        }
+    }
+
+    pub fn cycles_per_frame(&self) -> usize {
+        self.cycles_per_frame
+    }
+
+    /// Sets how many instructions are executed by each call to `run_frame`.
+    pub fn set_cycles_per_frame(&mut self, cycles_per_frame: usize) {
+        self.cycles_per_frame = cycles_per_frame;
+    }
+
+    /// Sets the tracer recording every executed instruction, or removes it.
+    /// The tracer is shared by clones of the machine, and is not part of save states.
+    pub fn set_tracer(&mut self, tracer: Option<Rc<RefCell<Tracer>>>) {
+        self.tracer = tracer;
+    }
+
+    /// Runs one 60 Hz frame: executes `cycles_per_frame` instructions, then counts the timers down.
+    /// `should_draw` is set if any of the instructions changed the display during the frame.
+    pub fn run_frame(&mut self, keys: &Keys) -> Result<State, ExecError> {
+        self.state.should_draw = false;
+        for _ in 0..self.cycles_per_frame {
+            self.step(keys)?;
+        }
+        self.end_frame();
+        Ok(self.state.clone())
+    }
+
+    /// Counts the timers down, as `run_frame` does once its instructions are executed.
+    /// Callers stepping through a frame themselves call it after `cycles_per_frame` steps.
+    pub fn end_frame(&mut self) {
+        self.update_timers();
+        self.state.play_audio = self.state.sound_timer > 0;
+        self.frame += 1;
+        self.update_sound();
+    }
+
+    /// Number of frames run so far.
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    /// What the buzzer plays, nothing while the sound timer is stopped.
+    pub fn sound(&self) -> Option<Sound> {
+        self.sound
+    }
+
+    /// Takes the changes of the buzzer since the last call, oldest first.
+    /// A sound timer set to N during a frame sounds for exactly N frames from the start of it,
+    /// however the frames are played.
+    pub fn take_audio_events(&mut self) -> Vec<AudioEvent> {
+        std::mem::take(&mut self.audio_events)
+    }
+
+    /// Emits an audio event if the sound changed, as of the start of the current frame.
+    fn update_sound(&mut self) {
+        let sound = if self.state.sound_timer > 0 {
+            Some(Sound {
+                pattern: self.state.audio_pattern,
+                pitch: self.state.pitch,
+            })
+        } else {
+            None
+        };
+        if sound == self.sound {
+            return;
+        }
+        self.sound = sound;
+        if self.audio_events.len() == MAX_AUDIO_EVENTS {
+            self.audio_events.remove(0);
         }
+        self.audio_events.push(AudioEvent {
+            frame: self.frame,
+            sound,
+        });
+    }
+
+    /// Executes a single instruction, leaving the timers untouched.
+    pub fn iteration(&mut self, keys: &Keys) -> Result<State, ExecError> {
+        self.state.should_draw = false;
+        self.step(keys)?;
+        Ok(self.state.clone())
     }
 
-    pub fn iteration(&mut self, keys: &Keys) -> Option<State> {
-        if self.state.waiting_for_key {
+    /// Executes a single instruction, or waits for a key if `Fx0A` is pending.
+    pub fn step(&mut self, keys: &Keys) -> Result<(), ExecError> {
+        if self.state.halted {
+            // the program exited, there is nothing left to execute
+        } else if self.state.waiting_for_key {
             if let Some(key) = keys.iter().position(|&ks| ks == KeyState::Down) {
                 self.state.registers[self.state.key_register_index] = key as u8;
                 self.state.waiting_for_key = false;
             }
         } else {
-            let instruction = self.state.instruction();
+            let address = self.state.pc;
+            let instruction = self.state.fetch()?;
+            if let Some(tracer) = &self.tracer {
+                tracer.borrow_mut().trace(&self.state, instruction);
+            }
             self.state.next_instruction();
-            self.update_timers();
-            self.state.should_draw = false;
-            self.state.play_audio = self.state.sound_timer > 0;
-            self.parse_instruction(instruction, keys);
+            self.parse_instruction(instruction, address, keys)?;
         }
-        Some(self.state.clone())
+        Ok(())
+    }
+
+    /// Executes `opcode` as if it were at the program counter, without reading it from memory.
+    /// Meant to test a single instruction: the timers are left untouched and nothing is traced.
+    pub fn execute(&mut self, opcode: u16, keys: &Keys) -> Result<(), ExecError> {
+        let address = self.state.pc;
+        self.state.next_instruction();
+        self.parse_instruction(opcode, address, keys)
     }
 
     fn update_timers(&mut self) {
@@ -41,277 +171,411 @@ impl Chip8 {
         }
     }
 
-    fn parse_instruction(&mut self, instruction: u16, keys: &Keys) {
-        match instruction & 0xF000 {
-            0x0000 => match instruction & 0x00FF {
-                0x00E0 => self._00e0(),
-                0x00EE => self._00ee(),
-                _ => panic!("Unknown opcode: {:X}", instruction),
-            },
-            0x1000 => self._1nnn(instruction),
-            0x2000 => self._2nnn(instruction),
-            0x3000 => self._3xkk(instruction),
-            0x4000 => self._4xkk(instruction),
-            0x5000 => self._5xy0(instruction),
-            0x6000 => self._6xkk(instruction),
-            0x7000 => self._7xkk(instruction),
-            0x8000 => match instruction & 0x000F {
-                0x0000 => self._8xy0(instruction),
-                0x0001 => self._8xy1(instruction),
-                0x0002 => self._8xy2(instruction),
-                0x0003 => self._8xy3(instruction),
-                0x0004 => self._8xy4(instruction),
-                0x0005 => self._8xy5(instruction),
-                0x0006 => self._8xy6(instruction),
-                0x0007 => self._8xy7(instruction),
-                0x000E => self._8xye(instruction),
-                _ => panic!("Unknown opcode: {:X}", instruction),
-            },
-            0x9000 => self._9xy0(instruction),
-            0xA000 => self._annn(instruction),
-            0xB000 => self._bnnn(instruction),
-            0xC000 => self._cxkk(instruction),
-            0xD000 => self._dxyn(instruction),
-            0xE000 => match instruction & 0x00FF {
-                0x009E => self._ex9e(instruction, keys),
-                0x00A1 => self._exa1(instruction, keys),
-                _ => panic!("Unknown opcode: {:X}", instruction),
-            },
-            0xF000 => match instruction & 0x00FF {
-                0x0007 => self._fx07(instruction),
-                0x000A => self._fx0a(instruction),
-                0x0015 => self._fx15(instruction),
-                0x0018 => self._fx18(instruction),
-                0x001E => self._fx1e(instruction),
-                0x0033 => self._fx33(instruction),
-                0x0055 => self._fx55(instruction),
-                0x0065 => self._fx65(instruction),
-                0x0029 => self._fx29(instruction),
-                _ => panic!("Unknown opcode: {:X}", instruction),
-            },
-            _ => panic!("Unknown opcode {:X}", instruction),
-        };
+    fn parse_instruction(
+        &mut self,
+        instruction: u16,
+        address: usize,
+        keys: &Keys,
+    ) -> Result<(), ExecError> {
+        let decoded = decode(instruction).map_err(|_| ExecError::UnknownOpcode {
+            opcode: instruction,
+            address,
+        })?;
+        match decoded {
+            Instruction::Cls => self._00e0(),
+            Instruction::Ret => self._00ee(),
+            Instruction::ScrollDown(_) => self._00cn(instruction),
+            Instruction::ScrollUp(_) => self._00dn(instruction),
+            Instruction::ScrollRight => self._00fb(),
+            Instruction::ScrollLeft => self._00fc(),
+            Instruction::Exit => self._00fd(),
+            Instruction::Low => self._00fe(),
+            Instruction::High => self._00ff(),
+            Instruction::Jp(_) => self._1nnn(instruction),
+            Instruction::Call(_) => self._2nnn(instruction),
+            Instruction::SeByte(_, _) => self._3xkk(instruction),
+            Instruction::SneByte(_, _) => self._4xkk(instruction),
+            Instruction::SeRegister(_, _) => self._5xy0(instruction),
+            Instruction::Save(_, _) => self._5xy2(instruction),
+            Instruction::Load(_, _) => self._5xy3(instruction),
+            Instruction::LdByte(_, _) => self._6xkk(instruction),
+            Instruction::AddByte(_, _) => self._7xkk(instruction),
+            Instruction::LdRegister(_, _) => self._8xy0(instruction),
+            Instruction::Or(_, _) => self._8xy1(instruction),
+            Instruction::And(_, _) => self._8xy2(instruction),
+            Instruction::Xor(_, _) => self._8xy3(instruction),
+            Instruction::AddRegister(_, _) => self._8xy4(instruction),
+            Instruction::Sub(_, _) => self._8xy5(instruction),
+            Instruction::Shr(_, _) => self._8xy6(instruction),
+            Instruction::Subn(_, _) => self._8xy7(instruction),
+            Instruction::Shl(_, _) => self._8xye(instruction),
+            Instruction::SneRegister(_, _) => self._9xy0(instruction),
+            Instruction::LdI(_) => self._annn(instruction),
+            Instruction::JpV0(_) => self._bnnn(instruction),
+            Instruction::Rnd(_, _) => self._cxkk(instruction),
+            Instruction::Drw(_, _, _) => self._dxyn(instruction),
+            Instruction::Skp(_) => self._ex9e(instruction, keys),
+            Instruction::Sknp(_) => self._exa1(instruction, keys),
+            Instruction::LdILong => self._f000(),
+            Instruction::Plane(_) => self._fn01(instruction),
+            Instruction::Audio => self._f002(),
+            Instruction::LdVxDt(_) => self._fx07(instruction),
+            Instruction::LdVxK(_) => self._fx0a(instruction),
+            Instruction::LdDtVx(_) => self._fx15(instruction),
+            Instruction::LdStVx(_) => self._fx18(instruction),
+            Instruction::AddI(_) => self._fx1e(instruction),
+            Instruction::LdF(_) => self._fx29(instruction),
+            Instruction::LdHf(_) => self._fx30(instruction),
+            Instruction::LdB(_) => self._fx33(instruction),
+            Instruction::Pitch(_) => self._fx3a(instruction),
+            Instruction::LdMemoryVx(_) => self._fx55(instruction),
+            Instruction::LdVxMemory(_) => self._fx65(instruction),
+            Instruction::LdRVx(_) => self._fx75(instruction),
+            Instruction::LdVxR(_) => self._fx85(instruction),
+        }
     }
 
     /// Clear the display.
-    fn _00e0(&mut self) {
-        self.state
-            .display_buffer
-            .iter_mut()
-            .for_each(|value| value.iter_mut().for_each(|px| *px = 0));
+    fn _00e0(&mut self) -> Result<(), ExecError> {
+        self.state.display_buffer.clear_planes(self.state.planes);
+        self.state.should_draw = true;
+        Ok(())
     }
 
     /// Return from a subroutine.
     /// The interpreter sets the program counter to the address at the top of the stack, then subtracts 1 from the stack pointer.
-    fn _00ee(&mut self) {
-        let return_address = self.state.stack.pop();
+    fn _00ee(&mut self) -> Result<(), ExecError> {
+        let return_address = self.state.stack.pop()?;
         self.state.pc = return_address as usize;
+        Ok(())
+    }
+
+    /// Scroll display n lines down.
+    /// The lines at the top of the screen are left blank.
+    fn _00cn(&mut self, instruction: u16) -> Result<(), ExecError> {
+        let n = Self::_n(instruction) as usize;
+        self.state.display_buffer.scroll_down(n, self.state.planes);
+        self.state.should_draw = true;
+        Ok(())
+    }
+
+    /// Scroll display n lines up.
+    /// The lines at the bottom of the screen are left blank.
+    fn _00dn(&mut self, instruction: u16) -> Result<(), ExecError> {
+        let n = Self::_n(instruction) as usize;
+        self.state.display_buffer.scroll_up(n, self.state.planes);
+        self.state.should_draw = true;
+        Ok(())
+    }
+
+    /// Scroll display 4 pixels right.
+    fn _00fb(&mut self) -> Result<(), ExecError> {
+        self.state.display_buffer.scroll_right(4, self.state.planes);
+        self.state.should_draw = true;
+        Ok(())
+    }
+
+    /// Scroll display 4 pixels left.
+    fn _00fc(&mut self) -> Result<(), ExecError> {
+        self.state.display_buffer.scroll_left(4, self.state.planes);
+        self.state.should_draw = true;
+        Ok(())
+    }
+
+    /// Exit the interpreter.
+    /// No further instructions are executed.
+    fn _00fd(&mut self) -> Result<(), ExecError> {
+        self.state.halted = true;
+        Ok(())
+    }
+
+    /// Disable high resolution graphics mode.
+    /// The display switches back to 64x32 pixels and is cleared.
+    fn _00fe(&mut self) -> Result<(), ExecError> {
+        self.state.hires = false;
+        self.state
+            .display_buffer
+            .resize(DISPLAY_WIDTH, DISPLAY_HEIGHT);
+        self.state.should_draw = true;
+        Ok(())
+    }
+
+    /// Enable high resolution graphics mode.
+    /// The display switches to 128x64 pixels and is cleared.
+    fn _00ff(&mut self) -> Result<(), ExecError> {
+        self.state.hires = true;
+        self.state
+            .display_buffer
+            .resize(HIRES_DISPLAY_WIDTH, HIRES_DISPLAY_HEIGHT);
+        self.state.should_draw = true;
+        Ok(())
     }
 
     /// Jump to location nnn.
     /// The interpreter sets the program counter to nnn.
-    fn _1nnn(&mut self, instruction: u16) {
+    fn _1nnn(&mut self, instruction: u16) -> Result<(), ExecError> {
         let address = Self::_nnn(instruction);
         self.state.pc = address as usize;
+        Ok(())
     }
 
     /// Call subroutine at nnn.
     /// The interpreter increments the stack pointer, then puts the current PC on the top of the stack.
     /// The PC is then set to nnn.
-    fn _2nnn(&mut self, instruction: u16) {
+    fn _2nnn(&mut self, instruction: u16) -> Result<(), ExecError> {
         let call_address = Self::_nnn(instruction);
-        self.state.stack.push(self.state.pc as u16);
+        self.state.stack.push(self.state.pc as u16)?;
         self.state.pc = call_address as usize;
+        Ok(())
     }
 
     /// Skip next instruction if Vx = kk.
     /// The interpreter compares register Vx to kk, and if they are equal, increments the program counter by 2.
-    fn _3xkk(&mut self, instruction: u16) {
+    fn _3xkk(&mut self, instruction: u16) -> Result<(), ExecError> {
         let register_index = Self::_x(instruction) as usize;
         let value = Self::_kk(instruction);
         let vx: u8 = self.state.registers[register_index];
         if vx == value {
-            self.state.next_instruction();
+            self.state.skip_instruction();
         }
+        Ok(())
     }
 
     /// Skip next instruction if Vx != kk.
     /// The interpreter compares register Vx to kk, and if they are not equal, increments the program counter by 2.
-    fn _4xkk(&mut self, instruction: u16) {
+    fn _4xkk(&mut self, instruction: u16) -> Result<(), ExecError> {
         let x = Self::_x(instruction) as usize;
         let value = Self::_kk(instruction);
         let vx: u8 = self.state.registers[x];
         if vx != value {
-            self.state.next_instruction();
+            self.state.skip_instruction();
         }
+        Ok(())
     }
 
     /// Skip next instruction if Vx = Vy.
     /// The interpreter compares register Vx to register Vy, and if they are equal, increments the program counter by 2.
-    fn _5xy0(&mut self, instruction: u16) {
+    fn _5xy0(&mut self, instruction: u16) -> Result<(), ExecError> {
         let register_x_index = Self::_x(instruction) as usize;
         let register_y_index = Self::_y(instruction) as usize;
         let vx: u8 = self.state.registers[register_x_index];
         let vy: u8 = self.state.registers[register_y_index];
 
         if vx == vy {
-            self.state.next_instruction();
+            self.state.skip_instruction();
         }
+        Ok(())
+    }
+
+    /// Save registers Vx through Vy in memory starting at location I.
+    /// The registers are saved in descending order when x is greater than y. I is not modified.
+    fn _5xy2(&mut self, instruction: u16) -> Result<(), ExecError> {
+        let address_register = self.state.i as usize;
+        for (offset, index) in Self::register_range(instruction).into_iter().enumerate() {
+            let value: u8 = self.state.registers[index];
+            self.state.ram.set(address_register + offset, value)?;
+        }
+        Ok(())
+    }
+
+    /// Load registers Vx through Vy from memory starting at location I.
+    /// The registers are loaded in descending order when x is greater than y. I is not modified.
+    fn _5xy3(&mut self, instruction: u16) -> Result<(), ExecError> {
+        let address_register = self.state.i as usize;
+        for (offset, index) in Self::register_range(instruction).into_iter().enumerate() {
+            self.state.registers[index] = self.state.ram.get(address_register + offset)?;
+        }
+        Ok(())
     }
 
     /// Set Vx = kk.
     /// The interpreter puts the value kk into register Vx.
-    fn _6xkk(&mut self, instruction: u16) {
+    fn _6xkk(&mut self, instruction: u16) -> Result<(), ExecError> {
         let register_index = Self::_x(instruction) as usize;
         let register_value = Self::_kk(instruction);
         self.state.registers[register_index] = register_value;
+        Ok(())
     }
 
     /// Set Vx = Vx + kk.
     /// Adds the value kk to the value of register Vx, then stores the result in Vx.
-    fn _7xkk(&mut self, instruction: u16) {
+    fn _7xkk(&mut self, instruction: u16) -> Result<(), ExecError> {
         let register_index = Self::_x(instruction) as usize;
         let value = Self::_kk(instruction);
-        let vx = self.state.registers[register_index] as u8;
+        let vx = self.state.registers[register_index];
         let add_result = vx.wrapping_add(value);
         self.state.registers[register_index] = add_result;
+        Ok(())
     }
 
     /// Set Vx = Vy.
     /// Stores the value of register Vy in register Vx.
-    fn _8xy0(&mut self, instruction: u16) {
+    fn _8xy0(&mut self, instruction: u16) -> Result<(), ExecError> {
         let x = Self::_x(instruction) as usize;
         let y = Self::_y(instruction) as usize;
         self.state.registers[x] = self.state.registers[y];
+        Ok(())
     }
 
     /// Set Vx = Vx OR Vy.
     /// Performs a bitwise OR on the values of Vx and Vy, then stores the result in Vx.
     /// A bitwise OR compares the corrseponding bits from two values, and if either bit is 1,
     /// then the same bit in the result is also 1. Otherwise, it is 0.
-    fn _8xy1(&mut self, instruction: u16) {
+    fn _8xy1(&mut self, instruction: u16) -> Result<(), ExecError> {
         let x = Self::_x(instruction) as usize;
         let y = Self::_y(instruction) as usize;
         let vx: u8 = self.state.registers[x];
         let vy: u8 = self.state.registers[y];
         self.state.registers[x] = vx | vy;
+        if self.quirks.logic_resets_vf {
+            self.state.registers[0x0F] = 0;
+        }
+        Ok(())
     }
 
     /// Set Vx = Vx AND Vy.
     /// Performs a bitwise AND on the values of Vx and Vy, then stores the result in Vx.
     /// A bitwise AND compares the corrseponding bits from two values, and if both bits are 1,
     /// then the same bit in the result is also 1. Otherwise, it is 0.
-    fn _8xy2(&mut self, instruction: u16) {
+    fn _8xy2(&mut self, instruction: u16) -> Result<(), ExecError> {
         let x = Self::_x(instruction) as usize;
         let y = Self::_y(instruction) as usize;
         let vx: u8 = self.state.registers[x];
         let vy: u8 = self.state.registers[y];
         self.state.registers[x] = vx & vy;
+        if self.quirks.logic_resets_vf {
+            self.state.registers[0x0F] = 0;
+        }
+        Ok(())
     }
 
     /// Set Vx = Vx XOR Vy.
     /// Performs a bitwise exclusive OR on the values of Vx and Vy, then stores the result in Vx.
     /// An exclusive OR compares the corrseponding bits from two values, and if the bits are not both the same,
     /// then the corresponding bit in the result is set to 1. Otherwise, it is 0.
-    fn _8xy3(&mut self, instruction: u16) {
+    fn _8xy3(&mut self, instruction: u16) -> Result<(), ExecError> {
         let x = Self::_x(instruction) as usize;
         let y = Self::_y(instruction) as usize;
         let vx: u8 = self.state.registers[x];
         let vy: u8 = self.state.registers[y];
         self.state.registers[x] = vx ^ vy;
+        if self.quirks.logic_resets_vf {
+            self.state.registers[0x0F] = 0;
+        }
+        Ok(())
     }
 
     /// Set Vx = Vx + Vy, set VF = carry.
     /// The values of Vx and Vy are added together.
     /// If the result is greater than 8 bits (i.e., > 255,) VF is set to 1, otherwise 0.
     /// Only the lowest 8 bits of the result are kept, and stored in Vx.
-    fn _8xy4(&mut self, instruction: u16) {
+    fn _8xy4(&mut self, instruction: u16) -> Result<(), ExecError> {
         let x = Self::_x(instruction) as usize;
         let y = Self::_y(instruction) as usize;
         let vx = self.state.registers[x] as u16;
         let vy = self.state.registers[y] as u16;
         let result = vx + vy;
-        self.state.registers[0x0F] = (result > std::u8::MAX as u16).into();
         self.state.registers[x] = (result & 0x00FF) as u8;
+        // VF is written last, so that the flag wins when x is F
+        self.state.registers[0x0F] = (result > u8::MAX as u16).into();
+        Ok(())
     }
 
     /// Set Vx = Vx - Vy, set VF = NOT borrow.
-    /// If Vx > Vy, then VF is set to 1, otherwise 0.
+    /// If Vx >= Vy, then VF is set to 1, otherwise 0.
     /// Then Vy is subtracted from Vx, and the results stored in Vx.
-    fn _8xy5(&mut self, instruction: u16) {
+    fn _8xy5(&mut self, instruction: u16) -> Result<(), ExecError> {
         let x = Self::_x(instruction) as usize;
         let y = Self::_y(instruction) as usize;
         let vx: u8 = self.state.registers[x];
         let vy: u8 = self.state.registers[y];
-        self.state.registers[0x0F] = (vx > vy).into();
         self.state.registers[x] = vx.wrapping_sub(vy);
+        self.state.registers[0x0F] = (vx >= vy).into();
+        Ok(())
     }
 
     /// Set Vx = Vx SHR 1.
     /// If the least-significant bit of Vx is 1, then VF is set to 1, otherwise 0.
     /// Then Vx is divided by 2.
-    fn _8xy6(&mut self, instruction: u16) {
+    /// With the shift quirk, Vy is shifted instead and the result is stored in Vx.
+    fn _8xy6(&mut self, instruction: u16) -> Result<(), ExecError> {
         let x = Self::_x(instruction) as usize;
-        let vx: u8 = self.state.registers[x];
-        self.state.registers[0x0F] = vx & 1;
-        self.state.registers[x] >>= 1;
+        let source = self.shift_source(instruction);
+        let value: u8 = self.state.registers[source];
+        self.state.registers[x] = value >> 1;
+        self.state.registers[0x0F] = value & 1;
+        Ok(())
     }
 
     /// Set Vx = Vy - Vx, set VF = NOT borrow.
-    /// If Vy > Vx, then VF is set to 1, otherwise 0. Then Vx is subtracted from Vy, and the results stored in Vx.
-    fn _8xy7(&mut self, instruction: u16) {
+    /// If Vy >= Vx, then VF is set to 1, otherwise 0. Then Vx is subtracted from Vy, and the results stored in Vx.
+    fn _8xy7(&mut self, instruction: u16) -> Result<(), ExecError> {
         let x = Self::_x(instruction) as usize;
-        let y = Self::_x(instruction) as usize;
+        let y = Self::_y(instruction) as usize;
         let vx: u8 = self.state.registers[x];
         let vy: u8 = self.state.registers[y];
-        self.state.registers[0x0F] = (vy > vx).into();
         self.state.registers[x] = vy.wrapping_sub(vx);
+        self.state.registers[0x0F] = (vy >= vx).into();
+        Ok(())
     }
 
     // Set Vx = Vx SHL 1.
     // If the most-significant bit of Vx is 1, then VF is set to 1, otherwise to 0. Then Vx is multiplied by 2.
-    fn _8xye(&mut self, instruction: u16) {
+    // With the shift quirk, Vy is shifted instead and the result is stored in Vx.
+    fn _8xye(&mut self, instruction: u16) -> Result<(), ExecError> {
         let x = Self::_x(instruction) as usize;
-        let vx: u8 = self.state.registers[x];
-        self.state.registers[0x0F] = (vx & 0b10000000) >> 7;
-        self.state.registers[x] <<= 1;
+        let source = self.shift_source(instruction);
+        let value: u8 = self.state.registers[source];
+        self.state.registers[x] = value << 1;
+        self.state.registers[0x0F] = (value & 0b10000000) >> 7;
+        Ok(())
     }
 
     /// Skip next instruction if Vx != Vy.
     /// The values of Vx and Vy are compared, and if they are not equal, the program counter is increased by 2.
-    fn _9xy0(&mut self, instruction: u16) {
+    fn _9xy0(&mut self, instruction: u16) -> Result<(), ExecError> {
         let x = Self::_x(instruction) as usize;
-        let y = Self::_x(instruction) as usize;
+        let y = Self::_y(instruction) as usize;
         let vx: u8 = self.state.registers[x];
         let vy: u8 = self.state.registers[y];
         if vx != vy {
-            self.state.next_instruction();
+            self.state.skip_instruction();
         }
+        Ok(())
     }
 
     /// Set I = nnn.
     /// The value of register I is set to nnn.
-    fn _annn(&mut self, instruction: u16) {
+    fn _annn(&mut self, instruction: u16) -> Result<(), ExecError> {
         let address = Self::_nnn(instruction);
         self.state.set_address_register(address);
+        Ok(())
     }
 
     /// Jump to location nnn + V0.
     /// The program counter is set to nnn plus the value of V0.
-    fn _bnnn(&mut self, instruction: u16) {
+    /// With the jump quirk, Vx is used instead of V0, x being the highest nibble of nnn.
+    fn _bnnn(&mut self, instruction: u16) -> Result<(), ExecError> {
         let address = Self::_nnn(instruction) as usize;
-        let v0 = self.state.registers[0] as usize;
-        self.state.pc = address + v0;
+        let register_index = if self.quirks.jump_uses_vx {
+            Self::_x(instruction) as usize
+        } else {
+            0
+        };
+        let offset = self.state.registers[register_index] as usize;
+        self.state.pc = address + offset;
+        Ok(())
     }
 
     /// Set Vx = random byte AND kk.
     /// The interpreter generates a random number from 0 to 255, which is then ANDed with the value kk.
     /// The results are stored in Vx.
-    fn _cxkk(&mut self, instruction: u16) {
+    fn _cxkk(&mut self, instruction: u16) -> Result<(), ExecError> {
         let register_index = Self::_x(instruction) as usize;
         let value = Self::_kk(instruction);
-        let random_value: u8 = random();
+        let random_value: u8 = self.state.rng.next_u8();
         self.state.registers[register_index] = random_value & value;
+        Ok(())
     }
 
     /// Display n-byte sprite starting at memory location I at (Vx, Vy), set VF = collision.
@@ -320,139 +584,275 @@ impl Chip8 {
     /// Sprites are XORed onto the existing screen.
     /// If this causes any pixels to be erased, VF is set to 1, otherwise it is set to 0.
     /// If the sprite is positioned so part of it is outside the coordinates of the display,
-    /// it wraps around to the opposite side of the screen.
-    fn _dxyn(&mut self, _instruction: u16) {
+    /// it wraps around to the opposite side of the screen, unless the clipping quirk is enabled.
+    /// When n is 0, a 16x16 sprite made of 32 bytes is displayed instead.
+    fn _dxyn(&mut self, _instruction: u16) -> Result<(), ExecError> {
         let register_x = Self::_x(_instruction) as usize;
         let register_y = Self::_y(_instruction) as usize;
-        let x_start = self.state.registers[register_x] as usize;
-        let y_start = self.state.registers[register_y] as usize;
-        let n = Self::_n(_instruction) as usize;
-        let width = 8; // width of the sprite
+        let display_width = self.state.display_buffer.width();
+        let display_height = self.state.display_buffer.height();
+        let x_start = self.state.registers[register_x] as usize % display_width;
+        let y_start = self.state.registers[register_y] as usize % display_height;
+        let (width, height) = match Self::_n(_instruction) as usize {
+            0 => (16, 16),
+            n => (8, n),
+        };
+        let bytes_per_row = width / 8;
         let mut collision = false;
+        // each selected bitplane is drawn with its own sprite, stored right after the previous one
+        let mut address_register = self.state.i as usize;
 
-        for y in 0..n {
-            let address_register = self.state.i as usize;
-            let current_pixel_index = address_register + y;
-            for x in 0..width {
-                let x_current = (x_start + x) % DISPLAY_WIDTH;
-                let y_current = (y_start + y) % DISPLAY_HEIGHT;
-                let pixel = self.state.ram.get(current_pixel_index);
-
-                if pixel & (0x80 >> x) != 0 {
-                    if self.state.display_buffer[y_current][x_current] == 1 {
-                        collision = true;
+        for plane in (0..NUM_PLANES).map(|index| 1 << index) {
+            if self.state.planes & plane == 0 {
+                continue;
+            }
+            for y in 0..height {
+                let row_index = address_register + y * bytes_per_row;
+                for x in 0..width {
+                    let clipped = x_start + x >= display_width || y_start + y >= display_height;
+                    if clipped && self.quirks.clip_sprites {
+                        continue;
+                    }
+                    let x_current = (x_start + x) % display_width;
+                    let y_current = (y_start + y) % display_height;
+                    let pixel = self.state.ram.get(row_index + x / 8)?;
+
+                    if pixel & (0x80 >> (x % 8)) != 0 {
+                        let current = self.state.display_buffer.get(x_current, y_current);
+                        if current & plane != 0 {
+                            collision = true;
+                        }
+                        self.state
+                            .display_buffer
+                            .set(x_current, y_current, current ^ plane);
                     }
-                    self.state.display_buffer[y_current][x_current] ^= 1;
                 }
             }
+            address_register += height * bytes_per_row;
         }
         self.state.registers[0x0F] = collision.into();
         self.state.should_draw = true;
+        Ok(())
     }
 
     /// Skip next instruction if key with the value of Vx is pressed.
     /// Checks the keyboard, and if the key corresponding to the value of Vx is currently
     /// in the down position, PC is increased by 2.
-    fn _ex9e(&mut self, instruction: u16, keys: &Keys) {
+    fn _ex9e(&mut self, instruction: u16, keys: &Keys) -> Result<(), ExecError> {
         let register_index = Self::_x(instruction) as usize;
         let key_index = self.state.registers[register_index] as usize;
-        if keys[key_index] == KeyState::Down {
-            self.state.next_instruction();
+        if Self::key_state(keys, key_index)? == KeyState::Down {
+            self.state.skip_instruction();
         }
+        Ok(())
     }
 
     /// Skip next instruction if key with the value of Vx is not pressed.
     /// Checks the keyboard, and if the key corresponding to the value of Vx is currently in the up position, PC is increased by 2.
-    fn _exa1(&mut self, instruction: u16, keys: &Keys) {
+    fn _exa1(&mut self, instruction: u16, keys: &Keys) -> Result<(), ExecError> {
         let register_index = Self::_x(instruction) as usize;
         let key_index = self.state.registers[register_index] as usize;
 
-        if keys[key_index] == KeyState::Up {
-            self.state.next_instruction();
+        if Self::key_state(keys, key_index)? == KeyState::Up {
+            self.state.skip_instruction();
+        }
+        Ok(())
+    }
+
+    /// Set I = nnnn.
+    /// The address is read from the 2 bytes following the instruction, which are then skipped.
+    fn _f000(&mut self) -> Result<(), ExecError> {
+        let address = self.state.fetch_operand()?;
+        self.state.next_instruction();
+        self.state.i = address;
+        Ok(())
+    }
+
+    /// Select the bitplanes drawn to by the display instructions.
+    /// n is a bitmask of the planes, 0 selecting none and 3 selecting both.
+    fn _fn01(&mut self, instruction: u16) -> Result<(), ExecError> {
+        self.state.planes = Self::_x(instruction) & ALL_PLANES;
+        Ok(())
+    }
+
+    /// Load the 16-byte audio pattern starting at memory location I.
+    fn _f002(&mut self) -> Result<(), ExecError> {
+        let address_register = self.state.i as usize;
+        let mut pattern = [0; PATTERN_SIZE];
+        for (offset, sample) in pattern.iter_mut().enumerate() {
+            *sample = self.state.ram.get(address_register + offset)?;
         }
+        self.state.audio_pattern = Some(pattern);
+        self.update_sound();
+        Ok(())
     }
 
     /// Set Vx = delay timer value.
     /// The value of DT is placed into Vx.
-    fn _fx07(&mut self, instruction: u16) {
+    fn _fx07(&mut self, instruction: u16) -> Result<(), ExecError> {
         let register_index = Self::_x(instruction) as usize;
         self.state.registers[register_index] = self.state.delay_timer;
+        Ok(())
     }
 
     /// Wait for a key press, store the value of the key in Vx.
     /// All execution stops until a key is pressed, then the value of that key is stored in Vx.
-    fn _fx0a(&mut self, instruction: u16) {
+    fn _fx0a(&mut self, instruction: u16) -> Result<(), ExecError> {
         let x = Self::_x(instruction) as usize;
         self.state.key_register_index = x;
         self.state.waiting_for_key = true;
+        Ok(())
     }
 
     /// Set delay timer = Vx.
     /// DT is set equal to the value of Vx.
-    fn _fx15(&mut self, instruction: u16) {
+    fn _fx15(&mut self, instruction: u16) -> Result<(), ExecError> {
         let register_index = Self::_x(instruction) as usize;
         let timer_value: u8 = self.state.registers[register_index];
         self.state.delay_timer = timer_value;
+        Ok(())
     }
 
     /// Set sound timer = Vx.
     /// ST is set equal to the value of Vx.
-    fn _fx18(&mut self, instruction: u16) {
+    fn _fx18(&mut self, instruction: u16) -> Result<(), ExecError> {
         let register_index = Self::_x(instruction) as usize;
         let timer_value: u8 = self.state.registers[register_index];
         self.state.sound_timer = timer_value;
+        self.update_sound();
+        Ok(())
     }
 
     /// Set I = I + Vx.
     /// The values of I and Vx are added, and the results are stored in I.
-    fn _fx1e(&mut self, instruction: u16) {
+    /// With the overflow quirk, VF is set to 1 if the result falls outside of the memory, otherwise 0.
+    fn _fx1e(&mut self, instruction: u16) -> Result<(), ExecError> {
         let register_index = Self::_x(instruction) as usize;
         let vx = self.state.registers[register_index] as u16;
-        let result = vx as u32 + self.state.i as u32;
-        self.state.registers[0x0F] = (result > std::u8::MAX as u32).into();
+        let result = vx as usize + self.state.i as usize;
         self.state.i = self.state.i.wrapping_add(vx);
+        if self.quirks.add_i_sets_vf {
+            self.state.registers[0x0F] = (result >= self.state.ram.len()).into();
+        }
+        Ok(())
     }
 
     /// Set I = location of sprite for digit Vx.
-    fn _fx29(&mut self, instruction: u16) {
+    fn _fx29(&mut self, instruction: u16) -> Result<(), ExecError> {
         let register_index = Self::_x(instruction) as usize;
         let index = self.state.registers[register_index] as u16;
         // each digit is 5 bytes, so we can just multiply the digit by 5 to retrieve the index
-        self.state.i = index * 5;
+        self.state.i = FONT_START as u16 + index * 5;
+        Ok(())
+    }
+
+    /// Set I = location of the 10-byte high resolution sprite for digit Vx.
+    fn _fx30(&mut self, instruction: u16) -> Result<(), ExecError> {
+        let register_index = Self::_x(instruction) as usize;
+        let index = self.state.registers[register_index] as u16;
+        self.state.i = BIG_FONT_START as u16 + index * 10;
+        Ok(())
+    }
+
+    /// Set pitch = Vx.
+    /// The pitch sets the rate at which the audio pattern is played.
+    fn _fx3a(&mut self, instruction: u16) -> Result<(), ExecError> {
+        let register_index = Self::_x(instruction) as usize;
+        self.state.pitch = self.state.registers[register_index];
+        self.update_sound();
+        Ok(())
+    }
+
+    /// Store registers V0 through Vx in the RPL user flags.
+    fn _fx75(&mut self, instruction: u16) -> Result<(), ExecError> {
+        let last_register_index = Self::_x(instruction) as usize;
+        let count = last_register_index + 1;
+        self.state.flags[..count].copy_from_slice(&self.state.registers[..count]);
+        Ok(())
+    }
+
+    /// Read registers V0 through Vx from the RPL user flags.
+    fn _fx85(&mut self, instruction: u16) -> Result<(), ExecError> {
+        let last_register_index = Self::_x(instruction) as usize;
+        let count = last_register_index + 1;
+        self.state.registers[..count].copy_from_slice(&self.state.flags[..count]);
+        Ok(())
     }
 
     /// Store BCD representation of Vx in memory locations I, I+1, and I+2.
     /// The interpreter takes the decimal value of Vx, and places the hundreds digit in memory at location in I,
     /// the tens digit at location I+1, and the ones digit at location I+2.
-    fn _fx33(&mut self, instruction: u16) {
+    fn _fx33(&mut self, instruction: u16) -> Result<(), ExecError> {
         let register_index = Self::_x(instruction) as usize;
         let vx: u8 = self.state.registers[register_index];
         let address_register = self.state.i as usize;
-        self.state.ram.set(address_register, (vx / 100) % 10);
-        self.state.ram.set(address_register + 1, (vx / 10) % 10);
-        self.state.ram.set(address_register + 2, vx % 10);
+        self.state.ram.set(address_register, (vx / 100) % 10)?;
+        self.state.ram.set(address_register + 1, (vx / 10) % 10)?;
+        self.state.ram.set(address_register + 2, vx % 10)?;
+        Ok(())
     }
 
-    /// Read registers V0 through Vx from memory starting at location I.
-    /// The interpreter reads values from memory starting at location I into registers V0 through Vx.
-    fn _fx55(&mut self, instruction: u16) {
+    /// Store registers V0 through Vx in memory starting at location I.
+    /// The interpreter copies the values of registers V0 through Vx into memory, starting at the address in I.
+    fn _fx55(&mut self, instruction: u16) -> Result<(), ExecError> {
         let last_register_index = Self::_x(instruction) as usize;
 
         for index in 0..last_register_index + 1 {
             let value: u8 = self.state.registers[index];
-            self.state.ram.set(self.state.i as usize + index, value);
+            self.state.ram.set(self.state.i as usize + index, value)?;
         }
+        self.advance_address_register(last_register_index);
+        Ok(())
     }
 
     /// Read registers V0 through Vx from memory starting at location I.
     /// The interpreter reads values from memory starting at location I into registers V0 through Vx.
-    fn _fx65(&mut self, instruction: u16) {
+    fn _fx65(&mut self, instruction: u16) -> Result<(), ExecError> {
         let last_register_index = Self::_x(instruction) as usize;
         let address_register = self.state.i as usize;
 
         for i in 0..last_register_index + 1 {
-            self.state.registers[i] = self.state.ram.get(address_register + i);
+            self.state.registers[i] = self.state.ram.get(address_register + i)?;
+        }
+        self.advance_address_register(last_register_index);
+        Ok(())
+    }
+
+    /// Register shifted by `8xy6` and `8xyE`.
+    fn shift_source(&self, instruction: u16) -> usize {
+        if self.quirks.shift_uses_vy {
+            Self::_y(instruction) as usize
+        } else {
+            Self::_x(instruction) as usize
+        }
+    }
+
+    /// Moves I past the registers accessed by `Fx55` and `Fx65`, as the memory quirk dictates.
+    fn advance_address_register(&mut self, last_register_index: usize) {
+        let increment = match self.quirks.memory {
+            MemoryQuirk::Unchanged => 0,
+            MemoryQuirk::IncrementByX => last_register_index,
+            MemoryQuirk::IncrementByXPlusOne => last_register_index + 1,
+        };
+        self.state.i = self.state.i.wrapping_add(increment as u16);
+    }
+
+    /// Registers from Vx to Vy, in descending order when x is greater than y.
+    fn register_range(instruction: u16) -> Vec<usize> {
+        let x = Self::_x(instruction) as usize;
+        let y = Self::_y(instruction) as usize;
+        if x <= y {
+            (x..=y).collect()
+        } else {
+            (y..=x).rev().collect()
+        }
+    }
+
+    fn key_state(keys: &Keys, key_index: usize) -> Result<KeyState, ExecError> {
+        if key_index >= NUM_KEYS {
+            return Err(ExecError::InvalidKey { key: key_index });
         }
+        Ok(keys[key_index])
     }
 
     fn _nnn(instruction: u16) -> u16 {
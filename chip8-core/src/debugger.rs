@@ -0,0 +1,220 @@
+use std::collections::BTreeSet;
+use std::fmt;
+use std::ops::Range;
+
+use crate::cpu::Chip8;
+use crate::error::ExecError;
+use crate::instruction::{decode, Instruction};
+use crate::keyboard::Keys;
+use crate::state::State;
+
+/// Why the debugger paused the machine.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Stop {
+    /// The program counter reached a breakpoint.
+    Breakpoint(usize),
+    /// An instruction wrote to a watched address.
+    Write(usize),
+    /// A single instruction was executed.
+    Step,
+    /// The program counter reached the address given to `run_to`.
+    Reached(usize),
+}
+
+impl fmt::Display for Stop {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Stop::Breakpoint(address) => write!(f, "breakpoint at 0x{:03X}", address),
+            Stop::Write(address) => write!(f, "write to 0x{:03X}", address),
+            Stop::Step => write!(f, "step"),
+            Stop::Reached(address) => write!(f, "reached 0x{:03X}", address),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Command {
+    Step,
+    RunTo(usize),
+}
+
+/// Runs a machine an instruction at a time, pausing it on breakpoints.
+///
+/// Frames are spread across calls to `run_frame` while paused, so the timers still
+/// count down once every `cycles_per_frame` instructions.
+#[derive(Default)]
+pub struct Debugger {
+    breakpoints: BTreeSet<usize>,
+    write_breakpoints: BTreeSet<usize>,
+    paused: bool,
+    command: Option<Command>,
+    // set when execution resumes, so that a breakpoint at the program counter is stepped over
+    resuming: bool,
+    // instructions executed in the current frame
+    cycle: usize,
+    frames: usize,
+}
+
+impl Debugger {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of frames run to completion.
+    pub fn frames(&self) -> usize {
+        self.frames
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn pause(&mut self) {
+        self.paused = true;
+        self.command = None;
+    }
+
+    pub fn resume(&mut self) {
+        self.paused = false;
+        self.command = None;
+        self.resuming = true;
+    }
+
+    /// Executes the next instruction, then pauses.
+    pub fn step(&mut self) {
+        self.paused = true;
+        self.command = Some(Command::Step);
+        self.resuming = true;
+    }
+
+    /// Like `step`, but runs a whole subroutine when the next instruction calls one.
+    pub fn step_over(&mut self, computer: &Chip8) {
+        let state = &computer.state;
+        match state
+            .instruction()
+            .ok()
+            .and_then(|opcode| decode(opcode).ok())
+        {
+            Some(Instruction::Call(_)) if !state.waiting_for_key => self.run_to(state.pc + 2),
+            _ => self.step(),
+        }
+    }
+
+    /// Runs until the program counter reaches `address`, or a breakpoint is hit.
+    pub fn run_to(&mut self, address: usize) {
+        self.paused = false;
+        self.command = Some(Command::RunTo(address));
+        self.resuming = true;
+    }
+
+    /// Forgets the frame in progress and any pending step or `run_to`, for when the machine
+    /// is replaced by a loaded or rewound one. Breakpoints are kept, and a paused machine
+    /// stays paused.
+    pub fn reset(&mut self) {
+        self.command = None;
+        self.resuming = false;
+        self.cycle = 0;
+    }
+
+    pub fn breakpoints(&self) -> &BTreeSet<usize> {
+        &self.breakpoints
+    }
+
+    pub fn write_breakpoints(&self) -> &BTreeSet<usize> {
+        &self.write_breakpoints
+    }
+
+    /// Sets a breakpoint at `address`, or removes the one already there.
+    /// Returns whether the breakpoint is now set.
+    pub fn toggle_breakpoint(&mut self, address: usize) -> bool {
+        toggle(&mut self.breakpoints, address)
+    }
+
+    /// Pauses whenever an instruction writes to `address`, or stops doing so.
+    /// Returns whether the breakpoint is now set.
+    pub fn toggle_write_breakpoint(&mut self, address: usize) -> bool {
+        toggle(&mut self.write_breakpoints, address)
+    }
+
+    /// Runs the rest of the current frame, stopping early on a breakpoint or after a step.
+    /// Does nothing while paused, unless a step was requested.
+    pub fn run_frame(
+        &mut self,
+        computer: &mut Chip8,
+        keys: &Keys,
+    ) -> Result<Option<Stop>, ExecError> {
+        if self.paused && self.command.is_none() {
+            return Ok(None);
+        }
+        if self.cycle >= computer.cycles_per_frame() {
+            // the previous call stopped after the last instruction of a frame
+            self.end_frame(computer);
+        }
+        if self.cycle == 0 {
+            computer.state.should_draw = false;
+        }
+        while self.cycle < computer.cycles_per_frame() {
+            let pc = computer.state.pc;
+            if !self.resuming {
+                if self.breakpoints.contains(&pc) {
+                    return Ok(Some(self.stop(Stop::Breakpoint(pc))));
+                }
+                if self.command == Some(Command::RunTo(pc)) {
+                    return Ok(Some(self.stop(Stop::Reached(pc))));
+                }
+            }
+            self.resuming = false;
+
+            let mut written = written_range(&computer.state);
+            computer.step(keys)?;
+            self.cycle += 1;
+            if let Some(address) = written.find(|address| self.write_breakpoints.contains(address))
+            {
+                return Ok(Some(self.stop(Stop::Write(address))));
+            }
+            if self.command == Some(Command::Step) {
+                return Ok(Some(self.stop(Stop::Step)));
+            }
+        }
+        self.end_frame(computer);
+        Ok(None)
+    }
+
+    fn end_frame(&mut self, computer: &mut Chip8) {
+        computer.end_frame();
+        self.cycle = 0;
+        self.frames += 1;
+    }
+
+    fn stop(&mut self, stop: Stop) -> Stop {
+        self.pause();
+        stop
+    }
+}
+
+fn toggle(addresses: &mut BTreeSet<usize>, address: usize) -> bool {
+    if addresses.remove(&address) {
+        false
+    } else {
+        addresses.insert(address);
+        true
+    }
+}
+
+/// Addresses the next instruction writes to memory.
+fn written_range(state: &State) -> Range<usize> {
+    let i = state.i as usize;
+    if state.halted || state.waiting_for_key {
+        return 0..0;
+    }
+    match state
+        .instruction()
+        .ok()
+        .and_then(|opcode| decode(opcode).ok())
+    {
+        Some(Instruction::LdB(_)) => i..i + 3,
+        Some(Instruction::LdMemoryVx(x)) => i..i + x as usize + 1,
+        Some(Instruction::Save(x, y)) => i..i + (x as i32 - y as i32).unsigned_abs() as usize + 1,
+        _ => 0..0,
+    }
+}
@@ -0,0 +1,190 @@
+use std::collections::VecDeque;
+use std::fmt;
+
+use crate::cpu::Chip8;
+use crate::error::ExecError;
+use crate::keyboard::Keys;
+use crate::trace::TraceRecord;
+
+/// Instructions listed before a divergence.
+const CONTEXT: usize = 8;
+/// Frames spent waiting for a key before giving up on the reference trace.
+const MAX_IDLE_FRAMES: usize = 60 * 60;
+
+/// A line of a reference trace that is not a record.
+#[derive(Clone, PartialEq, Debug)]
+pub struct ReferenceError {
+    pub line: usize,
+}
+
+impl fmt::Display for ReferenceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: invalid trace record", self.line)
+    }
+}
+
+impl std::error::Error for ReferenceError {}
+
+/// Parses a reference trace in the text format written by `Tracer`, see `TraceRecord::parse`.
+/// Blank lines and lines starting with `#` are skipped.
+pub fn parse_reference(source: &str) -> Result<Vec<TraceRecord>, ReferenceError> {
+    source
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty() && !line.trim_start().starts_with('#'))
+        .map(|(index, line)| TraceRecord::parse(line).ok_or(ReferenceError { line: index + 1 }))
+        .collect()
+}
+
+/// The first instruction at which the machine departed from the reference trace.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Divergence {
+    /// Index of the record in the reference trace.
+    pub index: usize,
+    pub expected: TraceRecord,
+    /// The machine as it was instead, nothing if it could not get there.
+    pub actual: Option<TraceRecord>,
+    /// Why the machine could not get there.
+    pub reason: Option<String>,
+    /// The instructions executed just before, which all matched the reference, oldest first.
+    pub context: Vec<TraceRecord>,
+}
+
+impl Divergence {
+    /// Names of the fields which differ, empty if the machine could not get there.
+    pub fn differences(&self) -> Vec<String> {
+        match &self.actual {
+            Some(actual) => differences(&self.expected, actual),
+            None => Vec::new(),
+        }
+    }
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "divergence at record {}", self.index)?;
+        for record in &self.context {
+            writeln!(f, "           {}", record)?;
+        }
+        writeln!(f, "expected:  {}", self.expected)?;
+        match &self.actual {
+            Some(actual) => {
+                writeln!(f, "actual:    {}", actual)?;
+                write!(f, "differs in {}", self.differences().join(", "))
+            }
+            None => write!(
+                f,
+                "actual:    {}",
+                self.reason.as_deref().unwrap_or("nothing")
+            ),
+        }
+    }
+}
+
+/// Runs `computer` an instruction at a time against the `reference` trace, comparing the
+/// program counter, opcode, registers and I before every instruction, and returns the first
+/// divergence. Timers are not compared, as emulators disagree on when they tick.
+/// `keys` gives the keys held during a frame.
+pub fn compare<F: FnMut(usize) -> Keys>(
+    computer: &mut Chip8,
+    reference: &[TraceRecord],
+    mut keys: F,
+) -> Option<Divergence> {
+    let mut context = VecDeque::with_capacity(CONTEXT);
+    let mut frame = 0;
+    let mut cycle = 0;
+    for (index, expected) in reference.iter().enumerate() {
+        let diverge = |actual, reason: Option<String>, context: &VecDeque<TraceRecord>| {
+            Some(Divergence {
+                index,
+                expected: *expected,
+                actual,
+                reason,
+                context: context.iter().copied().collect(),
+            })
+        };
+
+        // waiting for a key executes nothing, so no record is expected
+        let mut idle = 0;
+        while computer.state.waiting_for_key && idle < MAX_IDLE_FRAMES * computer.cycles_per_frame()
+        {
+            if let Err(error) = step(computer, &keys(frame), &mut cycle, &mut frame) {
+                return diverge(None, Some(error.to_string()), &context);
+            }
+            idle += 1;
+        }
+        let state = &computer.state;
+        if state.halted {
+            return diverge(None, Some("the program exited".to_string()), &context);
+        }
+        if state.waiting_for_key {
+            return diverge(None, Some("waiting for a key".to_string()), &context);
+        }
+        let opcode = match state.instruction() {
+            Ok(opcode) => opcode,
+            Err(error) => return diverge(None, Some(error.to_string()), &context),
+        };
+        // records are only written for executed instructions, so the index is the cycle
+        let actual = TraceRecord::new(index as u64, state, opcode);
+        if !differences(expected, &actual).is_empty() {
+            return diverge(Some(actual), None, &context);
+        }
+
+        if context.len() == CONTEXT {
+            context.pop_front();
+        }
+        context.push_back(actual);
+        let result = step(computer, &keys(frame), &mut cycle, &mut frame);
+        if let (Err(error), Some(next)) = (result, reference.get(index + 1)) {
+            return Some(Divergence {
+                index: index + 1,
+                expected: *next,
+                actual: None,
+                reason: Some(error.to_string()),
+                context: context.into_iter().collect(),
+            });
+        }
+    }
+    None
+}
+
+/// Executes an instruction, ending the frame once it holds `cycles_per_frame` of them.
+fn step(
+    computer: &mut Chip8,
+    keys: &Keys,
+    cycle: &mut usize,
+    frame: &mut usize,
+) -> Result<(), ExecError> {
+    computer.step(keys)?;
+    *cycle += 1;
+    if *cycle == computer.cycles_per_frame() {
+        computer.end_frame();
+        *cycle = 0;
+        *frame += 1;
+    }
+    Ok(())
+}
+
+fn differences(expected: &TraceRecord, actual: &TraceRecord) -> Vec<String> {
+    let mut fields = Vec::new();
+    if expected.pc != actual.pc {
+        fields.push("PC".to_string());
+    }
+    if expected.opcode != actual.opcode {
+        fields.push("opcode".to_string());
+    }
+    for (register, (expected, actual)) in expected
+        .registers
+        .iter()
+        .zip(actual.registers.iter())
+        .enumerate()
+    {
+        if expected != actual {
+            fields.push(format!("V{:X}", register));
+        }
+    }
+    if expected.i != actual.i {
+        fields.push("I".to_string());
+    }
+    fields
+}
@@ -1,7 +1,168 @@
+use std::io::{self, Write};
 
 pub use crate::state::DISPLAY_HEIGHT;
 pub use crate::state::DISPLAY_WIDTH;
+pub use crate::state::HIRES_DISPLAY_HEIGHT;
+pub use crate::state::HIRES_DISPLAY_WIDTH;
+
+pub const NUM_PLANES: usize = 2;
+pub const ALL_PLANES: u8 = 0b11;
+
+/// Pixels of the display, stored row by row.
+/// Each pixel is a bitmask of the bitplanes it is lit on, so its value ranges from 0 to 3.
+/// The resolution changes when a program switches between the low and high resolution modes.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Framebuffer {
+    width: usize,
+    height: usize,
+    pixels: Vec<u8>,
+}
+
+impl Framebuffer {
+    pub fn new(width: usize, height: usize) -> Self {
+        Framebuffer {
+            width,
+            height,
+            pixels: vec![0; width * height],
+        }
+    }
+
+    /// Restores pixels saved with `as_slice`.
+    pub fn from_pixels(width: usize, height: usize, pixels: Vec<u8>) -> Self {
+        assert_eq!(pixels.len(), width * height);
+        Framebuffer {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    pub fn as_slice(&self) -> &[u8] {
+        &self.pixels
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> u8 {
+        self.pixels[y * self.width + x]
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, value: u8) {
+        self.pixels[y * self.width + x] = value;
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item = &[u8]> {
+        self.pixels.chunks(self.width)
+    }
+
+    pub fn clear(&mut self) {
+        self.clear_planes(ALL_PLANES);
+    }
+
+    /// Clears the given bitplanes, leaving the others untouched.
+    pub fn clear_planes(&mut self, planes: u8) {
+        self.pixels.iter_mut().for_each(|px| *px &= !planes);
+    }
+
+    /// Changes the resolution, clearing the screen.
+    pub fn resize(&mut self, width: usize, height: usize) {
+        *self = Self::new(width, height);
+    }
+
+    /// Moves the given bitplanes down by `n` pixels, leaving blank rows at the top.
+    pub fn scroll_down(&mut self, n: usize, planes: u8) {
+        self.scroll(planes, |x, y| if y >= n { Some((x, y - n)) } else { None });
+    }
+
+    /// Moves the given bitplanes up by `n` pixels, leaving blank rows at the bottom.
+    pub fn scroll_up(&mut self, n: usize, planes: u8) {
+        let height = self.height;
+        self.scroll(planes, |x, y| {
+            if y + n < height {
+                Some((x, y + n))
+            } else {
+                None
+            }
+        });
+    }
+
+    /// Moves the given bitplanes right by `n` pixels, leaving blank columns on the left.
+    pub fn scroll_right(&mut self, n: usize, planes: u8) {
+        self.scroll(planes, |x, y| if x >= n { Some((x - n, y)) } else { None });
+    }
+
+    /// Moves the given bitplanes left by `n` pixels, leaving blank columns on the right.
+    pub fn scroll_left(&mut self, n: usize, planes: u8) {
+        let width = self.width;
+        self.scroll(planes, |x, y| {
+            if x + n < width {
+                Some((x + n, y))
+            } else {
+                None
+            }
+        });
+    }
+
+    /// Writes the pixels as a plain PBM image, a row per line, pixels lit on any bitplane being black.
+    pub fn write_pbm<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writeln!(writer, "P1")?;
+        writeln!(writer, "{} {}", self.width, self.height)?;
+        for row in self.rows() {
+            let line: String = row
+                .iter()
+                .map(|&pixel| if pixel == 0 { '0' } else { '1' })
+                .collect();
+            writeln!(writer, "{}", line)?;
+        }
+        Ok(())
+    }
+
+    /// Reads a plain PBM image, black pixels being lit on the first bitplane.
+    /// Returns nothing if the image is not a valid plain PBM.
+    pub fn read_pbm(source: &str) -> Option<Self> {
+        let mut words = source
+            .lines()
+            .map(|line| line.split('#').next().unwrap())
+            .flat_map(str::split_whitespace);
+        if words.next()? != "P1" {
+            return None;
+        }
+        let width = words.next()?.parse().ok()?;
+        let height = words.next()?.parse().ok()?;
+        // the pixels need not be separated
+        let pixels = words
+            .flat_map(str::chars)
+            .map(|pixel| match pixel {
+                '0' => Some(0),
+                '1' => Some(1),
+                _ => None,
+            })
+            .collect::<Option<Vec<u8>>>()?;
+        if pixels.len() != width * height {
+            return None;
+        }
+        Some(Self::from_pixels(width, height, pixels))
+    }
+
+    /// Rebuilds the given bitplanes, `source` mapping each pixel to the one it is copied from.
+    fn scroll<F: Fn(usize, usize) -> Option<(usize, usize)>>(&mut self, planes: u8, source: F) {
+        let previous = self.clone();
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let moved = source(x, y).map_or(0, |(x, y)| previous.get(x, y));
+                let kept = previous.get(x, y) & !planes;
+                self.set(x, y, kept | (moved & planes));
+            }
+        }
+    }
+}
 
 pub trait Display {
-    fn draw(&mut self, pixels: &[[u8; DISPLAY_WIDTH]; DISPLAY_HEIGHT]);
-}
\ No newline at end of file
+    fn draw(&mut self, framebuffer: &Framebuffer);
+}
@@ -0,0 +1,57 @@
+use std::error::Error;
+use std::fmt;
+
+/// Errors raised while executing a program.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ExecError {
+    /// The instruction at `address` does not decode to a known opcode.
+    UnknownOpcode { opcode: u16, address: usize },
+    /// A subroutine call was made with a full stack.
+    StackOverflow,
+    /// A return was made with an empty stack.
+    StackUnderflow,
+    /// A memory access was made outside of the RAM.
+    OutOfBounds { address: usize },
+    /// A key instruction referenced a key that does not exist.
+    InvalidKey { key: usize },
+}
+
+impl fmt::Display for ExecError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ExecError::UnknownOpcode { opcode, address } => {
+                write!(f, "unknown opcode {:04X} at {:03X}", opcode, address)
+            }
+            ExecError::StackOverflow => write!(f, "stack overflow"),
+            ExecError::StackUnderflow => write!(f, "stack underflow"),
+            ExecError::OutOfBounds { address } => {
+                write!(f, "memory access out of bounds at {:X}", address)
+            }
+            ExecError::InvalidKey { key } => write!(f, "invalid key {:X}", key),
+        }
+    }
+}
+
+impl Error for ExecError {}
+
+/// Errors raised while loading a program.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum LoadError {
+    /// The program does not fit in memory from `PROGRAM_START`.
+    RomTooLarge { size: usize, max: usize },
+    /// A save state holds a machine which cannot run, e.g. an unknown bitplane.
+    InvalidState { reason: &'static str },
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoadError::RomTooLarge { size, max } => {
+                write!(f, "ROM too large ({} bytes, max {})", size, max)
+            }
+            LoadError::InvalidState { reason } => write!(f, "invalid save state: {}", reason),
+        }
+    }
+}
+
+impl Error for LoadError {}
@@ -0,0 +1,470 @@
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream};
+
+use crate::cpu::Chip8;
+use crate::debugger::{Debugger, Stop};
+use crate::keyboard::Keys;
+
+/// Registers in the order they are numbered in, all of them being sent little-endian.
+/// V0-VF come first, followed by I, PC, the stack depth and the timers.
+const REGISTERS: &[(&str, usize)] = &[
+    ("v0", 1),
+    ("v1", 1),
+    ("v2", 1),
+    ("v3", 1),
+    ("v4", 1),
+    ("v5", 1),
+    ("v6", 1),
+    ("v7", 1),
+    ("v8", 1),
+    ("v9", 1),
+    ("va", 1),
+    ("vb", 1),
+    ("vc", 1),
+    ("vd", 1),
+    ("ve", 1),
+    ("vf", 1),
+    ("i", 2),
+    ("pc", 2),
+    ("sp", 1),
+    ("dt", 1),
+    ("st", 1),
+];
+const I: usize = 16;
+const PC: usize = 17;
+const SP: usize = 18;
+const DT: usize = 19;
+const ST: usize = 20;
+
+const INTERRUPT: u8 = 0x03;
+const ERROR: &str = "E01";
+const SIGINT: u8 = 2;
+const SIGILL: u8 = 4;
+const SIGTRAP: u8 = 5;
+
+/// Waits on localhost for a client speaking the GDB remote serial protocol.
+pub struct GdbServer {
+    listener: TcpListener,
+}
+
+impl GdbServer {
+    pub fn bind(port: u16) -> io::Result<Self> {
+        Ok(GdbServer {
+            listener: TcpListener::bind((Ipv4Addr::LOCALHOST, port))?,
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Waits for a client to attach.
+    pub fn accept(&self) -> io::Result<GdbSession> {
+        let (stream, _) = self.listener.accept()?;
+        stream.set_nodelay(true)?;
+        Ok(GdbSession::new(stream))
+    }
+}
+
+/// The stream a client talks over, which is polled for interrupts while the program runs.
+pub trait Connection: Read + Write {
+    /// Reads what the client sent without waiting, failing with `ErrorKind::WouldBlock`
+    /// when it sent nothing.
+    fn read_nonblocking(&mut self, buffer: &mut [u8]) -> io::Result<usize>;
+}
+
+impl Connection for TcpStream {
+    fn read_nonblocking(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        self.set_nonblocking(true)?;
+        let result = self.read(buffer);
+        self.set_nonblocking(false)?;
+        result
+    }
+}
+
+/// A client attached to the machine, which stays paused until it continues or steps.
+pub struct GdbSession<C: Connection = TcpStream> {
+    stream: C,
+    debugger: Debugger,
+}
+
+impl<C: Connection> GdbSession<C> {
+    pub fn new(stream: C) -> Self {
+        let mut debugger = Debugger::new();
+        debugger.pause();
+        GdbSession { stream, debugger }
+    }
+
+    pub fn into_connection(self) -> C {
+        self.stream
+    }
+
+    /// Serves the client until it detaches or kills the program.
+    /// `keys` gives the keys held down during each frame.
+    pub fn serve<F: FnMut(usize) -> Keys>(
+        &mut self,
+        computer: &mut Chip8,
+        mut keys: F,
+    ) -> io::Result<()> {
+        while let Some(packet) = self.receive()? {
+            let reply = match packet.as_bytes().first() {
+                Some(b'?') => stop_reply(SIGTRAP),
+                Some(b'g') => read_registers(computer),
+                Some(b'G') => ok_or_error(write_registers(computer, &packet[1..])),
+                Some(b'p') => parse_hex(&packet[1..])
+                    .and_then(|register| read_register(computer, register))
+                    .unwrap_or_else(|| ERROR.to_string()),
+                Some(b'P') => ok_or_error(write_register(computer, &packet[1..])),
+                Some(b'm') => {
+                    read_memory(computer, &packet[1..]).unwrap_or_else(|| ERROR.to_string())
+                }
+                Some(b'M') => ok_or_error(write_memory(computer, &packet[1..])),
+                // read and access watchpoints are not supported
+                Some(b'Z') | Some(b'z') if !matches!(packet.get(1..2), Some("0" | "1" | "2")) => {
+                    String::new()
+                }
+                Some(b'Z') => ok_or_error(self.set_breakpoint(&packet[1..], true)),
+                Some(b'z') => ok_or_error(self.set_breakpoint(&packet[1..], false)),
+                Some(b'c') | Some(b's') => {
+                    // resuming at another address is optional, as in `c addr`
+                    if let Some(address) = parse_hex(&packet[1..]) {
+                        computer.state.pc = address;
+                    }
+                    if packet.starts_with('s') {
+                        self.debugger.step();
+                    } else {
+                        self.debugger.resume();
+                    }
+                    match self.run(computer, &mut keys)? {
+                        Some(reply) => reply,
+                        None => return Ok(()),
+                    }
+                }
+                Some(b'H') => "OK".to_string(),
+                Some(b'k') => return Ok(()),
+                Some(b'D') => {
+                    self.send("OK")?;
+                    return Ok(());
+                }
+                Some(b'q') => query(&packet),
+                // anything else is not supported, which clients expect an empty reply for
+                _ => String::new(),
+            };
+            self.send(&reply)?;
+        }
+        Ok(())
+    }
+
+    /// Runs until the debugger stops, returning the stop reply,
+    /// or nothing if the client went away in the meantime.
+    fn run<F: FnMut(usize) -> Keys>(
+        &mut self,
+        computer: &mut Chip8,
+        keys: &mut F,
+    ) -> io::Result<Option<String>> {
+        loop {
+            if computer.state.halted {
+                return Ok(Some("W00".to_string()));
+            }
+            let frame_keys = keys(self.debugger.frames());
+            match self.debugger.run_frame(computer, &frame_keys) {
+                Ok(Some(Stop::Write(address))) => {
+                    return Ok(Some(format!("T{:02x}watch:{:x};", SIGTRAP, address)))
+                }
+                Ok(Some(_)) => return Ok(Some(stop_reply(SIGTRAP))),
+                Ok(None) => {}
+                Err(_) => {
+                    self.debugger.pause();
+                    return Ok(Some(stop_reply(SIGILL)));
+                }
+            }
+            match self.poll_interrupt()? {
+                Some(true) => {
+                    self.debugger.pause();
+                    return Ok(Some(stop_reply(SIGINT)));
+                }
+                Some(false) => {}
+                None => return Ok(None),
+            }
+        }
+    }
+
+    /// Checks whether the client asked to interrupt the program, without waiting.
+    /// Returns nothing once the client disconnected.
+    fn poll_interrupt(&mut self) -> io::Result<Option<bool>> {
+        let mut byte = [0; 1];
+        match self.stream.read_nonblocking(&mut byte) {
+            Ok(0) => Ok(None),
+            Ok(_) => Ok(Some(byte[0] == INTERRUPT)),
+            Err(error) if error.kind() == ErrorKind::WouldBlock => Ok(Some(false)),
+            Err(error) => Err(error),
+        }
+    }
+
+    /// `Z` and `z` packets: `type,address,kind`, software and hardware breakpoints
+    /// being set on the program counter, and write watchpoints (type 2) on memory.
+    fn set_breakpoint(&mut self, arguments: &str, set: bool) -> Option<()> {
+        let mut fields = arguments.split(',');
+        let kind = fields.next()?;
+        let address = parse_hex(fields.next()?)?;
+        let length = parse_hex(fields.next()?)?;
+        match kind {
+            "0" | "1" => {
+                if self.debugger.breakpoints().contains(&address) != set {
+                    self.debugger.toggle_breakpoint(address);
+                }
+            }
+            "2" => {
+                for address in address..address.saturating_add(length) {
+                    if self.debugger.write_breakpoints().contains(&address) != set {
+                        self.debugger.toggle_write_breakpoint(address);
+                    }
+                }
+            }
+            _ => return None,
+        }
+        Some(())
+    }
+
+    /// Reads the next packet, acknowledging it, or nothing once the client disconnected.
+    fn receive(&mut self) -> io::Result<Option<String>> {
+        loop {
+            // acknowledgements and interrupts sent while paused are skipped
+            match self.read_byte()? {
+                Some(b'$') => {}
+                Some(_) => continue,
+                None => return Ok(None),
+            }
+            let mut data = Vec::new();
+            loop {
+                match self.read_byte()? {
+                    Some(b'#') => break,
+                    Some(byte) => data.push(byte),
+                    None => return Ok(None),
+                }
+            }
+            let mut checksum = [0; 2];
+            self.stream.read_exact(&mut checksum)?;
+            let expected = std::str::from_utf8(&checksum)
+                .ok()
+                .and_then(|checksum| u8::from_str_radix(checksum, 16).ok());
+            if expected == Some(checksum_of(&data)) {
+                self.stream.write_all(b"+")?;
+                return Ok(Some(String::from_utf8_lossy(&data).into_owned()));
+            }
+            self.stream.write_all(b"-")?;
+        }
+    }
+
+    fn read_byte(&mut self) -> io::Result<Option<u8>> {
+        let mut byte = [0; 1];
+        match self.stream.read(&mut byte)? {
+            0 => Ok(None),
+            _ => Ok(Some(byte[0])),
+        }
+    }
+
+    fn send(&mut self, data: &str) -> io::Result<()> {
+        let packet = format!("${}#{:02x}", data, checksum_of(data.as_bytes()));
+        self.stream.write_all(packet.as_bytes())?;
+        self.stream.flush()
+    }
+}
+
+fn checksum_of(data: &[u8]) -> u8 {
+    data.iter().fold(0, |sum: u8, &byte| sum.wrapping_add(byte))
+}
+
+fn stop_reply(signal: u8) -> String {
+    format!("S{:02x}", signal)
+}
+
+fn ok_or_error(result: Option<()>) -> String {
+    match result {
+        Some(()) => "OK".to_string(),
+        None => ERROR.to_string(),
+    }
+}
+
+fn query(packet: &str) -> String {
+    if packet.starts_with("qSupported") {
+        "PacketSize=1000;qXfer:features:read+".to_string()
+    } else if packet == "qAttached" {
+        "1".to_string()
+    } else if packet == "qC" {
+        "QC1".to_string()
+    } else if packet == "qfThreadInfo" {
+        "m1".to_string()
+    } else if packet == "qsThreadInfo" {
+        "l".to_string()
+    } else if let Some(range) = packet.strip_prefix("qXfer:features:read:target.xml:") {
+        read_target_description(range).unwrap_or_else(|| ERROR.to_string())
+    } else {
+        String::new()
+    }
+}
+
+/// Describes the registers, as there is no CHIP-8 architecture built into GDB.
+fn target_description() -> String {
+    let mut xml = String::from(
+        "<?xml version=\"1.0\"?><!DOCTYPE target SYSTEM \"gdb-target.dtd\">\
+         <target version=\"1.0\"><feature name=\"org.chip8.core\">",
+    );
+    for (index, &(name, size)) in REGISTERS.iter().enumerate() {
+        let kind = match index {
+            I => " type=\"data_ptr\"",
+            PC => " type=\"code_ptr\"",
+            _ => "",
+        };
+        xml.push_str(&format!(
+            "<reg name=\"{}\" bitsize=\"{}\" regnum=\"{}\"{}/>",
+            name,
+            size * 8,
+            index,
+            kind
+        ));
+    }
+    xml.push_str("</feature></target>");
+    xml
+}
+
+/// Reads `offset,length` bytes of the target description, `l` marking the last chunk.
+fn read_target_description(range: &str) -> Option<String> {
+    let mut fields = range.split(',');
+    let offset = parse_hex(fields.next()?)?;
+    let length = parse_hex(fields.next()?)?;
+    let xml = target_description();
+    let start = offset.min(xml.len());
+    let end = offset.saturating_add(length).min(xml.len());
+    let marker = if end == xml.len() { 'l' } else { 'm' };
+    Some(format!("{}{}", marker, &xml[start..end]))
+}
+
+fn register_value(computer: &Chip8, register: usize) -> Option<usize> {
+    let state = &computer.state;
+    match register {
+        0..=15 => Some(state.registers[register] as usize),
+        I => Some(state.i as usize),
+        PC => Some(state.pc),
+        SP => Some(state.stack.as_slice().len()),
+        DT => Some(state.delay_timer as usize),
+        ST => Some(state.sound_timer as usize),
+        _ => None,
+    }
+}
+
+fn set_register_value(computer: &mut Chip8, register: usize, value: usize) -> Option<()> {
+    let state = &mut computer.state;
+    match register {
+        0..=15 => state.registers[register] = value as u8,
+        I => state.i = value as u16,
+        PC => state.pc = value,
+        SP => {
+            // the stack is resized, new entries being zero
+            while state.stack.as_slice().len() > value {
+                state.stack.pop().ok()?;
+            }
+            while state.stack.as_slice().len() < value {
+                state.stack.push(0).ok()?;
+            }
+        }
+        DT => state.delay_timer = value as u8,
+        ST => state.sound_timer = value as u8,
+        _ => return None,
+    }
+    Some(())
+}
+
+fn read_register(computer: &Chip8, register: usize) -> Option<String> {
+    let value = register_value(computer, register)?;
+    Some(to_hex(&value.to_le_bytes()[..REGISTERS[register].1]))
+}
+
+fn read_registers(computer: &Chip8) -> String {
+    (0..REGISTERS.len())
+        .map(|register| read_register(computer, register).unwrap())
+        .collect()
+}
+
+/// `P` packets: `register=value`.
+fn write_register(computer: &mut Chip8, arguments: &str) -> Option<()> {
+    let mut fields = arguments.splitn(2, '=');
+    let register = parse_hex(fields.next()?)?;
+    let size = REGISTERS.get(register)?.1;
+    let bytes = from_hex(fields.next()?)?;
+    if bytes.len() != size {
+        return None;
+    }
+    set_register_value(computer, register, little_endian(&bytes))
+}
+
+fn write_registers(computer: &mut Chip8, data: &str) -> Option<()> {
+    let bytes = from_hex(data)?;
+    if bytes.len() != REGISTERS.iter().map(|&(_, size)| size).sum::<usize>() {
+        return None;
+    }
+    let mut offset = 0;
+    for (register, &(_, size)) in REGISTERS.iter().enumerate() {
+        let value = little_endian(&bytes[offset..offset + size]);
+        set_register_value(computer, register, value)?;
+        offset += size;
+    }
+    Some(())
+}
+
+/// `m` packets: `address,length`.
+fn read_memory(computer: &Chip8, arguments: &str) -> Option<String> {
+    let mut fields = arguments.split(',');
+    let address = parse_hex(fields.next()?)?;
+    let length = parse_hex(fields.next()?)?;
+    let bytes = computer
+        .state
+        .ram
+        .as_slice()
+        .get(address..address.checked_add(length)?)?;
+    Some(to_hex(bytes))
+}
+
+/// `M` packets: `address,length:bytes`.
+fn write_memory(computer: &mut Chip8, arguments: &str) -> Option<()> {
+    let mut fields = arguments.splitn(2, ':');
+    let mut range = fields.next()?.split(',');
+    let address = parse_hex(range.next()?)?;
+    let length = parse_hex(range.next()?)?;
+    let bytes = from_hex(fields.next()?)?;
+    if bytes.len() != length {
+        return None;
+    }
+    computer
+        .state
+        .ram
+        .as_mut_slice()
+        .get_mut(address..address.checked_add(length)?)?
+        .copy_from_slice(&bytes);
+    Some(())
+}
+
+fn little_endian(bytes: &[u8]) -> usize {
+    bytes
+        .iter()
+        .rev()
+        .fold(0, |value, &byte| value << 8 | byte as usize)
+}
+
+fn parse_hex(digits: &str) -> Option<usize> {
+    usize::from_str_radix(digits, 16).ok()
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn from_hex(digits: &str) -> Option<Vec<u8>> {
+    if digits.len() % 2 != 0 {
+        return None;
+    }
+    (0..digits.len())
+        .step_by(2)
+        .map(|index| u8::from_str_radix(digits.get(index..index + 2)?, 16).ok())
+        .collect()
+}
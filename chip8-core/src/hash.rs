@@ -0,0 +1,7 @@
+/// 64-bit FNV-1a hash, which identifies the ROM a movie plays and compares memory contents
+/// between runs.
+pub fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xCBF2_9CE4_8422_2325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01B3)
+    })
+}
@@ -0,0 +1,289 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Error, ErrorKind, Write};
+use std::path::Path;
+
+use crate::display::Framebuffer;
+
+/// Colors of the pixels, indexed by the bitplanes they are lit on.
+pub type Palette = [[u8; 3]; 4];
+
+/// Black and white, the second bitplane being drawn in grays.
+pub const DEFAULT_PALETTE: Palette = [[0, 0, 0], [255, 255, 255], [170, 170, 170], [85, 85, 85]];
+
+const PNG_SIGNATURE: &[u8] = &[0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n'];
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ImageFormat {
+    Png,
+    /// Binary PPM, which most image tools read.
+    Ppm,
+}
+
+impl ImageFormat {
+    /// Guesses the format from the extension of `path`.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Option<Self> {
+        let extension = path.as_ref().extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "png" => Some(ImageFormat::Png),
+            "ppm" => Some(ImageFormat::Ppm),
+            _ => None,
+        }
+    }
+}
+
+/// Parses a palette written as comma separated `rrggbb` colors, for the unlit pixels and those
+/// lit on the first bitplane, then optionally those lit on the second bitplane and on both.
+pub fn parse_palette(colors: &str) -> Option<Palette> {
+    let colors = colors
+        .split(',')
+        .map(|color| {
+            let color = color.trim().trim_start_matches('#');
+            let value = u32::from_str_radix(color, 16)
+                .ok()
+                .filter(|_| color.len() == 6)?;
+            Some([(value >> 16) as u8, (value >> 8) as u8, value as u8])
+        })
+        .collect::<Option<Vec<_>>>()?;
+    match *colors.as_slice() {
+        [off, on] => Some([off, on, DEFAULT_PALETTE[2], DEFAULT_PALETTE[3]]),
+        [off, first, second, both] => Some([off, first, second, both]),
+        _ => None,
+    }
+}
+
+/// Writes the framebuffer to `path`, in the format its extension names, each pixel
+/// being drawn as a `scale` x `scale` square.
+pub fn save<P: AsRef<Path>>(
+    path: P,
+    framebuffer: &Framebuffer,
+    scale: usize,
+    palette: &Palette,
+) -> io::Result<()> {
+    let format = ImageFormat::from_path(&path)
+        .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "unknown image format"))?;
+    let mut writer = BufWriter::new(File::create(path)?);
+    match format {
+        ImageFormat::Png => write_png(&mut writer, framebuffer, scale, palette)?,
+        ImageFormat::Ppm => write_ppm(&mut writer, framebuffer, scale, palette)?,
+    }
+    writer.flush()
+}
+
+pub fn write_ppm<W: Write>(
+    writer: &mut W,
+    framebuffer: &Framebuffer,
+    scale: usize,
+    palette: &Palette,
+) -> io::Result<()> {
+    let (width, height, pixels) = scaled(framebuffer, scale);
+    write!(writer, "P6\n{} {}\n255\n", width, height)?;
+    let rgb: Vec<u8> = pixels
+        .iter()
+        .flat_map(|&pixel| palette[pixel as usize].iter().copied())
+        .collect();
+    writer.write_all(&rgb)
+}
+
+/// Writes an indexed PNG, compressed with runs of repeated pixels and rows.
+pub fn write_png<W: Write>(
+    writer: &mut W,
+    framebuffer: &Framebuffer,
+    scale: usize,
+    palette: &Palette,
+) -> io::Result<()> {
+    let (width, height, pixels) = scaled(framebuffer, scale);
+    writer.write_all(PNG_SIGNATURE)?;
+
+    let mut header = Vec::new();
+    header.extend_from_slice(&(width as u32).to_be_bytes());
+    header.extend_from_slice(&(height as u32).to_be_bytes());
+    // 8 bits per pixel, indexed colors, no interlacing
+    header.extend_from_slice(&[8, 3, 0, 0, 0]);
+    write_chunk(writer, b"IHDR", &header)?;
+    let colors: Vec<u8> = palette.iter().flatten().copied().collect();
+    write_chunk(writer, b"PLTE", &colors)?;
+
+    // every row starts with its filter: rows repeating the previous one are filtered
+    // against it, which turns them to zeros
+    let mut filtered = Vec::with_capacity((width + 1) * height);
+    let mut previous: Option<&[u8]> = None;
+    for row in pixels.chunks(width) {
+        if previous == Some(row) {
+            filtered.push(2);
+            filtered.resize(filtered.len() + width, 0);
+        } else {
+            filtered.push(0);
+            filtered.extend_from_slice(row);
+        }
+        previous = Some(row);
+    }
+    write_chunk(writer, b"IDAT", &zlib(&filtered))?;
+    write_chunk(writer, b"IEND", &[])
+}
+
+/// Pixels of the framebuffer scaled up, along with the width and height of the image.
+pub(crate) fn scaled(framebuffer: &Framebuffer, scale: usize) -> (usize, usize, Vec<u8>) {
+    let scale = scale.max(1);
+    let width = framebuffer.width() * scale;
+    let height = framebuffer.height() * scale;
+    let mut pixels = Vec::with_capacity(width * height);
+    for row in framebuffer.rows() {
+        let mut scaled_row = Vec::with_capacity(width);
+        for &pixel in row {
+            scaled_row.resize(scaled_row.len() + scale, pixel);
+        }
+        for _ in 0..scale {
+            pixels.extend_from_slice(&scaled_row);
+        }
+    }
+    (width, height, pixels)
+}
+
+fn write_chunk<W: Write>(writer: &mut W, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    writer.write_all(&(data.len() as u32).to_be_bytes())?;
+    writer.write_all(kind)?;
+    writer.write_all(data)?;
+    let crc = crc32(kind.iter().chain(data));
+    writer.write_all(&crc.to_be_bytes())
+}
+
+fn crc32<'a, I: IntoIterator<Item = &'a u8>>(bytes: I) -> u32 {
+    let crc = bytes.into_iter().fold(!0u32, |mut crc, &byte| {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                0xEDB8_8320 ^ (crc >> 1)
+            } else {
+                crc >> 1
+            };
+        }
+        crc
+    });
+    !crc
+}
+
+fn adler32(bytes: &[u8]) -> u32 {
+    let (a, b) = bytes.iter().fold((1u32, 0u32), |(a, b), &byte| {
+        let a = (a + byte as u32) % 65521;
+        (a, (b + a) % 65521)
+    });
+    b << 16 | a
+}
+
+/// Writes bits least significant first, as deflate and GIF expect.
+#[derive(Default)]
+pub(crate) struct BitWriter {
+    bytes: Vec<u8>,
+    buffer: u32,
+    length: u32,
+}
+
+impl BitWriter {
+    pub(crate) fn write(&mut self, value: u32, bits: u32) {
+        self.buffer |= value << self.length;
+        self.length += bits;
+        while self.length >= 8 {
+            self.bytes.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.length -= 8;
+        }
+    }
+
+    /// Writes a Huffman code, which deflate stores most significant bit first.
+    fn write_code(&mut self, code: u32, bits: u32) {
+        let reversed = code.reverse_bits() >> (32 - bits);
+        self.write(reversed, bits);
+    }
+
+    pub(crate) fn finish(mut self) -> Vec<u8> {
+        if self.length > 0 {
+            self.bytes.push(self.buffer as u8);
+        }
+        self.bytes
+    }
+}
+
+// first length of every length code from 257, and its extra bits
+const LENGTHS: [(u32, u32); 29] = [
+    (3, 0),
+    (4, 0),
+    (5, 0),
+    (6, 0),
+    (7, 0),
+    (8, 0),
+    (9, 0),
+    (10, 0),
+    (11, 1),
+    (13, 1),
+    (15, 1),
+    (17, 1),
+    (19, 2),
+    (23, 2),
+    (27, 2),
+    (31, 2),
+    (35, 3),
+    (43, 3),
+    (51, 3),
+    (59, 3),
+    (67, 4),
+    (83, 4),
+    (99, 4),
+    (115, 4),
+    (131, 5),
+    (163, 5),
+    (195, 5),
+    (227, 5),
+    (258, 0),
+];
+
+/// Compresses `data` in a zlib stream made of a single deflate block with the fixed codes,
+/// where runs of a repeated byte are copied from the byte before them.
+fn zlib(data: &[u8]) -> Vec<u8> {
+    let mut bits = BitWriter::default();
+    // last block, fixed Huffman codes
+    bits.write(1, 1);
+    bits.write(1, 2);
+    let mut index = 0;
+    while index < data.len() {
+        let run = if index == 0 {
+            0
+        } else {
+            data[index..]
+                .iter()
+                .take(258)
+                .take_while(|&&byte| byte == data[index - 1])
+                .count()
+        };
+        if run >= 3 {
+            let code = LENGTHS
+                .iter()
+                .rposition(|&(base, _)| base <= run as u32)
+                .unwrap();
+            let (base, extra) = LENGTHS[code];
+            write_symbol(&mut bits, 257 + code as u32);
+            bits.write(run as u32 - base, extra);
+            // distance code 0 copies from 1 byte back
+            bits.write_code(0, 5);
+            index += run;
+        } else {
+            write_symbol(&mut bits, data[index] as u32);
+            index += 1;
+        }
+    }
+    write_symbol(&mut bits, 256);
+
+    let mut stream = vec![0x78, 0x01];
+    stream.extend(bits.finish());
+    stream.extend_from_slice(&adler32(data).to_be_bytes());
+    stream
+}
+
+/// Writes a literal, length or end of block symbol with the fixed Huffman codes.
+fn write_symbol(bits: &mut BitWriter, symbol: u32) {
+    match symbol {
+        0..=143 => bits.write_code(0x30 + symbol, 8),
+        144..=255 => bits.write_code(0x190 + symbol - 144, 9),
+        256..=279 => bits.write_code(symbol - 256, 7),
+        _ => bits.write_code(0xC0 + symbol - 280, 8),
+    }
+}
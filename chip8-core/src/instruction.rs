@@ -0,0 +1,356 @@
+use std::error::Error;
+use std::fmt;
+
+/// Instruction set an instruction was introduced by.
+#[derive(Clone, Copy, PartialEq, PartialOrd, Debug)]
+pub enum Extension {
+    Chip8,
+    SuperChip,
+    XoChip,
+}
+
+/// A decoded instruction, registers being referred to by their index.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Instruction {
+    /// `00E0` - CLS
+    Cls,
+    /// `00EE` - RET
+    Ret,
+    /// `00Cn` - SCD n
+    ScrollDown(u8),
+    /// `00Dn` - SCU n
+    ScrollUp(u8),
+    /// `00FB` - SCR
+    ScrollRight,
+    /// `00FC` - SCL
+    ScrollLeft,
+    /// `00FD` - EXIT
+    Exit,
+    /// `00FE` - LOW
+    Low,
+    /// `00FF` - HIGH
+    High,
+    /// `1nnn` - JP addr
+    Jp(u16),
+    /// `2nnn` - CALL addr
+    Call(u16),
+    /// `3xkk` - SE Vx, byte
+    SeByte(u8, u8),
+    /// `4xkk` - SNE Vx, byte
+    SneByte(u8, u8),
+    /// `5xy0` - SE Vx, Vy
+    SeRegister(u8, u8),
+    /// `5xy2` - SAVE Vx, Vy
+    Save(u8, u8),
+    /// `5xy3` - LOAD Vx, Vy
+    Load(u8, u8),
+    /// `6xkk` - LD Vx, byte
+    LdByte(u8, u8),
+    /// `7xkk` - ADD Vx, byte
+    AddByte(u8, u8),
+    /// `8xy0` - LD Vx, Vy
+    LdRegister(u8, u8),
+    /// `8xy1` - OR Vx, Vy
+    Or(u8, u8),
+    /// `8xy2` - AND Vx, Vy
+    And(u8, u8),
+    /// `8xy3` - XOR Vx, Vy
+    Xor(u8, u8),
+    /// `8xy4` - ADD Vx, Vy
+    AddRegister(u8, u8),
+    /// `8xy5` - SUB Vx, Vy
+    Sub(u8, u8),
+    /// `8xy6` - SHR Vx, Vy
+    Shr(u8, u8),
+    /// `8xy7` - SUBN Vx, Vy
+    Subn(u8, u8),
+    /// `8xyE` - SHL Vx, Vy
+    Shl(u8, u8),
+    /// `9xy0` - SNE Vx, Vy
+    SneRegister(u8, u8),
+    /// `Annn` - LD I, addr
+    LdI(u16),
+    /// `Bnnn` - JP V0, addr
+    JpV0(u16),
+    /// `Cxkk` - RND Vx, byte
+    Rnd(u8, u8),
+    /// `Dxyn` - DRW Vx, Vy, n
+    Drw(u8, u8, u8),
+    /// `Ex9E` - SKP Vx
+    Skp(u8),
+    /// `ExA1` - SKNP Vx
+    Sknp(u8),
+    /// `F000 nnnn` - LD I, LONG addr, the address being stored in the 2 bytes that follow
+    LdILong,
+    /// `Fn01` - PLANE n
+    Plane(u8),
+    /// `F002` - AUDIO
+    Audio,
+    /// `Fx07` - LD Vx, DT
+    LdVxDt(u8),
+    /// `Fx0A` - LD Vx, K
+    LdVxK(u8),
+    /// `Fx15` - LD DT, Vx
+    LdDtVx(u8),
+    /// `Fx18` - LD ST, Vx
+    LdStVx(u8),
+    /// `Fx1E` - ADD I, Vx
+    AddI(u8),
+    /// `Fx29` - LD F, Vx
+    LdF(u8),
+    /// `Fx30` - LD HF, Vx
+    LdHf(u8),
+    /// `Fx33` - LD B, Vx
+    LdB(u8),
+    /// `Fx3A` - PITCH Vx
+    Pitch(u8),
+    /// `Fx55` - LD [I], Vx
+    LdMemoryVx(u8),
+    /// `Fx65` - LD Vx, [I]
+    LdVxMemory(u8),
+    /// `Fx75` - LD R, Vx
+    LdRVx(u8),
+    /// `Fx85` - LD Vx, R
+    LdVxR(u8),
+}
+
+/// The opcode does not match any instruction.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct DecodeError {
+    pub opcode: u16,
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "unknown opcode {:04X}", self.opcode)
+    }
+}
+
+impl Error for DecodeError {}
+
+pub fn decode(opcode: u16) -> Result<Instruction, DecodeError> {
+    let nnn = opcode & 0x0FFF;
+    let n = (opcode & 0x000F) as u8;
+    let x = ((opcode & 0x0F00) >> 8) as u8;
+    let y = ((opcode & 0x00F0) >> 4) as u8;
+    let kk = (opcode & 0x00FF) as u8;
+
+    let instruction = match opcode & 0xF000 {
+        0x0000 => match opcode {
+            0x00E0 => Instruction::Cls,
+            0x00EE => Instruction::Ret,
+            0x00FB => Instruction::ScrollRight,
+            0x00FC => Instruction::ScrollLeft,
+            0x00FD => Instruction::Exit,
+            0x00FE => Instruction::Low,
+            0x00FF => Instruction::High,
+            _ if opcode & 0xFFF0 == 0x00C0 => Instruction::ScrollDown(n),
+            _ if opcode & 0xFFF0 == 0x00D0 => Instruction::ScrollUp(n),
+            _ => return Err(DecodeError { opcode }),
+        },
+        0x1000 => Instruction::Jp(nnn),
+        0x2000 => Instruction::Call(nnn),
+        0x3000 => Instruction::SeByte(x, kk),
+        0x4000 => Instruction::SneByte(x, kk),
+        0x5000 => match n {
+            0x0 => Instruction::SeRegister(x, y),
+            0x2 => Instruction::Save(x, y),
+            0x3 => Instruction::Load(x, y),
+            _ => return Err(DecodeError { opcode }),
+        },
+        0x6000 => Instruction::LdByte(x, kk),
+        0x7000 => Instruction::AddByte(x, kk),
+        0x8000 => match n {
+            0x0 => Instruction::LdRegister(x, y),
+            0x1 => Instruction::Or(x, y),
+            0x2 => Instruction::And(x, y),
+            0x3 => Instruction::Xor(x, y),
+            0x4 => Instruction::AddRegister(x, y),
+            0x5 => Instruction::Sub(x, y),
+            0x6 => Instruction::Shr(x, y),
+            0x7 => Instruction::Subn(x, y),
+            0xE => Instruction::Shl(x, y),
+            _ => return Err(DecodeError { opcode }),
+        },
+        0x9000 if n == 0 => Instruction::SneRegister(x, y),
+        0xA000 => Instruction::LdI(nnn),
+        0xB000 => Instruction::JpV0(nnn),
+        0xC000 => Instruction::Rnd(x, kk),
+        0xD000 => Instruction::Drw(x, y, n),
+        0xE000 => match kk {
+            0x9E => Instruction::Skp(x),
+            0xA1 => Instruction::Sknp(x),
+            _ => return Err(DecodeError { opcode }),
+        },
+        0xF000 => match kk {
+            0x00 if x == 0 => Instruction::LdILong,
+            0x01 => Instruction::Plane(x),
+            0x02 if x == 0 => Instruction::Audio,
+            0x07 => Instruction::LdVxDt(x),
+            0x0A => Instruction::LdVxK(x),
+            0x15 => Instruction::LdDtVx(x),
+            0x18 => Instruction::LdStVx(x),
+            0x1E => Instruction::AddI(x),
+            0x29 => Instruction::LdF(x),
+            0x30 => Instruction::LdHf(x),
+            0x33 => Instruction::LdB(x),
+            0x3A => Instruction::Pitch(x),
+            0x55 => Instruction::LdMemoryVx(x),
+            0x65 => Instruction::LdVxMemory(x),
+            0x75 => Instruction::LdRVx(x),
+            0x85 => Instruction::LdVxR(x),
+            _ => return Err(DecodeError { opcode }),
+        },
+        _ => return Err(DecodeError { opcode }),
+    };
+    Ok(instruction)
+}
+
+impl Instruction {
+    /// Size of the instruction in memory, in bytes.
+    pub fn size(&self) -> usize {
+        match self {
+            Instruction::LdILong => 4,
+            _ => 2,
+        }
+    }
+
+    /// Opcode of the instruction, the inverse of `decode`.
+    /// The address following `LD I, LONG` is not part of it.
+    pub fn encode(&self) -> u16 {
+        let xy = |high: u16, x: u8, y: u8, n: u16| high | u16::from(x) << 8 | u16::from(y) << 4 | n;
+        let xkk = |high: u16, x: u8, kk: u8| high | u16::from(x) << 8 | u16::from(kk);
+        match *self {
+            Instruction::Cls => 0x00E0,
+            Instruction::Ret => 0x00EE,
+            Instruction::ScrollDown(n) => 0x00C0 | u16::from(n),
+            Instruction::ScrollUp(n) => 0x00D0 | u16::from(n),
+            Instruction::ScrollRight => 0x00FB,
+            Instruction::ScrollLeft => 0x00FC,
+            Instruction::Exit => 0x00FD,
+            Instruction::Low => 0x00FE,
+            Instruction::High => 0x00FF,
+            Instruction::Jp(nnn) => 0x1000 | nnn,
+            Instruction::Call(nnn) => 0x2000 | nnn,
+            Instruction::SeByte(x, kk) => xkk(0x3000, x, kk),
+            Instruction::SneByte(x, kk) => xkk(0x4000, x, kk),
+            Instruction::SeRegister(x, y) => xy(0x5000, x, y, 0x0),
+            Instruction::Save(x, y) => xy(0x5000, x, y, 0x2),
+            Instruction::Load(x, y) => xy(0x5000, x, y, 0x3),
+            Instruction::LdByte(x, kk) => xkk(0x6000, x, kk),
+            Instruction::AddByte(x, kk) => xkk(0x7000, x, kk),
+            Instruction::LdRegister(x, y) => xy(0x8000, x, y, 0x0),
+            Instruction::Or(x, y) => xy(0x8000, x, y, 0x1),
+            Instruction::And(x, y) => xy(0x8000, x, y, 0x2),
+            Instruction::Xor(x, y) => xy(0x8000, x, y, 0x3),
+            Instruction::AddRegister(x, y) => xy(0x8000, x, y, 0x4),
+            Instruction::Sub(x, y) => xy(0x8000, x, y, 0x5),
+            Instruction::Shr(x, y) => xy(0x8000, x, y, 0x6),
+            Instruction::Subn(x, y) => xy(0x8000, x, y, 0x7),
+            Instruction::Shl(x, y) => xy(0x8000, x, y, 0xE),
+            Instruction::SneRegister(x, y) => xy(0x9000, x, y, 0x0),
+            Instruction::LdI(nnn) => 0xA000 | nnn,
+            Instruction::JpV0(nnn) => 0xB000 | nnn,
+            Instruction::Rnd(x, kk) => xkk(0xC000, x, kk),
+            Instruction::Drw(x, y, n) => xy(0xD000, x, y, u16::from(n)),
+            Instruction::Skp(x) => xkk(0xE000, x, 0x9E),
+            Instruction::Sknp(x) => xkk(0xE000, x, 0xA1),
+            Instruction::LdILong => 0xF000,
+            Instruction::Plane(n) => xkk(0xF000, n, 0x01),
+            Instruction::Audio => 0xF002,
+            Instruction::LdVxDt(x) => xkk(0xF000, x, 0x07),
+            Instruction::LdVxK(x) => xkk(0xF000, x, 0x0A),
+            Instruction::LdDtVx(x) => xkk(0xF000, x, 0x15),
+            Instruction::LdStVx(x) => xkk(0xF000, x, 0x18),
+            Instruction::AddI(x) => xkk(0xF000, x, 0x1E),
+            Instruction::LdF(x) => xkk(0xF000, x, 0x29),
+            Instruction::LdHf(x) => xkk(0xF000, x, 0x30),
+            Instruction::LdB(x) => xkk(0xF000, x, 0x33),
+            Instruction::Pitch(x) => xkk(0xF000, x, 0x3A),
+            Instruction::LdMemoryVx(x) => xkk(0xF000, x, 0x55),
+            Instruction::LdVxMemory(x) => xkk(0xF000, x, 0x65),
+            Instruction::LdRVx(x) => xkk(0xF000, x, 0x75),
+            Instruction::LdVxR(x) => xkk(0xF000, x, 0x85),
+        }
+    }
+
+    pub fn extension(&self) -> Extension {
+        match self {
+            Instruction::ScrollDown(_)
+            | Instruction::ScrollRight
+            | Instruction::ScrollLeft
+            | Instruction::Exit
+            | Instruction::Low
+            | Instruction::High
+            | Instruction::LdHf(_)
+            | Instruction::LdRVx(_)
+            | Instruction::LdVxR(_) => Extension::SuperChip,
+            Instruction::Drw(_, _, 0) => Extension::SuperChip,
+            Instruction::ScrollUp(_)
+            | Instruction::Save(_, _)
+            | Instruction::Load(_, _)
+            | Instruction::LdILong
+            | Instruction::Plane(_)
+            | Instruction::Audio
+            | Instruction::Pitch(_) => Extension::XoChip,
+            _ => Extension::Chip8,
+        }
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Instruction::Cls => write!(f, "CLS"),
+            Instruction::Ret => write!(f, "RET"),
+            Instruction::ScrollDown(n) => write!(f, "SCD {}", n),
+            Instruction::ScrollUp(n) => write!(f, "SCU {}", n),
+            Instruction::ScrollRight => write!(f, "SCR"),
+            Instruction::ScrollLeft => write!(f, "SCL"),
+            Instruction::Exit => write!(f, "EXIT"),
+            Instruction::Low => write!(f, "LOW"),
+            Instruction::High => write!(f, "HIGH"),
+            Instruction::Jp(nnn) => write!(f, "JP 0x{:03X}", nnn),
+            Instruction::Call(nnn) => write!(f, "CALL 0x{:03X}", nnn),
+            Instruction::SeByte(x, kk) => write!(f, "SE V{:X}, 0x{:02X}", x, kk),
+            Instruction::SneByte(x, kk) => write!(f, "SNE V{:X}, 0x{:02X}", x, kk),
+            Instruction::SeRegister(x, y) => write!(f, "SE V{:X}, V{:X}", x, y),
+            Instruction::Save(x, y) => write!(f, "SAVE V{:X}, V{:X}", x, y),
+            Instruction::Load(x, y) => write!(f, "LOAD V{:X}, V{:X}", x, y),
+            Instruction::LdByte(x, kk) => write!(f, "LD V{:X}, 0x{:02X}", x, kk),
+            Instruction::AddByte(x, kk) => write!(f, "ADD V{:X}, 0x{:02X}", x, kk),
+            Instruction::LdRegister(x, y) => write!(f, "LD V{:X}, V{:X}", x, y),
+            Instruction::Or(x, y) => write!(f, "OR V{:X}, V{:X}", x, y),
+            Instruction::And(x, y) => write!(f, "AND V{:X}, V{:X}", x, y),
+            Instruction::Xor(x, y) => write!(f, "XOR V{:X}, V{:X}", x, y),
+            Instruction::AddRegister(x, y) => write!(f, "ADD V{:X}, V{:X}", x, y),
+            Instruction::Sub(x, y) => write!(f, "SUB V{:X}, V{:X}", x, y),
+            Instruction::Shr(x, y) => write!(f, "SHR V{:X}, V{:X}", x, y),
+            Instruction::Subn(x, y) => write!(f, "SUBN V{:X}, V{:X}", x, y),
+            Instruction::Shl(x, y) => write!(f, "SHL V{:X}, V{:X}", x, y),
+            Instruction::SneRegister(x, y) => write!(f, "SNE V{:X}, V{:X}", x, y),
+            Instruction::LdI(nnn) => write!(f, "LD I, 0x{:03X}", nnn),
+            Instruction::JpV0(nnn) => write!(f, "JP V0, 0x{:03X}", nnn),
+            Instruction::Rnd(x, kk) => write!(f, "RND V{:X}, 0x{:02X}", x, kk),
+            Instruction::Drw(x, y, n) => write!(f, "DRW V{:X}, V{:X}, {}", x, y, n),
+            Instruction::Skp(x) => write!(f, "SKP V{:X}", x),
+            Instruction::Sknp(x) => write!(f, "SKNP V{:X}", x),
+            Instruction::LdILong => write!(f, "LD I, LONG"),
+            Instruction::Plane(n) => write!(f, "PLANE {}", n),
+            Instruction::Audio => write!(f, "AUDIO"),
+            Instruction::LdVxDt(x) => write!(f, "LD V{:X}, DT", x),
+            Instruction::LdVxK(x) => write!(f, "LD V{:X}, K", x),
+            Instruction::LdDtVx(x) => write!(f, "LD DT, V{:X}", x),
+            Instruction::LdStVx(x) => write!(f, "LD ST, V{:X}", x),
+            Instruction::AddI(x) => write!(f, "ADD I, V{:X}", x),
+            Instruction::LdF(x) => write!(f, "LD F, V{:X}", x),
+            Instruction::LdHf(x) => write!(f, "LD HF, V{:X}", x),
+            Instruction::LdB(x) => write!(f, "LD B, V{:X}", x),
+            Instruction::Pitch(x) => write!(f, "PITCH V{:X}", x),
+            Instruction::LdMemoryVx(x) => write!(f, "LD [I], V{:X}", x),
+            Instruction::LdVxMemory(x) => write!(f, "LD V{:X}, [I]", x),
+            Instruction::LdRVx(x) => write!(f, "LD R, V{:X}", x),
+            Instruction::LdVxR(x) => write!(f, "LD V{:X}, R", x),
+        }
+    }
+}
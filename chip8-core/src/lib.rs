@@ -1,10 +1,32 @@
+pub mod access;
 pub mod cpu;
 pub mod ram;
+pub mod rewind;
+pub mod rng;
+pub mod savestate;
+pub mod script;
 pub mod stack;
 pub mod state;
+pub mod trace;
 pub mod keyboard;
+pub mod quirks;
 pub mod display;
 pub mod audio;
+pub mod error;
+pub mod instruction;
+pub mod asm;
+pub mod debugger;
+pub mod gdb;
+pub mod difftest;
+pub mod movie;
+pub mod image;
+pub mod video;
+pub mod wav;
+pub mod hash;
 
-pub use cpu::Chip8;
-pub use state::{State, DISPLAY_HEIGHT, DISPLAY_WIDTH};
+pub use cpu::{Chip8, DEFAULT_CYCLES_PER_FRAME, FRAME_RATE};
+pub use error::{ExecError, LoadError};
+pub use instruction::{decode, Instruction};
+pub use quirks::Quirks;
+pub use display::Framebuffer;
+pub use state::{State, DISPLAY_HEIGHT, DISPLAY_WIDTH, HIRES_DISPLAY_HEIGHT, HIRES_DISPLAY_WIDTH};
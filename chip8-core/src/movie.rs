@@ -0,0 +1,132 @@
+use std::io::{Read, Result, Write};
+
+use crate::cpu::Chip8;
+use crate::error::LoadError;
+use crate::hash::fnv1a;
+use crate::keyboard::{KeyState, Keys, NUM_KEYS};
+use crate::quirks::Quirks;
+use crate::savestate::{
+    invalid_data, read_quirks, read_u32, read_u64, read_u8, write_quirks, write_u16, write_u32,
+    write_u64, write_u8,
+};
+
+const MAGIC: &[u8; 4] = b"C8MV";
+/// Version of the format, bumped whenever its layout changes.
+pub const VERSION: u8 = 1;
+
+/// The keys held on every frame of a run, along with what it takes to replay it:
+/// execution being deterministic, the same ROM, quirks, speed and seed lead to the same frames.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Movie {
+    /// FNV-1a hash of the ROM the movie was recorded with.
+    pub rom_hash: u64,
+    pub quirks: Quirks,
+    pub cycles_per_frame: usize,
+    pub seed: u64,
+    // a bitmask of the keys held down on each frame
+    frames: Vec<u16>,
+}
+
+impl Movie {
+    /// Starts recording a run of `program` on a machine that has not run yet.
+    pub fn new(program: &[u8], computer: &Chip8, seed: u64) -> Self {
+        Movie {
+            rom_hash: fnv1a(program),
+            quirks: computer.quirks,
+            cycles_per_frame: computer.cycles_per_frame(),
+            seed,
+            frames: Vec::new(),
+        }
+    }
+
+    /// Number of frames recorded.
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// Appends the keys held during the next frame.
+    pub fn record(&mut self, keys: &Keys) {
+        let mask = keys
+            .iter()
+            .enumerate()
+            .filter(|(_, &state)| state == KeyState::Down)
+            .fold(0, |mask, (key, _)| mask | 1 << key);
+        self.frames.push(mask);
+    }
+
+    /// Drops the frames from `frames` on, e.g. when the run is rewound.
+    pub fn truncate(&mut self, frames: usize) {
+        self.frames.truncate(frames);
+    }
+
+    /// Keys held during `frame`, or nothing past the end of the movie.
+    pub fn keys(&self, frame: usize) -> Option<Keys> {
+        let mask = *self.frames.get(frame)?;
+        let mut keys = [KeyState::Up; NUM_KEYS];
+        for (key, state) in keys.iter_mut().enumerate() {
+            if mask & 1 << key != 0 {
+                *state = KeyState::Down;
+            }
+        }
+        Some(keys)
+    }
+
+    /// Whether the movie was recorded with `program`.
+    pub fn matches(&self, program: &[u8]) -> bool {
+        fnv1a(program) == self.rom_hash
+    }
+
+    /// Loads `program` in a machine set up like the one the movie was recorded with.
+    pub fn machine(&self, program: &[u8]) -> std::result::Result<Chip8, LoadError> {
+        let mut computer = Chip8::new(program, self.quirks, self.seed)?;
+        computer.set_cycles_per_frame(self.cycles_per_frame);
+        Ok(computer)
+    }
+
+    pub fn write<W: Write>(&self, writer: &mut W) -> Result<()> {
+        writer.write_all(MAGIC)?;
+        write_u8(writer, VERSION)?;
+        write_u64(writer, self.rom_hash)?;
+        write_quirks(writer, &self.quirks)?;
+        write_u32(writer, self.cycles_per_frame as u32)?;
+        write_u64(writer, self.seed)?;
+        for &mask in &self.frames {
+            write_u16(writer, mask)?;
+        }
+        Ok(())
+    }
+
+    /// Reads a movie written by `write`, whose frames run to the end of the input.
+    pub fn read<R: Read>(mut reader: R) -> Result<Self> {
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid_data("not a movie"));
+        }
+        if read_u8(&mut reader)? != VERSION {
+            return Err(invalid_data("unsupported movie version"));
+        }
+        let mut movie = Movie {
+            rom_hash: read_u64(&mut reader)?,
+            quirks: read_quirks(&mut reader)?,
+            cycles_per_frame: read_u32(&mut reader)? as usize,
+            seed: read_u64(&mut reader)?,
+            frames: Vec::new(),
+        };
+        let mut frames = Vec::new();
+        reader.read_to_end(&mut frames)?;
+        // a frame cut in half would shift the keys of every frame after it
+        if frames.len() % 2 != 0 {
+            return Err(invalid_data("truncated movie"));
+        }
+        movie.frames = frames
+            .chunks_exact(2)
+            .map(|mask| u16::from_be_bytes([mask[0], mask[1]]))
+            .collect();
+        Ok(movie)
+    }
+}
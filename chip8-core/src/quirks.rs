@@ -0,0 +1,114 @@
+use crate::ram::{MEMORY_SIZE, XO_MEMORY_SIZE};
+
+/// How `Fx55` and `Fx65` update the I register once the registers have been copied.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum MemoryQuirk {
+    /// I is left untouched.
+    Unchanged,
+    /// I is incremented by x.
+    IncrementByX,
+    /// I is incremented by x + 1, pointing right after the last accessed byte.
+    IncrementByXPlusOne,
+}
+
+/// Behaviours that differ between the interpreters CHIP-8 programs were written for.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Quirks {
+    /// `8xy6` and `8xyE` shift Vy and store the result in Vx, instead of shifting Vx in place.
+    pub shift_uses_vy: bool,
+    /// How `Fx55` and `Fx65` leave the I register.
+    pub memory: MemoryQuirk,
+    /// `Bnnn` jumps to nnn + Vx, x being the highest nibble of nnn, instead of nnn + V0.
+    pub jump_uses_vx: bool,
+    /// `Fx1E` sets VF to 1 when I overflows past the addressable memory, and to 0 otherwise.
+    pub add_i_sets_vf: bool,
+    /// `Dxyn` clips sprites at the edges of the screen instead of wrapping them around.
+    pub clip_sprites: bool,
+    /// `8xy1`, `8xy2` and `8xy3` reset VF to 0.
+    pub logic_resets_vf: bool,
+    /// Bytes of addressable memory.
+    pub memory_size: usize,
+}
+
+impl Quirks {
+    /// The original interpreter of the COSMAC VIP.
+    pub fn cosmac_vip() -> Self {
+        Quirks {
+            shift_uses_vy: true,
+            memory: MemoryQuirk::IncrementByXPlusOne,
+            jump_uses_vx: false,
+            add_i_sets_vf: false,
+            clip_sprites: true,
+            logic_resets_vf: true,
+            memory_size: MEMORY_SIZE,
+        }
+    }
+
+    /// The CHIP-48 interpreter of the HP-48 calculators.
+    pub fn chip48() -> Self {
+        Quirks {
+            shift_uses_vy: false,
+            memory: MemoryQuirk::IncrementByX,
+            jump_uses_vx: true,
+            add_i_sets_vf: false,
+            clip_sprites: true,
+            logic_resets_vf: false,
+            memory_size: MEMORY_SIZE,
+        }
+    }
+
+    /// The SUPER-CHIP 1.1 interpreter.
+    pub fn superchip() -> Self {
+        Quirks {
+            shift_uses_vy: false,
+            memory: MemoryQuirk::Unchanged,
+            jump_uses_vx: true,
+            add_i_sets_vf: false,
+            clip_sprites: true,
+            logic_resets_vf: false,
+            memory_size: MEMORY_SIZE,
+        }
+    }
+
+    /// Modern behaviour, as implemented by the Octo IDE for XO-CHIP programs.
+    pub fn octo() -> Self {
+        Quirks {
+            shift_uses_vy: true,
+            memory: MemoryQuirk::IncrementByXPlusOne,
+            jump_uses_vx: false,
+            add_i_sets_vf: false,
+            clip_sprites: false,
+            logic_resets_vf: false,
+            memory_size: XO_MEMORY_SIZE,
+        }
+    }
+
+    /// Looks up a preset by name: `vip`, `chip48`, `schip` or `octo`.
+    pub fn preset(name: &str) -> Option<Self> {
+        match name {
+            "vip" => Some(Self::cosmac_vip()),
+            "chip48" => Some(Self::chip48()),
+            "schip" => Some(Self::superchip()),
+            "octo" => Some(Self::octo()),
+            _ => None,
+        }
+    }
+}
+
+/// The behaviour of this emulator before quirks could be chosen: shifts act on Vx in place,
+/// `Fx55` and `Fx65` leave I untouched, `Bnnn` adds V0 and sprites wrap around the screen.
+/// `Fx1E` differs: it used to set VF whenever I + Vx went past 0xFF, which no interpreter does,
+/// and now leaves VF alone.
+impl Default for Quirks {
+    fn default() -> Self {
+        Quirks {
+            shift_uses_vy: false,
+            memory: MemoryQuirk::Unchanged,
+            jump_uses_vx: false,
+            add_i_sets_vf: false,
+            clip_sprites: false,
+            logic_resets_vf: false,
+            memory_size: MEMORY_SIZE,
+        }
+    }
+}
@@ -1,7 +1,16 @@
-const MEMORY_SIZE: usize = 4096;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::access::{Access, AccessHook};
+use crate::error::{ExecError, LoadError};
+
+pub const MEMORY_SIZE: usize = 4096;
+pub const XO_MEMORY_SIZE: usize = 0x10000;
 pub const PROGRAM_START: usize = 512;
+pub const FONT_START: usize = 0;
+pub const BIG_FONT_START: usize = 0x50;
 
-static FONT_SPRITES: &'static [u8] = &[
+static FONT_SPRITES: &[u8] = &[
     0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
     0x20, 0x60, 0x20, 0x20, 0x70, // 1
     0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
@@ -20,42 +29,134 @@ static FONT_SPRITES: &'static [u8] = &[
     0xF0, 0x80, 0xF0, 0x80, 0x80, // F
 ];
 
+static BIG_FONT_SPRITES: &[u8] = &[
+    0xFF, 0xFF, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, // 0
+    0x18, 0x78, 0x78, 0x18, 0x18, 0x18, 0x18, 0x18, 0xFF, 0xFF, // 1
+    0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // 2
+    0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 3
+    0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0x03, 0x03, // 4
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 5
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 6
+    0xFF, 0xFF, 0x03, 0x03, 0x06, 0x0C, 0x18, 0x18, 0x18, 0x18, // 7
+    0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 8
+    0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 9
+    0x7E, 0xFF, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xC3, // A
+    0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, // B
+    0x3C, 0xFF, 0xC3, 0xC0, 0xC0, 0xC0, 0xC0, 0xC3, 0xFF, 0x3C, // C
+    0xFC, 0xFE, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFE, 0xFC, // D
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // E
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xC0, 0xC0, // F
+];
+
 #[derive(Clone)]
 pub struct Ram {
-    buffer: [u8; MEMORY_SIZE],
+    buffer: Vec<u8>,
+    hook: Option<Rc<RefCell<dyn AccessHook>>>,
 }
 
 impl Ram {
-    pub fn new(program: &[u8]) -> Self {
-        let mut memory = [0; MEMORY_SIZE];
+    /// Loads `program` at `PROGRAM_START` in a memory of `size` bytes, which it must fit in.
+    pub fn new(program: &[u8], size: usize) -> Result<Self, LoadError> {
+        let max = size.saturating_sub(PROGRAM_START);
+        if program.len() > max {
+            return Err(LoadError::RomTooLarge {
+                size: program.len(),
+                max,
+            });
+        }
+        let mut memory = vec![0; size];
 
         // load program data
-        for i in 0..program.len() {
-            memory[i + PROGRAM_START] = program[i];
-        }
+        memory[PROGRAM_START..PROGRAM_START + program.len()].copy_from_slice(program);
+        memory[FONT_START..FONT_START + FONT_SPRITES.len()].copy_from_slice(FONT_SPRITES);
+        memory[BIG_FONT_START..BIG_FONT_START + BIG_FONT_SPRITES.len()]
+            .copy_from_slice(BIG_FONT_SPRITES);
 
-        for i in 0..FONT_SPRITES.len() {
-            memory[i] = FONT_SPRITES[i];
+        Ok(Self {
+            buffer: memory,
+            hook: None,
+        })
+    }
+
+    /// Restores memory saved with `as_slice`.
+    pub fn from_bytes(bytes: Vec<u8>) -> Self {
+        Self {
+            buffer: bytes,
+            hook: None,
         }
+    }
+
+    /// Sets the hook notified of the accesses made by the machine, or removes it.
+    /// The hook is shared by clones of the memory, and is not part of save states.
+    pub fn set_hook(&mut self, hook: Option<Rc<RefCell<dyn AccessHook>>>) {
+        self.hook = hook;
+    }
+
+    /// The memory contents, read without notifying the hook.
+    pub fn as_slice(&self) -> &[u8] {
+        &self.buffer
+    }
 
-        Self { buffer: memory }
+    /// The memory contents, for debuggers to change without notifying the hook.
+    pub fn as_mut_slice(&mut self) -> &mut [u8] {
+        &mut self.buffer
     }
 
     pub fn len(&self) -> usize {
         self.buffer.len()
     }
 
-    pub fn get(&self, index: usize) -> u8 {
-        self.buffer[index]
+    pub fn is_empty(&self) -> bool {
+        self.buffer.is_empty()
+    }
+
+    pub fn get(&self, index: usize) -> Result<u8, ExecError> {
+        let value = self.peek(index)?;
+        self.notify(index, Access::Read, value);
+        Ok(value)
+    }
+
+    /// Reads the instruction at `index`.
+    pub fn fetch(&self, index: usize) -> Result<u16, ExecError> {
+        let instruction = self.peek_u16(index)?;
+        self.notify(index, Access::Fetch, (instruction >> 8) as u8);
+        Ok(instruction)
+    }
+
+    /// Reads the word following a 4-byte instruction, which is executed along with it.
+    pub fn fetch_operand(&self, index: usize) -> Result<u16, ExecError> {
+        let operand = self.peek_u16(index)?;
+        self.notify(index, Access::Operand, (operand >> 8) as u8);
+        Ok(operand)
+    }
+
+    /// Reads a big-endian word without notifying the hook.
+    pub fn peek_u16(&self, index: usize) -> Result<u16, ExecError> {
+        let first = self.peek(index)? as u16;
+        let second = self.peek(index + 1)? as u16;
+        Ok((first << 8) + second)
     }
 
-    pub fn get_u16(&self, index: usize) -> u16 {
-        let first = self.buffer[index] as u16;
-        let second = self.buffer[index + 1] as u16;
-        (first << 8) + second
+    pub fn set(&mut self, index: usize, value: u8) -> Result<(), ExecError> {
+        let byte = self
+            .buffer
+            .get_mut(index)
+            .ok_or(ExecError::OutOfBounds { address: index })?;
+        *byte = value;
+        self.notify(index, Access::Write, value);
+        Ok(())
     }
 
-    pub fn set(&mut self, index: usize, value: u8) {
-        self.buffer[index] = value;
+    fn peek(&self, index: usize) -> Result<u8, ExecError> {
+        self.buffer
+            .get(index)
+            .copied()
+            .ok_or(ExecError::OutOfBounds { address: index })
+    }
+
+    fn notify(&self, index: usize, access: Access, value: u8) {
+        if let Some(hook) = &self.hook {
+            hook.borrow_mut().access(index, access, value);
+        }
     }
 }
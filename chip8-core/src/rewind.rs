@@ -0,0 +1,133 @@
+use std::collections::VecDeque;
+
+use crate::cpu::Chip8;
+
+/// Snapshots of the recent history of a machine, used to step back in time.
+/// Only the latest snapshot is stored whole: every older one is delta-compressed against
+/// the snapshot that followed it, and the oldest ones are dropped once the buffer outgrows its budget.
+pub struct RewindBuffer {
+    budget: usize,
+    latest: Option<Vec<u8>>,
+    deltas: VecDeque<Vec<u8>>,
+    size: usize,
+}
+
+impl RewindBuffer {
+    /// Creates a buffer holding at most `budget` bytes of snapshots.
+    pub fn new(budget: usize) -> Self {
+        RewindBuffer {
+            budget,
+            latest: None,
+            deltas: VecDeque::new(),
+            size: 0,
+        }
+    }
+
+    /// Number of snapshots that can be restored.
+    pub fn len(&self) -> usize {
+        usize::from(self.latest.is_some()) + self.deltas.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.latest.is_none()
+    }
+
+    /// Bytes used by the stored snapshots.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn clear(&mut self) {
+        self.latest = None;
+        self.deltas.clear();
+        self.size = 0;
+    }
+
+    /// Records a snapshot of the machine.
+    pub fn push(&mut self, computer: &Chip8) {
+        let mut snapshot = Vec::new();
+        computer
+            .save_state(&mut snapshot)
+            .expect("writing to memory cannot fail");
+
+        if let Some(previous) = self.latest.take() {
+            let delta = encode(&snapshot, &previous);
+            self.size += delta.len();
+            self.size -= previous.len();
+            self.deltas.push_back(delta);
+        }
+        self.size += snapshot.len();
+        self.latest = Some(snapshot);
+
+        while self.size > self.budget && !self.deltas.is_empty() {
+            let oldest = self.deltas.pop_front().unwrap();
+            self.size -= oldest.len();
+        }
+    }
+
+    /// Removes the most recent snapshot and restores the machine it was taken from.
+    pub fn pop(&mut self) -> Option<Chip8> {
+        let snapshot = self.latest.take()?;
+        self.size -= snapshot.len();
+        if let Some(delta) = self.deltas.pop_back() {
+            let previous = decode(&snapshot, &delta);
+            self.size -= delta.len();
+            self.size += previous.len();
+            self.latest = Some(previous);
+        }
+        Some(Chip8::load_state(snapshot.as_slice()).expect("snapshots are valid save states"))
+    }
+}
+
+/// Encodes `target` relative to `base`, as the length of `target` followed by runs of bytes
+/// that differ between them. Bytes past the end of the shorter snapshot, as happens across
+/// a resolution change, are compared against zeros.
+fn encode(base: &[u8], target: &[u8]) -> Vec<u8> {
+    let length = base.len().max(target.len());
+    let byte = |snapshot: &[u8], index: usize| snapshot.get(index).copied().unwrap_or(0);
+    let differs = |index: usize| byte(base, index) != byte(target, index);
+
+    let mut delta = (target.len() as u32).to_be_bytes().to_vec();
+    let mut start = 0;
+    while start < length {
+        if !differs(start) {
+            start += 1;
+            continue;
+        }
+        let end = (start..length)
+            .find(|&index| !differs(index))
+            .unwrap_or(length);
+        delta.extend_from_slice(&(start as u32).to_be_bytes());
+        delta.extend_from_slice(&((end - start) as u32).to_be_bytes());
+        delta.extend((start..end).map(|index| byte(base, index) ^ byte(target, index)));
+        start = end;
+    }
+    delta
+}
+
+/// Rebuilds the snapshot encoded against `base`.
+fn decode(base: &[u8], delta: &[u8]) -> Vec<u8> {
+    let length = read_u32(delta) as usize;
+    let mut target = base.to_vec();
+    target.resize(length.max(base.len()), 0);
+
+    let mut position = 4;
+    while position < delta.len() {
+        let start = read_u32(&delta[position..]) as usize;
+        let run = read_u32(&delta[position + 4..]) as usize;
+        position += 8;
+        for (byte, mask) in target[start..start + run]
+            .iter_mut()
+            .zip(&delta[position..position + run])
+        {
+            *byte ^= mask;
+        }
+        position += run;
+    }
+    target.truncate(length);
+    target
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
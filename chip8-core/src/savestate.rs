@@ -0,0 +1,282 @@
+use std::io::{Error, ErrorKind, Read, Result, Write};
+
+use crate::audio::{Pattern, Sound, PATTERN_SIZE};
+use crate::cpu::Chip8;
+use crate::display::{Framebuffer, ALL_PLANES};
+use crate::error::LoadError;
+use crate::keyboard::NUM_KEYS;
+use crate::quirks::{MemoryQuirk, Quirks};
+use crate::ram::{Ram, MEMORY_SIZE, XO_MEMORY_SIZE};
+use crate::rng::Rng;
+use crate::stack::Stack;
+use crate::state::{
+    State, DISPLAY_HEIGHT, DISPLAY_WIDTH, HIRES_DISPLAY_HEIGHT, HIRES_DISPLAY_WIDTH, NUM_FLAGS,
+};
+
+const MAGIC: &[u8; 4] = b"C8SS";
+/// Version of the format, bumped whenever its layout changes.
+pub const VERSION: u8 = 2;
+
+impl Chip8 {
+    /// Writes the whole machine, quirks included, in a versioned binary format.
+    /// The frame counter and the sound being played are saved too, so that a loaded machine
+    /// stamps its audio events as the saved one would.
+    pub fn save_state<W: Write>(&self, writer: &mut W) -> Result<()> {
+        writer.write_all(MAGIC)?;
+        write_u8(writer, VERSION)?;
+        write_quirks(writer, &self.quirks)?;
+        write_u32(writer, self.cycles_per_frame() as u32)?;
+        write_u64(writer, self.frame)?;
+        match self.sound {
+            Some(sound) => {
+                write_bool(writer, true)?;
+                write_pattern(writer, sound.pattern)?;
+                write_u8(writer, sound.pitch)?;
+            }
+            None => write_bool(writer, false)?,
+        }
+        write_state(writer, &self.state)
+    }
+
+    /// Reads a machine written by `save_state`.
+    /// Values the machine cannot run with are reported as an `InvalidData` error wrapping a
+    /// `LoadError`.
+    pub fn load_state<R: Read>(mut reader: R) -> Result<Self> {
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid_data("not a save state"));
+        }
+        let version = read_u8(&mut reader)?;
+        if version != VERSION {
+            return Err(invalid_data("unsupported save state version"));
+        }
+        let quirks = read_quirks(&mut reader)?;
+        let cycles_per_frame = read_u32(&mut reader)? as usize;
+        let frame = read_u64(&mut reader)?;
+        let sound = if read_bool(&mut reader)? {
+            Some(Sound {
+                pattern: read_pattern(&mut reader)?,
+                pitch: read_u8(&mut reader)?,
+            })
+        } else {
+            None
+        };
+        let state = read_state(&mut reader, &quirks)?;
+
+        let mut computer = Chip8::from_state(state, quirks);
+        computer.set_cycles_per_frame(cycles_per_frame);
+        computer.frame = frame;
+        computer.sound = sound;
+        Ok(computer)
+    }
+}
+
+pub(crate) fn write_quirks<W: Write>(writer: &mut W, quirks: &Quirks) -> Result<()> {
+    write_bool(writer, quirks.shift_uses_vy)?;
+    let memory = match quirks.memory {
+        MemoryQuirk::Unchanged => 0,
+        MemoryQuirk::IncrementByX => 1,
+        MemoryQuirk::IncrementByXPlusOne => 2,
+    };
+    write_u8(writer, memory)?;
+    write_bool(writer, quirks.jump_uses_vx)?;
+    write_bool(writer, quirks.add_i_sets_vf)?;
+    write_bool(writer, quirks.clip_sprites)?;
+    write_bool(writer, quirks.logic_resets_vf)?;
+    write_u32(writer, quirks.memory_size as u32)
+}
+
+pub(crate) fn read_quirks<R: Read>(reader: &mut R) -> Result<Quirks> {
+    let shift_uses_vy = read_bool(reader)?;
+    let memory = match read_u8(reader)? {
+        0 => MemoryQuirk::Unchanged,
+        1 => MemoryQuirk::IncrementByX,
+        2 => MemoryQuirk::IncrementByXPlusOne,
+        _ => return Err(invalid_data("invalid memory quirk")),
+    };
+    let jump_uses_vx = read_bool(reader)?;
+    let add_i_sets_vf = read_bool(reader)?;
+    let clip_sprites = read_bool(reader)?;
+    let logic_resets_vf = read_bool(reader)?;
+    let memory_size = read_u32(reader)? as usize;
+    if !(MEMORY_SIZE..=XO_MEMORY_SIZE).contains(&memory_size) {
+        return Err(invalid_data("invalid memory size"));
+    }
+    Ok(Quirks {
+        shift_uses_vy,
+        memory,
+        jump_uses_vx,
+        add_i_sets_vf,
+        clip_sprites,
+        logic_resets_vf,
+        memory_size,
+    })
+}
+
+fn write_state<W: Write>(writer: &mut W, state: &State) -> Result<()> {
+    writer.write_all(state.ram.as_slice())?;
+    writer.write_all(&state.registers)?;
+    let stack = state.stack.as_slice();
+    write_u8(writer, stack.len() as u8)?;
+    for &address in stack {
+        write_u16(writer, address)?;
+    }
+    write_u16(writer, state.display_buffer.width() as u16)?;
+    write_u16(writer, state.display_buffer.height() as u16)?;
+    writer.write_all(state.display_buffer.as_slice())?;
+    write_bool(writer, state.hires)?;
+    write_u8(writer, state.planes)?;
+    write_u8(writer, state.delay_timer)?;
+    write_u8(writer, state.sound_timer)?;
+    write_u32(writer, state.pc as u32)?;
+    write_u16(writer, state.i)?;
+    write_bool(writer, state.should_draw)?;
+    write_bool(writer, state.waiting_for_key)?;
+    write_u8(writer, state.key_register_index as u8)?;
+    write_bool(writer, state.play_audio)?;
+    write_pattern(writer, state.audio_pattern)?;
+    write_u8(writer, state.pitch)?;
+    writer.write_all(&state.flags)?;
+    write_bool(writer, state.halted)?;
+    write_u64(writer, state.rng.state())
+}
+
+fn read_state<R: Read>(reader: &mut R, quirks: &Quirks) -> Result<State> {
+    let mut state = State::new(&[], quirks.memory_size, 0).expect("an empty program always fits");
+
+    let mut memory = vec![0; quirks.memory_size];
+    reader.read_exact(&mut memory)?;
+    state.ram = Ram::from_bytes(memory);
+    reader.read_exact(&mut state.registers)?;
+    let stack_size = read_u8(reader)?;
+    state.stack = Stack::new();
+    for _ in 0..stack_size {
+        let address = read_u16(reader)?;
+        state
+            .stack
+            .push(address)
+            .map_err(|_| invalid_state("stack too deep"))?;
+    }
+    let width = read_u16(reader)? as usize;
+    let height = read_u16(reader)? as usize;
+    let resolution = (width, height);
+    if resolution != (DISPLAY_WIDTH, DISPLAY_HEIGHT)
+        && resolution != (HIRES_DISPLAY_WIDTH, HIRES_DISPLAY_HEIGHT)
+    {
+        return Err(invalid_state("invalid display resolution"));
+    }
+    let mut pixels = vec![0; width * height];
+    reader.read_exact(&mut pixels)?;
+    if pixels.iter().any(|&pixel| pixel & !ALL_PLANES != 0) {
+        return Err(invalid_state("pixel lit on an unknown bitplane"));
+    }
+    state.display_buffer = Framebuffer::from_pixels(width, height, pixels);
+    state.hires = read_bool(reader)?;
+    if state.hires != (width == HIRES_DISPLAY_WIDTH) {
+        return Err(invalid_state("display resolution does not match the mode"));
+    }
+    state.planes = read_u8(reader)?;
+    if state.planes & !ALL_PLANES != 0 {
+        return Err(invalid_state("unknown bitplanes selected"));
+    }
+    state.delay_timer = read_u8(reader)?;
+    state.sound_timer = read_u8(reader)?;
+    state.pc = read_u32(reader)? as usize;
+    state.i = read_u16(reader)?;
+    state.should_draw = read_bool(reader)?;
+    state.waiting_for_key = read_bool(reader)?;
+    state.key_register_index = read_u8(reader)? as usize;
+    if state.key_register_index >= NUM_KEYS {
+        return Err(invalid_state("invalid key register"));
+    }
+    state.play_audio = read_bool(reader)?;
+    state.audio_pattern = read_pattern(reader)?;
+    state.pitch = read_u8(reader)?;
+    let mut flags = [0; NUM_FLAGS];
+    reader.read_exact(&mut flags)?;
+    state.flags = flags;
+    state.halted = read_bool(reader)?;
+    state.rng =
+        Rng::from_state(read_u64(reader)?).ok_or_else(|| invalid_state("invalid rng state"))?;
+    Ok(state)
+}
+
+fn write_pattern<W: Write>(writer: &mut W, pattern: Option<Pattern>) -> Result<()> {
+    match pattern {
+        Some(pattern) => {
+            write_bool(writer, true)?;
+            writer.write_all(&pattern)
+        }
+        None => write_bool(writer, false),
+    }
+}
+
+fn read_pattern<R: Read>(reader: &mut R) -> Result<Option<Pattern>> {
+    if !read_bool(reader)? {
+        return Ok(None);
+    }
+    let mut pattern = [0; PATTERN_SIZE];
+    reader.read_exact(&mut pattern)?;
+    Ok(Some(pattern))
+}
+
+fn invalid_state(reason: &'static str) -> Error {
+    Error::new(ErrorKind::InvalidData, LoadError::InvalidState { reason })
+}
+
+pub(crate) fn invalid_data(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+pub(crate) fn write_u8<W: Write>(writer: &mut W, value: u8) -> Result<()> {
+    writer.write_all(&[value])
+}
+
+fn write_bool<W: Write>(writer: &mut W, value: bool) -> Result<()> {
+    write_u8(writer, value.into())
+}
+
+pub(crate) fn write_u16<W: Write>(writer: &mut W, value: u16) -> Result<()> {
+    writer.write_all(&value.to_be_bytes())
+}
+
+pub(crate) fn write_u32<W: Write>(writer: &mut W, value: u32) -> Result<()> {
+    writer.write_all(&value.to_be_bytes())
+}
+
+pub(crate) fn write_u64<W: Write>(writer: &mut W, value: u64) -> Result<()> {
+    writer.write_all(&value.to_be_bytes())
+}
+
+pub(crate) fn read_u8<R: Read>(reader: &mut R) -> Result<u8> {
+    let mut bytes = [0; 1];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes[0])
+}
+
+fn read_bool<R: Read>(reader: &mut R) -> Result<bool> {
+    match read_u8(reader)? {
+        0 => Ok(false),
+        1 => Ok(true),
+        _ => Err(invalid_data("invalid boolean")),
+    }
+}
+
+pub(crate) fn read_u16<R: Read>(reader: &mut R) -> Result<u16> {
+    let mut bytes = [0; 2];
+    reader.read_exact(&mut bytes)?;
+    Ok(u16::from_be_bytes(bytes))
+}
+
+pub(crate) fn read_u32<R: Read>(reader: &mut R) -> Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_be_bytes(bytes))
+}
+
+pub(crate) fn read_u64<R: Read>(reader: &mut R) -> Result<u64> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_be_bytes(bytes))
+}
@@ -0,0 +1,91 @@
+use std::error::Error;
+use std::fmt;
+
+use crate::keyboard::{KeyState, Keys, NUM_KEYS};
+
+/// Keys held down on given frames, to drive a program without a keyboard.
+///
+/// Each line of a script holds a frame, or an inclusive range of frames, followed by
+/// the hexadecimal keys held down during those frames:
+///
+/// ```text
+/// # press 5 for a second, then 4 and 6 together
+/// 60-119 5
+/// 150 4 6
+/// ```
+#[derive(Clone, Default, Debug)]
+pub struct KeyScript {
+    entries: Vec<(usize, usize, usize)>, // first frame, last frame, key
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct ScriptError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl Error for ScriptError {}
+
+impl KeyScript {
+    pub fn parse(source: &str) -> Result<Self, ScriptError> {
+        let mut entries = Vec::new();
+        for (index, line) in source.lines().enumerate() {
+            let error = |message: String| ScriptError {
+                line: index + 1,
+                message,
+            };
+            let line = line.split('#').next().unwrap().trim();
+            let mut words = line.split_whitespace();
+            let frames = match words.next() {
+                Some(frames) => frames,
+                None => continue,
+            };
+            let (first, last) = parse_frames(frames)
+                .ok_or_else(|| error(format!("invalid frames `{}`", frames)))?;
+            let mut has_keys = false;
+            for word in words {
+                let key = usize::from_str_radix(word, 16)
+                    .ok()
+                    .filter(|&key| key < NUM_KEYS)
+                    .ok_or_else(|| error(format!("invalid key `{}`", word)))?;
+                entries.push((first, last, key));
+                has_keys = true;
+            }
+            if !has_keys {
+                return Err(error("missing keys".to_string()));
+            }
+        }
+        Ok(KeyScript { entries })
+    }
+
+    /// Keys held down during `frame`.
+    pub fn keys(&self, frame: usize) -> Keys {
+        let mut keys = [KeyState::Up; NUM_KEYS];
+        for &(first, last, key) in &self.entries {
+            if (first..=last).contains(&frame) {
+                keys[key] = KeyState::Down;
+            }
+        }
+        keys
+    }
+}
+
+fn parse_frames(frames: &str) -> Option<(usize, usize)> {
+    let mut bounds = frames.splitn(2, '-');
+    let first = bounds.next()?.parse().ok()?;
+    let last = match bounds.next() {
+        Some(last) => last.parse().ok()?,
+        None => first,
+    };
+    if first <= last {
+        Some((first, last))
+    } else {
+        None
+    }
+}
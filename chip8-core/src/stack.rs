@@ -1,3 +1,5 @@
+use crate::error::ExecError;
+
 const STACK_SIZE: usize = 16;
 
 #[derive(Clone)]
@@ -6,6 +8,12 @@ pub struct Stack {
     head: usize,
 }
 
+impl Default for Stack {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Stack {
     pub fn new() -> Self {
         Stack {
@@ -14,19 +22,32 @@ impl Stack {
         }
     }
 
-    pub fn push(&mut self, value: u16) {
+    /// Addresses currently on the stack, from the bottom to the top.
+    pub fn as_slice(&self) -> &[u16] {
+        &self.buffer[..self.head]
+    }
+
+    pub fn push(&mut self, value: u16) -> Result<(), ExecError> {
+        if self.head == STACK_SIZE {
+            return Err(ExecError::StackOverflow);
+        }
         self.buffer[self.head] = value;
-        self.head += 1
+        self.head += 1;
+        Ok(())
     }
 
-    pub fn pop(&mut self) -> u16 {
-        assert!(self.head > 0);
+    pub fn pop(&mut self) -> Result<u16, ExecError> {
+        if self.head == 0 {
+            return Err(ExecError::StackUnderflow);
+        }
         self.head -= 1;
-        self.buffer[self.head]
+        Ok(self.buffer[self.head])
     }
 
-    pub fn top(&self) -> u16 {
-        assert!(self.head > 0);
-        self.buffer[self.head - 1]
+    pub fn top(&self) -> Result<u16, ExecError> {
+        if self.head == 0 {
+            return Err(ExecError::StackUnderflow);
+        }
+        Ok(self.buffer[self.head - 1])
     }
 }
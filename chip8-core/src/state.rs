@@ -1,16 +1,25 @@
-use crate::ram::{Ram, PROGRAM_START};
+use crate::audio::{Pattern, DEFAULT_PITCH};
+use crate::display::Framebuffer;
+use crate::error::{ExecError, LoadError};
+use crate::ram::{Ram, MEMORY_SIZE, PROGRAM_START};
+use crate::rng::Rng;
 use crate::stack::Stack;
 
 const NUM_REGISTERS: usize = 16;
 pub const DISPLAY_WIDTH: usize = 64;
 pub const DISPLAY_HEIGHT: usize = 32;
+pub const HIRES_DISPLAY_WIDTH: usize = 128;
+pub const HIRES_DISPLAY_HEIGHT: usize = 64;
+pub const NUM_FLAGS: usize = 16;
 
 #[derive(Clone)]
 pub struct State {
     pub ram: Ram,
     pub registers: [u8; NUM_REGISTERS],
     pub stack: Stack,
-    pub display_buffer: [[u8; DISPLAY_WIDTH]; DISPLAY_HEIGHT],
+    pub display_buffer: Framebuffer,
+    pub hires: bool,
+    pub planes: u8, // bitplanes selected for drawing
     pub delay_timer: u8,
     pub sound_timer: u8,
     pub pc: usize, // program counter
@@ -19,15 +28,22 @@ pub struct State {
     pub waiting_for_key: bool,
     pub key_register_index: usize,
     pub play_audio: bool,
+    pub audio_pattern: Option<Pattern>,
+    pub pitch: u8,
+    pub flags: [u8; NUM_FLAGS], // persistent RPL user flags
+    pub halted: bool,
+    pub rng: Rng,
 }
 
 impl State {
-    pub fn new(program: &[u8]) -> Self {
-        State {
-            ram: Ram::new(program),
+    pub fn new(program: &[u8], memory_size: usize, seed: u64) -> Result<Self, LoadError> {
+        Ok(State {
+            ram: Ram::new(program, memory_size)?,
             registers: [0; NUM_REGISTERS],
             stack: Stack::new(),
-            display_buffer: [[0; DISPLAY_WIDTH]; DISPLAY_HEIGHT],
+            display_buffer: Framebuffer::new(DISPLAY_WIDTH, DISPLAY_HEIGHT),
+            hires: false,
+            planes: 1,
             delay_timer: 0,
             sound_timer: 0,
             pc: PROGRAM_START,
@@ -36,19 +52,142 @@ impl State {
             waiting_for_key: false,
             key_register_index: 0,
             play_audio: false,
-        }
+            audio_pattern: None,
+            pitch: DEFAULT_PITCH,
+            flags: [0; NUM_FLAGS],
+            halted: false,
+            rng: Rng::new(seed),
+        })
+    }
+
+    /// The instruction at the program counter, read without notifying the memory hook.
+    pub fn instruction(&self) -> Result<u16, ExecError> {
+        self.ram.peek_u16(self.pc)
     }
 
-    pub fn instruction(&self) -> u16 {
-        self.ram.get_u16(self.pc)
+    /// Reads the instruction at the program counter in order to execute it.
+    pub fn fetch(&self) -> Result<u16, ExecError> {
+        self.ram.fetch(self.pc)
+    }
+
+    /// Reads the operand at the program counter, once past the 4-byte instruction it belongs to.
+    pub fn fetch_operand(&self) -> Result<u16, ExecError> {
+        self.ram.fetch_operand(self.pc)
     }
 
     pub fn next_instruction(&mut self) {
         self.pc += 2;
     }
 
+    /// Moves past the next instruction, which is 4 bytes long for the XO-CHIP `F000 nnnn`.
+    /// An instruction cut short by the end of the memory is skipped as 2 bytes, its fetch
+    /// failing once it is reached.
+    pub fn skip_instruction(&mut self) {
+        if self.pc + 1 < self.ram.len() && self.instruction() == Ok(0xF000) {
+            self.pc += 2;
+        }
+        self.next_instruction();
+    }
+
     pub fn set_address_register(&mut self, address: u16) {
-        debug_assert!(address < self.ram.len() as u16);
+        debug_assert!((address as usize) < self.ram.len());
         self.i = address;
     }
 }
+
+/// Builds a `State` in a given condition, e.g. to test an instruction against it.
+/// The memory holds the fonts and an empty program unless told otherwise.
+pub struct StateBuilder {
+    state: State,
+}
+
+impl Default for StateBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StateBuilder {
+    pub fn new() -> Self {
+        Self::with_memory_size(MEMORY_SIZE)
+    }
+
+    /// Starts from a memory of the given size, which should match the quirks the state runs with.
+    pub fn with_memory_size(memory_size: usize) -> Self {
+        StateBuilder {
+            state: State::new(&[], memory_size, 0).expect("an empty program always fits"),
+        }
+    }
+
+    /// Loads a program at `PROGRAM_START`.
+    pub fn program(self, program: &[u8]) -> Self {
+        self.memory(PROGRAM_START, program)
+    }
+
+    /// Overwrites the memory starting at `address`.
+    pub fn memory(mut self, address: usize, bytes: &[u8]) -> Self {
+        self.state.ram.as_mut_slice()[address..address + bytes.len()].copy_from_slice(bytes);
+        self
+    }
+
+    pub fn register(mut self, index: usize, value: u8) -> Self {
+        self.state.registers[index] = value;
+        self
+    }
+
+    pub fn registers(mut self, registers: [u8; NUM_REGISTERS]) -> Self {
+        self.state.registers = registers;
+        self
+    }
+
+    pub fn i(mut self, i: u16) -> Self {
+        self.state.i = i;
+        self
+    }
+
+    pub fn pc(mut self, pc: usize) -> Self {
+        self.state.pc = pc;
+        self
+    }
+
+    /// Pushes return addresses on the stack, from the bottom to the top.
+    /// Panics if they do not fit.
+    pub fn stack(mut self, addresses: &[u16]) -> Self {
+        for &address in addresses {
+            self.state
+                .stack
+                .push(address)
+                .expect("too many return addresses");
+        }
+        self
+    }
+
+    pub fn delay_timer(mut self, value: u8) -> Self {
+        self.state.delay_timer = value;
+        self
+    }
+
+    pub fn sound_timer(mut self, value: u8) -> Self {
+        self.state.sound_timer = value;
+        self
+    }
+
+    /// Switches to the high resolution mode.
+    pub fn hires(mut self) -> Self {
+        self.state.hires = true;
+        self.state
+            .display_buffer
+            .resize(HIRES_DISPLAY_WIDTH, HIRES_DISPLAY_HEIGHT);
+        self
+    }
+
+    /// Seeds the random numbers generated by `Cxkk`.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.state.rng = Rng::new(seed);
+        self
+    }
+
+    pub fn build(self) -> State {
+        self.state
+    }
+}
@@ -0,0 +1,227 @@
+use std::fmt;
+use std::io::{self, Error, ErrorKind, Read, Write};
+use std::ops::Range;
+
+use crate::instruction::decode;
+use crate::state::State;
+
+const MAGIC: &[u8; 4] = b"C8TR";
+/// Version of the binary format, bumped whenever its layout changes.
+pub const VERSION: u8 = 1;
+const RECORD_SIZE: usize = 32;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TraceFormat {
+    /// One line per instruction, meant to be diffed.
+    Text,
+    /// Fixed size big-endian records following a header, for long runs.
+    Binary,
+}
+
+/// The machine as an instruction is about to be executed.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct TraceRecord {
+    /// Number of instructions executed before this one.
+    pub cycle: u64,
+    pub pc: u16,
+    pub opcode: u16,
+    pub registers: [u8; 16],
+    pub i: u16,
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+}
+
+impl TraceRecord {
+    pub fn new(cycle: u64, state: &State, opcode: u16) -> Self {
+        TraceRecord {
+            cycle,
+            pc: state.pc as u16,
+            opcode,
+            registers: state.registers,
+            i: state.i,
+            delay_timer: state.delay_timer,
+            sound_timer: state.sound_timer,
+        }
+    }
+
+    /// Parses a line of a text trace, in which the mnemonic and the timers are optional:
+    /// `cycle pc opcode [mnemonic] V v0 ... vf I i [DT dt ST st]`, numbers being hexadecimal
+    /// except for the cycle.
+    pub fn parse(line: &str) -> Option<Self> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let hex = |word: &str| u16::from_str_radix(word, 16).ok();
+        let byte = |word: &str| u8::from_str_radix(word, 16).ok();
+        let cycle = words.first()?.parse().ok()?;
+        let pc = hex(words.get(1)?)?;
+        let opcode = hex(words.get(2)?)?;
+        // mnemonics never hold a lone `V`, registers being written `Vx`
+        let start = 3 + words.iter().skip(3).position(|&word| word == "V")? + 1;
+        let mut registers = [0; 16];
+        for (register, word) in registers.iter_mut().zip(words.get(start..start + 16)?) {
+            *register = byte(word)?;
+        }
+        let mut rest = words[start + 16..].iter();
+        let mut record = TraceRecord {
+            cycle,
+            pc,
+            opcode,
+            registers,
+            i: 0,
+            delay_timer: 0,
+            sound_timer: 0,
+        };
+        if *rest.next()? != "I" {
+            return None;
+        }
+        record.i = hex(rest.next()?)?;
+        while let Some(&name) = rest.next() {
+            let value = byte(rest.next()?)?;
+            match name {
+                "DT" => record.delay_timer = value,
+                "ST" => record.sound_timer = value,
+                _ => return None,
+            }
+        }
+        Some(record)
+    }
+
+    pub fn write_binary<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let mut record = Vec::with_capacity(RECORD_SIZE);
+        record.extend_from_slice(&self.cycle.to_be_bytes());
+        record.extend_from_slice(&self.pc.to_be_bytes());
+        record.extend_from_slice(&self.opcode.to_be_bytes());
+        record.extend_from_slice(&self.registers);
+        record.extend_from_slice(&self.i.to_be_bytes());
+        record.push(self.delay_timer);
+        record.push(self.sound_timer);
+        writer.write_all(&record)
+    }
+
+    /// Reads the next record of a binary trace, or nothing at the end of the trace.
+    pub fn read_binary<R: Read>(reader: &mut R) -> io::Result<Option<Self>> {
+        let mut record = [0; RECORD_SIZE];
+        let mut length = 0;
+        while length < RECORD_SIZE {
+            match reader.read(&mut record[length..])? {
+                0 if length == 0 => return Ok(None),
+                0 => return Err(Error::new(ErrorKind::UnexpectedEof, "truncated trace")),
+                read => length += read,
+            }
+        }
+        let u16_at = |index: usize| u16::from_be_bytes([record[index], record[index + 1]]);
+        let mut cycle = [0; 8];
+        cycle.copy_from_slice(&record[..8]);
+        let mut registers = [0; 16];
+        registers.copy_from_slice(&record[12..28]);
+        Ok(Some(TraceRecord {
+            cycle: u64::from_be_bytes(cycle),
+            pc: u16_at(8),
+            opcode: u16_at(10),
+            registers,
+            i: u16_at(28),
+            delay_timer: record[30],
+            sound_timer: record[31],
+        }))
+    }
+}
+
+impl fmt::Display for TraceRecord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mnemonic = match decode(self.opcode) {
+            Ok(instruction) => instruction.to_string(),
+            Err(_) => format!("DW 0x{:04X}", self.opcode),
+        };
+        write!(
+            f,
+            "{:>9} {:04X} {:04X} {:<20} V",
+            self.cycle, self.pc, self.opcode, mnemonic
+        )?;
+        for register in &self.registers {
+            write!(f, " {:02X}", register)?;
+        }
+        write!(
+            f,
+            " I {:04X} DT {:02X} ST {:02X}",
+            self.i, self.delay_timer, self.sound_timer
+        )
+    }
+}
+
+/// Writes the binary trace header, which `read_header` checks.
+pub fn write_header<W: Write>(writer: &mut W) -> io::Result<()> {
+    writer.write_all(MAGIC)?;
+    writer.write_all(&[VERSION])
+}
+
+pub fn read_header<R: Read>(reader: &mut R) -> io::Result<()> {
+    let mut header = [0; 5];
+    reader.read_exact(&mut header)?;
+    if &header[..4] != MAGIC {
+        return Err(Error::new(ErrorKind::InvalidData, "not a trace"));
+    }
+    if header[4] != VERSION {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            "unsupported trace version",
+        ));
+    }
+    Ok(())
+}
+
+/// Records every instruction executed by a machine, see `Chip8::set_tracer`.
+pub struct Tracer {
+    writer: Box<dyn Write>,
+    format: TraceFormat,
+    range: Option<Range<usize>>,
+    cycle: u64,
+    // writing stops at the first error, which `finish` returns
+    error: Option<io::Error>,
+}
+
+impl Tracer {
+    pub fn new<W: Write + 'static>(writer: W, format: TraceFormat) -> Self {
+        let mut tracer = Tracer {
+            writer: Box::new(writer),
+            format,
+            range: None,
+            cycle: 0,
+            error: None,
+        };
+        if format == TraceFormat::Binary {
+            tracer.error = write_header(&mut tracer.writer).err();
+        }
+        tracer
+    }
+
+    /// Only records the instructions at addresses in `range`, cycles still counting the others.
+    pub fn set_range(&mut self, range: Range<usize>) {
+        self.range = Some(range);
+    }
+
+    /// Records the instruction at the program counter, before it is executed.
+    pub fn trace(&mut self, state: &State, opcode: u16) {
+        let cycle = self.cycle;
+        self.cycle += 1;
+        let in_range = self
+            .range
+            .as_ref()
+            .map_or(true, |range| range.contains(&state.pc));
+        if !in_range || self.error.is_some() {
+            return;
+        }
+        let record = TraceRecord::new(cycle, state, opcode);
+        let result = match self.format {
+            TraceFormat::Text => writeln!(self.writer, "{}", record),
+            TraceFormat::Binary => record.write_binary(&mut self.writer),
+        };
+        self.error = result.err();
+    }
+
+    /// Flushes the trace, returning the first error met while writing it.
+    pub fn finish(&mut self) -> io::Result<()> {
+        match self.error.take() {
+            Some(error) => Err(error),
+            None => self.writer.flush(),
+        }
+    }
+}
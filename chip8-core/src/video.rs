@@ -0,0 +1,268 @@
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::path::Path;
+
+use crate::cpu::FRAME_RATE;
+use crate::display::Framebuffer;
+use crate::image::{scaled, BitWriter, Palette};
+use crate::state::{HIRES_DISPLAY_HEIGHT, HIRES_DISPLAY_WIDTH};
+
+/// GIF viewers slow down frames shown for less than 2 hundredths of a second.
+const MIN_GIF_DELAY: usize = 2;
+const MAX_LZW_CODES: u16 = 4096;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum VideoFormat {
+    /// Animated GIF, repeated frames being merged into longer ones.
+    Gif,
+    /// Uncompressed YUV 4:4:4 at 60 frames per second, which video encoders read.
+    Y4m,
+}
+
+impl VideoFormat {
+    /// Guesses the format from the extension of `path`.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Option<Self> {
+        let extension = path.as_ref().extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "gif" => Some(VideoFormat::Gif),
+            "y4m" => Some(VideoFormat::Y4m),
+            _ => None,
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            VideoFormat::Gif => "gif",
+            VideoFormat::Y4m => "y4m",
+        }
+    }
+}
+
+/// A frame waiting for the next different one, which tells how long it was shown.
+struct PendingFrame {
+    pixels: Vec<u8>,
+    start: usize,
+}
+
+/// Streams the frames of a run to a video, one frame per frame of the 60 Hz clock.
+/// Videos are as large as the high resolution display scaled by `scale`, low resolution
+/// frames being drawn with pixels twice as large.
+pub struct VideoRecorder<W: Write> {
+    writer: W,
+    format: VideoFormat,
+    scale: usize,
+    palette: Palette,
+    frames: usize,
+    pending: Option<PendingFrame>,
+}
+
+impl<W: Write> VideoRecorder<W> {
+    pub fn new(
+        mut writer: W,
+        format: VideoFormat,
+        scale: usize,
+        palette: Palette,
+    ) -> io::Result<Self> {
+        let scale = scale.max(1);
+        let width = HIRES_DISPLAY_WIDTH * scale;
+        let height = HIRES_DISPLAY_HEIGHT * scale;
+        match format {
+            VideoFormat::Gif => write_gif_header(&mut writer, width, height, &palette)?,
+            VideoFormat::Y4m => writeln!(
+                writer,
+                "YUV4MPEG2 W{} H{} F{}:1 Ip A1:1 C444",
+                width, height, FRAME_RATE
+            )?,
+        }
+        Ok(VideoRecorder {
+            writer,
+            format,
+            scale,
+            palette,
+            frames: 0,
+            pending: None,
+        })
+    }
+
+    /// Number of frames recorded.
+    pub fn len(&self) -> usize {
+        self.frames
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames == 0
+    }
+
+    /// Appends the display as it is at the end of a frame.
+    pub fn frame(&mut self, framebuffer: &Framebuffer) -> io::Result<()> {
+        let scale = self.scale * HIRES_DISPLAY_WIDTH / framebuffer.width();
+        let (_, _, pixels) = scaled(framebuffer, scale);
+        let frame = self.frames;
+        self.frames += 1;
+        match self.format {
+            VideoFormat::Y4m => write_y4m_frame(&mut self.writer, &pixels, &self.palette),
+            VideoFormat::Gif => {
+                if let Some(pending) = &mut self.pending {
+                    if pending.pixels == pixels {
+                        return Ok(());
+                    }
+                    // a frame too short to be shown is replaced by the next one
+                    if centiseconds(frame) - centiseconds(pending.start) < MIN_GIF_DELAY {
+                        pending.pixels = pixels;
+                        return Ok(());
+                    }
+                }
+                self.write_pending(frame)?;
+                self.pending = Some(PendingFrame {
+                    pixels,
+                    start: frame,
+                });
+                Ok(())
+            }
+        }
+    }
+
+    /// Writes the last frame and the end of the video, and returns the writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        if self.format == VideoFormat::Gif {
+            self.write_pending(self.frames)?;
+            self.writer.write_all(&[0x3B])?;
+        }
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+
+    /// Writes the pending GIF frame, shown until `end`.
+    fn write_pending(&mut self, end: usize) -> io::Result<()> {
+        let pending = match self.pending.take() {
+            Some(pending) => pending,
+            None => return Ok(()),
+        };
+        let delay = (centiseconds(end) - centiseconds(pending.start)).max(MIN_GIF_DELAY);
+        let scale = self.scale;
+        write_gif_frame(
+            &mut self.writer,
+            HIRES_DISPLAY_WIDTH * scale,
+            HIRES_DISPLAY_HEIGHT * scale,
+            &pending.pixels,
+            delay.min(u16::MAX as usize) as u16,
+        )
+    }
+}
+
+/// Time at which a frame starts, in the hundredths of a second GIF delays are counted in.
+fn centiseconds(frame: usize) -> usize {
+    let rate = FRAME_RATE as usize;
+    (frame * 100 + rate / 2) / rate
+}
+
+fn write_gif_header<W: Write>(
+    writer: &mut W,
+    width: usize,
+    height: usize,
+    palette: &Palette,
+) -> io::Result<()> {
+    writer.write_all(b"GIF89a")?;
+    writer.write_all(&(width as u16).to_le_bytes())?;
+    writer.write_all(&(height as u16).to_le_bytes())?;
+    // a global color table of 4 colors, no background color or aspect ratio
+    writer.write_all(&[0x91, 0, 0])?;
+    for color in palette {
+        writer.write_all(color)?;
+    }
+    // the application extension which makes the animation loop forever
+    writer.write_all(&[0x21, 0xFF, 11])?;
+    writer.write_all(b"NETSCAPE2.0")?;
+    writer.write_all(&[3, 1, 0, 0, 0])
+}
+
+fn write_gif_frame<W: Write>(
+    writer: &mut W,
+    width: usize,
+    height: usize,
+    pixels: &[u8],
+    delay: u16,
+) -> io::Result<()> {
+    // graphic control extension, frames being left in place until the next one
+    writer.write_all(&[0x21, 0xF9, 4, 0x04])?;
+    writer.write_all(&delay.to_le_bytes())?;
+    writer.write_all(&[0, 0])?;
+
+    // image descriptor covering the whole screen, without a local color table
+    writer.write_all(&[0x2C, 0, 0, 0, 0])?;
+    writer.write_all(&(width as u16).to_le_bytes())?;
+    writer.write_all(&(height as u16).to_le_bytes())?;
+    writer.write_all(&[0])?;
+
+    // minimum code size of the 4 colors, then the data in blocks of up to 255 bytes
+    writer.write_all(&[2])?;
+    for block in lzw(pixels).chunks(255) {
+        writer.write_all(&[block.len() as u8])?;
+        writer.write_all(block)?;
+    }
+    writer.write_all(&[0])
+}
+
+/// Compresses pixels of 4 colors with the variable length LZW codes of GIF.
+fn lzw(pixels: &[u8]) -> Vec<u8> {
+    const CLEAR: u16 = 4;
+    const END: u16 = 5;
+    const FIRST_CODE_SIZE: u32 = 3;
+
+    let mut bits = BitWriter::default();
+    let mut table: HashMap<(u16, u8), u16> = HashMap::new();
+    let mut code_size = FIRST_CODE_SIZE;
+    let mut next = END + 1;
+    bits.write(CLEAR as u32, code_size);
+    let mut prefix = pixels[0] as u16;
+    for &pixel in &pixels[1..] {
+        if let Some(&code) = table.get(&(prefix, pixel)) {
+            prefix = code;
+            continue;
+        }
+        bits.write(prefix as u32, code_size);
+        if next == MAX_LZW_CODES {
+            // the table is full, the decoder starts over along with it
+            bits.write(CLEAR as u32, code_size);
+            table.clear();
+            code_size = FIRST_CODE_SIZE;
+            next = END + 1;
+        } else {
+            table.insert((prefix, pixel), next);
+            next += 1;
+            if next > 1 << code_size {
+                code_size += 1;
+            }
+        }
+        prefix = pixel as u16;
+    }
+    bits.write(prefix as u32, code_size);
+    // the decoder adds a code for the last one, which may widen the codes
+    if next == 1 << code_size && next < MAX_LZW_CODES {
+        code_size += 1;
+    }
+    bits.write(END as u32, code_size);
+    bits.finish()
+}
+
+fn write_y4m_frame<W: Write>(writer: &mut W, pixels: &[u8], palette: &Palette) -> io::Result<()> {
+    let colors: Vec<[u8; 3]> = palette.iter().map(|&color| ycbcr(color)).collect();
+    writer.write_all(b"FRAME\n")?;
+    // the Y, Cb and Cr planes follow each other
+    let mut planes = [Vec::new(), Vec::new(), Vec::new()];
+    for &pixel in pixels {
+        for (plane, &value) in planes.iter_mut().zip(&colors[pixel as usize]) {
+            plane.push(value);
+        }
+    }
+    planes.iter().try_for_each(|plane| writer.write_all(plane))
+}
+
+/// Converts a color to the limited range BT.601 YCbCr video players assume.
+fn ycbcr([r, g, b]: [u8; 3]) -> [u8; 3] {
+    let (r, g, b) = (r as f64, g as f64, b as f64);
+    let y = 16.0 + (65.738 * r + 129.057 * g + 25.064 * b) / 256.0;
+    let cb = 128.0 + (-37.945 * r - 74.494 * g + 112.439 * b) / 256.0;
+    let cr = 128.0 + (112.439 * r - 94.154 * g - 18.285 * b) / 256.0;
+    [y.round() as u8, cb.round() as u8, cr.round() as u8]
+}
@@ -0,0 +1,99 @@
+use std::io::{self, Write};
+
+use std::collections::VecDeque;
+
+use crate::audio::{AudioEvent, Synth, Tone};
+use crate::cpu::{Chip8, FRAME_RATE};
+
+pub const DEFAULT_SAMPLE_RATE: u32 = 44100;
+
+/// Renders the buzzer of a run offline, a frame at a time, to 16-bit mono PCM samples.
+/// As the audio events are stamped with frames, the same run always renders the same samples.
+pub struct AudioRecorder {
+    synth: Synth,
+    sample_rate: u32,
+    // the frame of the machine the recording started at
+    start: u64,
+    frames: usize,
+    events: VecDeque<AudioEvent>,
+    samples: Vec<i16>,
+}
+
+impl AudioRecorder {
+    /// Starts recording the sound of `computer` from its current frame.
+    pub fn new(sample_rate: u32, tone: Tone, computer: &Chip8) -> Self {
+        let mut synth = Synth::new(sample_rate, tone);
+        synth.set_sound(computer.sound());
+        AudioRecorder {
+            synth,
+            sample_rate,
+            start: computer.frame(),
+            frames: 0,
+            events: VecDeque::new(),
+            samples: Vec::new(),
+        }
+    }
+
+    /// Number of frames rendered.
+    pub fn len(&self) -> usize {
+        self.frames
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames == 0
+    }
+
+    pub fn samples(&self) -> &[i16] {
+        &self.samples
+    }
+
+    /// Renders the next frame, given the audio events the machine emitted while running it.
+    /// Events stamped with a later frame are kept for it.
+    pub fn frame(&mut self, events: &[AudioEvent]) {
+        self.events.extend(events);
+        let frame = self.start + self.frames as u64;
+        while let Some(event) = self.events.front() {
+            if event.frame > frame {
+                break;
+            }
+            self.synth.set_sound(event.sound);
+            self.events.pop_front();
+        }
+        self.frames += 1;
+        // frames do not hold a whole number of samples, so their ends are rounded down
+        let end = self.frames * self.sample_rate as usize / FRAME_RATE as usize;
+        let count = end - self.samples.len();
+        for _ in 0..count {
+            let sample = self.synth.sample() * i16::MAX as f32;
+            self.samples.push(sample as i16);
+        }
+    }
+
+    /// Writes the samples as a WAV file.
+    pub fn write<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let data_size = self.samples.len() as u32 * 2;
+        writer.write_all(b"RIFF")?;
+        writer.write_all(&(36 + data_size).to_le_bytes())?;
+        writer.write_all(b"WAVE")?;
+
+        writer.write_all(b"fmt ")?;
+        writer.write_all(&16u32.to_le_bytes())?;
+        // uncompressed PCM, 1 channel
+        writer.write_all(&1u16.to_le_bytes())?;
+        writer.write_all(&1u16.to_le_bytes())?;
+        writer.write_all(&self.sample_rate.to_le_bytes())?;
+        writer.write_all(&(self.sample_rate * 2).to_le_bytes())?;
+        // 2 bytes per sample, 16 bits each
+        writer.write_all(&2u16.to_le_bytes())?;
+        writer.write_all(&16u16.to_le_bytes())?;
+
+        writer.write_all(b"data")?;
+        writer.write_all(&data_size.to_le_bytes())?;
+        let bytes: Vec<u8> = self
+            .samples
+            .iter()
+            .flat_map(|sample| sample.to_le_bytes())
+            .collect();
+        writer.write_all(&bytes)
+    }
+}
@@ -0,0 +1,135 @@
+use chip8::access::{Access, AccessCounts, AccessTracker, SelfModification, Watch, WatchHit};
+use chip8::asm::assemble;
+use chip8::keyboard::{KeyState, NUM_KEYS};
+use chip8::{Chip8, Quirks};
+use std::cell::RefCell;
+use std::ops::Range;
+use std::rc::Rc;
+
+const NO_KEYS: [KeyState; NUM_KEYS] = [KeyState::Up; NUM_KEYS];
+
+// reads and writes DATA, patches PATCH into CLS before running it, then loads a long address
+const PROGRAM: &str = "
+        LD I, data
+        LD V0, [I]
+        LD V0, 0x12
+        LD [I], V0
+        LD I, patch
+        LD V0, 0x00
+        LD V1, 0xE0
+        LD [I], V1
+patch:  db 0xFF, 0xFF
+        LD I, long data
+end:    JP end
+data:   db 0x00
+";
+
+const DATA: usize = 0x218;
+const PATCH: usize = 0x210;
+const LONG_LOAD: usize = 0x212;
+
+/// Runs the program with a tracker watching each of `watchpoints`.
+fn track(watchpoints: &[(Range<usize>, Watch)]) -> Rc<RefCell<AccessTracker>> {
+    let mut tracker = AccessTracker::new();
+    for (range, watch) in watchpoints {
+        tracker.add_watchpoint(range.clone(), *watch);
+    }
+    let tracker = Rc::new(RefCell::new(tracker));
+    let program = assemble(PROGRAM).unwrap();
+    let mut computer = Chip8::new(&program, Quirks::default(), 0).unwrap();
+    computer.state.ram.set_hook(Some(tracker.clone()));
+    for _ in 0..15 {
+        computer.iteration(&NO_KEYS).unwrap();
+    }
+    tracker
+}
+
+fn hit(address: usize, access: Access, value: u8, pc: usize) -> WatchHit {
+    WatchHit {
+        address,
+        access,
+        value,
+        pc,
+    }
+}
+
+#[test]
+fn reports_reads_and_writes_to_watched_memory() {
+    let data = DATA..DATA + 1;
+    let read = hit(DATA, Access::Read, 0x00, 0x202);
+    let write = hit(DATA, Access::Write, 0x12, 0x206);
+    assert_eq!(
+        track(&[(data.clone(), Watch::Read)])
+            .borrow_mut()
+            .take_hits(),
+        vec![read]
+    );
+    assert_eq!(
+        track(&[(data.clone(), Watch::Write)])
+            .borrow_mut()
+            .take_hits(),
+        vec![write]
+    );
+    let tracker = track(&[(data, Watch::ReadWrite)]);
+    assert_eq!(tracker.borrow_mut().take_hits(), vec![read, write]);
+    assert!(tracker.borrow_mut().take_hits().is_empty());
+}
+
+#[test]
+fn reports_instructions_executed_from_watched_memory() {
+    let tracker = track(&[
+        (PATCH..PATCH + 2, Watch::Fetch),
+        (PATCH..PATCH + 2, Watch::Write),
+        (LONG_LOAD + 2..LONG_LOAD + 4, Watch::Fetch),
+    ]);
+    assert_eq!(
+        tracker.borrow_mut().take_hits(),
+        vec![
+            hit(PATCH, Access::Write, 0x00, 0x20E),
+            hit(PATCH + 1, Access::Write, 0xE0, 0x20E),
+            hit(PATCH, Access::Fetch, 0x00, PATCH),
+            // the operand of the long load is reported as read by the load itself
+            hit(LONG_LOAD + 2, Access::Operand, (DATA >> 8) as u8, LONG_LOAD),
+        ]
+    );
+}
+
+#[test]
+fn counts_every_access() {
+    let tracker = track(&[]);
+    let tracker = tracker.borrow();
+    let counts = |reads, writes, fetches| AccessCounts {
+        reads,
+        writes,
+        fetches,
+    };
+    assert_eq!(tracker.counts(DATA), counts(1, 1, 0));
+    assert_eq!(tracker.counts(0x200), counts(0, 0, 1));
+    assert_eq!(tracker.counts(PATCH + 1), counts(0, 1, 1));
+    // both words of the long load are executed once
+    for address in LONG_LOAD..LONG_LOAD + 4 {
+        assert_eq!(tracker.counts(address), counts(0, 0, 1), "{:04X}", address);
+    }
+    assert_eq!(tracker.counts(LONG_LOAD + 4), counts(0, 0, 5));
+    assert_eq!(tracker.counts(0xFFF), counts(0, 0, 0));
+    assert_eq!(tracker.counts(0x10000), counts(0, 0, 0));
+}
+
+#[test]
+fn detects_self_modifying_code() {
+    let tracker = track(&[]);
+    // DATA is written but never executed
+    assert_eq!(
+        tracker.borrow().self_modifications(),
+        &[
+            SelfModification {
+                address: PATCH,
+                written_by: 0x20E,
+            },
+            SelfModification {
+                address: PATCH + 1,
+                written_by: 0x20E,
+            },
+        ]
+    );
+}
@@ -0,0 +1,105 @@
+use chip8::asm::{assemble, assemble_file, AsmError};
+use std::env;
+use std::fs;
+use std::process;
+
+/// The line, column and message of the error raised by `source`.
+fn error(source: &str) -> (usize, usize, String) {
+    let AsmError {
+        file,
+        line,
+        column,
+        message,
+    } = assemble(source).unwrap_err();
+    assert_eq!(file, "<source>");
+    (line, column, message)
+}
+
+#[test]
+fn reports_undefined_symbols() {
+    assert_eq!(
+        error("start:  CLS\n        JP nowhere\n"),
+        (2, 12, "unknown symbol `nowhere`".to_string())
+    );
+    assert_eq!(
+        error("\n        LD V0, 0x10 + SPEED\n"),
+        (2, 23, "unknown symbol `SPEED`".to_string())
+    );
+}
+
+#[test]
+fn reports_duplicate_names() {
+    assert_eq!(
+        error("start:  CLS\nstart:  RET\n"),
+        (2, 1, "`start` is already defined".to_string())
+    );
+    assert_eq!(
+        error("define SPEED 1\ndefine SPEED 2\n"),
+        (2, 8, "`SPEED` is already defined".to_string())
+    );
+    assert_eq!(
+        error("macro twice\nendm\nmacro twice\nendm\n"),
+        (3, 1, "macro `twice` is already defined".to_string())
+    );
+}
+
+#[test]
+fn reports_operands_out_of_range() {
+    assert_eq!(
+        error("        LD V0, 256\n"),
+        (1, 16, "256 does not fit in a byte".to_string())
+    );
+    assert_eq!(
+        error("        DRW V0, V1, 16\n"),
+        (1, 21, "16 does not fit in a nibble".to_string())
+    );
+    assert_eq!(
+        error("        CLS\n        JP 0x1000\n"),
+        (2, 12, "4096 does not fit in an address".to_string())
+    );
+    assert_eq!(
+        error("data:   db 1, 2, 256\n"),
+        (1, 18, "256 does not fit in a byte".to_string())
+    );
+    assert_eq!(
+        error("        LD V0\n"),
+        (1, 9, "invalid operands for `LD`".to_string())
+    );
+}
+
+#[test]
+fn stops_recursive_macros() {
+    let source = "
+macro forever
+        CLS
+        forever
+endm
+        forever
+";
+    assert_eq!(
+        error(source),
+        (4, 9, "includes or macros nested too deeply".to_string())
+    );
+}
+
+#[test]
+fn stops_include_cycles() {
+    let directory = env::temp_dir().join(format!("chip8-asm-{}", process::id()));
+    fs::create_dir_all(&directory).unwrap();
+    fs::write(directory.join("a.asm"), "        CLS\ninclude \"b.asm\"\n").unwrap();
+    fs::write(directory.join("b.asm"), "        RET\ninclude \"a.asm\"\n").unwrap();
+    let error = assemble_file(directory.join("a.asm")).unwrap_err();
+    fs::remove_dir_all(&directory).unwrap();
+    assert!(error.file.ends_with("a.asm"), "{}", error.file);
+    assert_eq!(
+        (error.line, error.column, error.message.as_str()),
+        (2, 1, "includes or macros nested too deeply")
+    );
+}
+
+#[test]
+fn reports_missing_includes() {
+    let error = error("        CLS\ninclude \"missing.asm\"\n");
+    assert_eq!((error.0, error.1), (2, 9));
+    assert!(error.2.starts_with("cannot include"), "{}", error.2);
+}
@@ -0,0 +1,88 @@
+use chip8::asm::assemble;
+use chip8::audio::{AudioEvent, Sound, DEFAULT_PITCH};
+use chip8::keyboard::{KeyState, NUM_KEYS};
+use chip8::{Chip8, Quirks};
+
+fn events(source: &str, frames: usize) -> Vec<AudioEvent> {
+    let program = assemble(source).unwrap();
+    let mut computer = Chip8::new(&program, Quirks::octo(), 0).unwrap();
+    for _ in 0..frames {
+        computer.run_frame(&[KeyState::Up; NUM_KEYS]).unwrap();
+    }
+    assert_eq!(computer.frame(), frames as u64);
+    computer.take_audio_events()
+}
+
+fn event(frame: u64, sound: Option<Sound>) -> AudioEvent {
+    AudioEvent { frame, sound }
+}
+
+const TONE: Option<Sound> = Some(Sound {
+    pattern: None,
+    pitch: DEFAULT_PITCH,
+});
+
+#[test]
+fn stamps_beeps_with_frames() {
+    // the second beep is cut short by the third, set during the same frame
+    let source = "
+        LD V0, 3
+        LD ST, V0
+        LD V1, 5
+        LD DT, V1
+wait:   LD V1, DT
+        SE V1, 0
+        JP wait
+        LD V0, 1
+        LD ST, V0
+        LD V0, 0
+        LD ST, V0
+        LD V0, 2
+        LD ST, V0
+end:    JP end
+    ";
+    assert_eq!(
+        events(source, 20),
+        vec![
+            event(0, TONE),
+            event(3, None),
+            event(5, TONE),
+            event(5, None),
+            event(5, TONE),
+            event(7, None),
+        ]
+    );
+}
+
+#[test]
+fn stamps_changes_of_sound() {
+    let source = "
+        LD V0, 10
+        LD ST, V0
+        LD V1, 100
+        PITCH V1
+        LD I, pattern
+        AUDIO
+end:    JP end
+pattern:
+        db 0xF0, 0xF0, 0xF0, 0xF0, 0xF0, 0xF0, 0xF0, 0xF0
+        db 0xF0, 0xF0, 0xF0, 0xF0, 0xF0, 0xF0, 0xF0, 0xF0
+    ";
+    let pitched = Sound {
+        pattern: None,
+        pitch: 100,
+    };
+    let patterned = Sound {
+        pattern: Some([0xF0; 16]),
+        pitch: 100,
+    };
+    assert_eq!(
+        events(source, 20),
+        vec![
+            event(0, TONE),
+            event(0, Some(pitched)),
+            event(0, Some(patterned)),
+            event(10, None),
+        ]
+    );
+}
@@ -0,0 +1,190 @@
+use chip8::asm::assemble;
+use chip8::debugger::{Debugger, Stop};
+use chip8::keyboard::{KeyState, NUM_KEYS};
+use chip8::{Chip8, Quirks};
+
+const NO_KEYS: [KeyState; NUM_KEYS] = [KeyState::Up; NUM_KEYS];
+
+const PROGRAM: &str = "
+        LD V0, 0
+        LD I, 0x300
+loop:   ADD V0, 1
+        CALL sub
+        LD [I], V0
+        JP loop
+sub:    ADD V1, 2
+        RET
+";
+
+const LOOP: usize = 0x204;
+const CALL: usize = 0x206;
+const STORE: usize = 0x208;
+const SUB: usize = 0x20C;
+
+fn machine() -> Chip8 {
+    let mut computer = Chip8::new(&assemble(PROGRAM).unwrap(), Quirks::default(), 0).unwrap();
+    computer.set_cycles_per_frame(4);
+    computer.state.delay_timer = 10;
+    computer
+}
+
+fn run(debugger: &mut Debugger, computer: &mut Chip8) -> Option<Stop> {
+    debugger.run_frame(computer, &NO_KEYS).unwrap()
+}
+
+/// Runs frames until the debugger stops the machine.
+fn run_until_stop(debugger: &mut Debugger, computer: &mut Chip8) -> Stop {
+    for _ in 0..100 {
+        if let Some(stop) = run(debugger, computer) {
+            return stop;
+        }
+    }
+    panic!("the debugger never stopped, at {:04X}", computer.state.pc);
+}
+
+#[test]
+fn pauses_on_breakpoints() {
+    let mut computer = machine();
+    let mut debugger = Debugger::new();
+    assert!(debugger.toggle_breakpoint(SUB));
+    assert_eq!(
+        run_until_stop(&mut debugger, &mut computer),
+        Stop::Breakpoint(SUB)
+    );
+    assert_eq!(computer.state.pc, SUB);
+    assert!(debugger.is_paused());
+    // nothing runs while paused
+    assert_eq!(run(&mut debugger, &mut computer), None);
+    assert_eq!(computer.state.pc, SUB);
+
+    // resuming runs the instruction under the breakpoint, stopping at the next pass
+    debugger.resume();
+    assert_eq!(
+        run_until_stop(&mut debugger, &mut computer),
+        Stop::Breakpoint(SUB)
+    );
+    assert_eq!(computer.state.registers[0], 2);
+
+    assert!(!debugger.toggle_breakpoint(SUB));
+    assert!(debugger.breakpoints().is_empty());
+}
+
+#[test]
+fn steps_single_instructions() {
+    let mut computer = machine();
+    let mut debugger = Debugger::new();
+    debugger.pause();
+    for &pc in &[0x202, LOOP, CALL, SUB] {
+        debugger.step();
+        assert_eq!(run(&mut debugger, &mut computer), Some(Stop::Step));
+        assert_eq!(computer.state.pc, pc);
+        assert!(debugger.is_paused());
+    }
+}
+
+#[test]
+fn steps_over_subroutine_calls() {
+    let mut computer = machine();
+    let mut debugger = Debugger::new();
+    debugger.pause();
+    for _ in 0..3 {
+        debugger.step();
+        run(&mut debugger, &mut computer);
+    }
+    assert_eq!(computer.state.pc, CALL);
+    debugger.step_over(&computer);
+    assert_eq!(
+        run_until_stop(&mut debugger, &mut computer),
+        Stop::Reached(STORE)
+    );
+    assert_eq!(computer.state.registers[1], 2);
+    assert!(computer.state.stack.as_slice().is_empty());
+    // other instructions are stepped
+    debugger.step_over(&computer);
+    assert_eq!(run(&mut debugger, &mut computer), Some(Stop::Step));
+    assert_eq!(computer.state.pc, STORE + 2);
+
+    // a breakpoint inside the subroutine stops it first
+    debugger.run_to(CALL);
+    assert_eq!(
+        run_until_stop(&mut debugger, &mut computer),
+        Stop::Reached(CALL)
+    );
+    debugger.toggle_breakpoint(SUB + 2);
+    debugger.step_over(&computer);
+    assert_eq!(
+        run_until_stop(&mut debugger, &mut computer),
+        Stop::Breakpoint(SUB + 2)
+    );
+}
+
+#[test]
+fn runs_to_an_address() {
+    let mut computer = machine();
+    let mut debugger = Debugger::new();
+    debugger.run_to(STORE);
+    assert_eq!(
+        run_until_stop(&mut debugger, &mut computer),
+        Stop::Reached(STORE)
+    );
+    assert_eq!(computer.state.pc, STORE);
+    assert!(debugger.is_paused());
+}
+
+#[test]
+fn pauses_after_writes_to_watched_addresses() {
+    let mut computer = machine();
+    let mut debugger = Debugger::new();
+    assert!(debugger.toggle_write_breakpoint(0x300));
+    // the machine stops once the write is done
+    assert_eq!(
+        run_until_stop(&mut debugger, &mut computer),
+        Stop::Write(0x300)
+    );
+    assert_eq!(computer.state.pc, STORE + 2);
+    assert_eq!(computer.state.ram.as_slice()[0x300], 1);
+    assert_eq!(debugger.write_breakpoints().len(), 1);
+}
+
+#[test]
+fn spreads_frames_across_steps() {
+    let mut computer = machine();
+    let mut debugger = Debugger::new();
+    debugger.pause();
+    // a frame ends once its four instructions ran, when the next one is stepped
+    for step in 1..=12 {
+        debugger.step();
+        run(&mut debugger, &mut computer);
+        assert_eq!(computer.frame(), (step as u64 - 1) / 4, "step {}", step);
+    }
+    assert_eq!(computer.state.delay_timer, 8);
+
+    // running ends the frame left complete, then runs a whole one
+    debugger.resume();
+    assert_eq!(run(&mut debugger, &mut computer), None);
+    assert_eq!((computer.frame(), debugger.frames()), (4, 4));
+    assert_eq!(computer.state.delay_timer, 6);
+}
+
+#[test]
+fn starts_afresh_with_a_replaced_machine() {
+    let mut computer = machine();
+    let mut debugger = Debugger::new();
+    debugger.pause();
+    for _ in 0..2 {
+        debugger.step();
+        run(&mut debugger, &mut computer);
+    }
+    debugger.step();
+    computer = machine();
+    debugger.reset();
+    // the pending step is dropped
+    assert_eq!(run(&mut debugger, &mut computer), None);
+    assert_eq!(computer.state.pc, 0x200);
+
+    // and the new machine runs a whole frame
+    debugger.resume();
+    assert_eq!(run(&mut debugger, &mut computer), None);
+    assert_eq!(computer.state.pc, SUB);
+    assert_eq!(computer.frame(), 1);
+}
@@ -0,0 +1,69 @@
+use chip8::asm::assemble;
+use chip8::instruction::{decode, DecodeError, Extension, Instruction};
+
+/// Every opcode that decodes, with its instruction.
+fn instructions() -> Vec<(u16, Instruction)> {
+    (0..=0xFFFF)
+        .filter_map(|opcode| decode(opcode).ok().map(|instruction| (opcode, instruction)))
+        .collect()
+}
+
+#[test]
+fn encodes_every_decoded_opcode_back() {
+    for (opcode, instruction) in instructions() {
+        assert_eq!(
+            instruction.encode(),
+            opcode,
+            "{:04X} {}",
+            opcode,
+            instruction
+        );
+    }
+}
+
+#[test]
+fn rejects_unknown_opcodes() {
+    // 39 in 0nnn, 10 whole groups, 3 of 5xyn, 9 of 8xyn, 9xy0, 2 of Exkk, then F000, F002,
+    // Fx01 and 13 more of Fxkk
+    let expected =
+        39 + 10 * 0x1000 + 3 * 0x100 + 9 * 0x100 + 0x100 + 2 * 0x10 + 2 + 0x10 + 13 * 0x10;
+    assert_eq!(instructions().len(), expected);
+    for &opcode in &[
+        0x0000, 0x00E1, 0x0123, 0x5001, 0x5004, 0x8008, 0x800F, 0x9001, 0xE09F, 0xF100, 0xF102,
+        0xF0FF,
+    ] {
+        assert_eq!(decode(opcode), Err(DecodeError { opcode }));
+    }
+    assert_eq!(
+        DecodeError { opcode: 0x5001 }.to_string(),
+        "unknown opcode 5001"
+    );
+}
+
+#[test]
+fn assembles_the_mnemonics_back() {
+    for (opcode, instruction) in instructions() {
+        let mut expected = opcode.to_be_bytes().to_vec();
+        let source = match instruction {
+            Instruction::LdILong => {
+                expected.extend_from_slice(&[0x12, 0x34]);
+                "LD I, LONG 0x1234".to_string()
+            }
+            _ => instruction.to_string(),
+        };
+        assert_eq!(assemble(&source).ok(), Some(expected), "{}", source);
+    }
+}
+
+#[test]
+fn sizes_and_extensions() {
+    let instruction = |opcode| decode(opcode).unwrap();
+    assert_eq!(instruction(0xF000).size(), 4);
+    assert_eq!(instruction(0xD125).size(), 2);
+    assert_eq!(instruction(0xD125).extension(), Extension::Chip8);
+    assert_eq!(instruction(0xD120).extension(), Extension::SuperChip);
+    assert_eq!(instruction(0x00FF).extension(), Extension::SuperChip);
+    assert_eq!(instruction(0x00D1).extension(), Extension::XoChip);
+    assert_eq!(instruction(0xF201).extension(), Extension::XoChip);
+    assert_eq!(instruction(0x00EE).extension(), Extension::Chip8);
+}
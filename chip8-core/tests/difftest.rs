@@ -0,0 +1,73 @@
+use std::fs;
+use std::path::Path;
+
+use chip8::asm::assemble_file;
+use chip8::difftest::{compare, parse_reference, Divergence};
+use chip8::keyboard::{KeyState, NUM_KEYS};
+use chip8::trace::TraceRecord;
+use chip8::{Chip8, Quirks};
+
+fn load(name: &str) -> (Chip8, Vec<TraceRecord>) {
+    let directory = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests");
+    let program = assemble_file(directory.join("roms").join(format!("{}.asm", name)))
+        .unwrap_or_else(|error| panic!("{}", error));
+    let reference = fs::read_to_string(directory.join("traces").join(format!("{}.trace", name)))
+        .expect("missing reference trace");
+    let reference = parse_reference(&reference).unwrap_or_else(|error| panic!("{}", error));
+    (
+        Chip8::new(&program, Quirks::default(), 0).unwrap(),
+        reference,
+    )
+}
+
+fn run(name: &str) -> Option<Divergence> {
+    let (mut computer, reference) = load(name);
+    compare(&mut computer, &reference, |_| [KeyState::Up; NUM_KEYS])
+}
+
+fn check(name: &str) {
+    if let Some(divergence) = run(name) {
+        panic!("{}\n{}", name, divergence);
+    }
+}
+
+#[test]
+fn subtract_from_other_register() {
+    check("8xy7");
+}
+
+#[test]
+fn skip_if_registers_differ() {
+    check("9xy0");
+}
+
+#[test]
+fn reports_first_divergence() {
+    let (mut computer, mut reference) = load("9xy0");
+    reference[4].registers[3] = 0xBC;
+    reference[6].pc = 0x210;
+    let divergence = compare(&mut computer, &reference, |_| [KeyState::Up; NUM_KEYS])
+        .expect("the trace was altered");
+    assert_eq!(divergence.index, 4);
+    assert_eq!(divergence.differences(), vec!["V3".to_string()]);
+    assert_eq!(divergence.context.len(), 4);
+    assert_eq!(
+        divergence.actual.map(|record| record.registers[3]),
+        Some(0xBB)
+    );
+}
+
+#[test]
+fn reports_running_past_the_end() {
+    let (mut computer, mut reference) = load("8xy7");
+    let last = *reference.last().unwrap();
+    reference.push(TraceRecord {
+        cycle: last.cycle + 1,
+        pc: 0x20C,
+        ..last
+    });
+    let divergence = compare(&mut computer, &reference, |_| [KeyState::Up; NUM_KEYS])
+        .expect("the program loops");
+    assert_eq!(divergence.index, reference.len() - 1);
+    assert_eq!(divergence.differences(), vec!["PC".to_string()]);
+}
@@ -0,0 +1,73 @@
+//! Disassembles `tests/roms/disasm.asm` for each extension and compares the listing with the
+//! one in `tests/golden`. Setting `UPDATE_GOLDEN` rewrites the listings.
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{self, Command};
+
+use chip8::asm::assemble_file;
+
+fn directory() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests")
+}
+
+/// Runs the disassembler with `flags` on `program`, returning what it printed.
+fn disassemble(program: &[u8], flags: &[&str], name: &str) -> String {
+    let path = env::temp_dir().join(format!("chip8-disasm-{}-{}.ch8", name, process::id()));
+    fs::write(&path, program).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_chip8-disasm"))
+        .args(flags)
+        .arg(&path)
+        .output()
+        .unwrap();
+    fs::remove_file(&path).unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).unwrap()
+}
+
+fn check(flags: &[&str], golden: &str) {
+    let program = assemble_file(directory().join("roms").join("disasm.asm")).unwrap();
+    let actual = disassemble(&program, flags, golden);
+    let path = directory().join("golden").join(format!("{}.txt", golden));
+    if env::var_os("UPDATE_GOLDEN").is_some() {
+        fs::write(&path, &actual)
+            .unwrap_or_else(|error| panic!("cannot write {}: {}", path.display(), error));
+        return;
+    }
+    let expected = fs::read_to_string(&path)
+        .unwrap_or_else(|error| panic!("cannot read {}: {}", path.display(), error));
+    assert_eq!(actual, expected, "listing differs from {}", path.display());
+}
+
+#[test]
+fn chip8() {
+    check(&[], "disasm-chip8");
+}
+
+#[test]
+fn schip() {
+    check(&["--schip"], "disasm-schip");
+}
+
+#[test]
+fn xo() {
+    check(&["--xo"], "disasm-xo");
+}
+
+#[test]
+fn prints_usage() {
+    let output = Command::new(env!("CARGO_BIN_EXE_chip8-disasm"))
+        .arg("--xo")
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "usage: chip8-disasm [--schip|--xo] rom_path\n"
+    );
+}
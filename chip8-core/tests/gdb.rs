@@ -0,0 +1,198 @@
+use chip8::asm::assemble;
+use chip8::gdb::{Connection, GdbSession};
+use chip8::keyboard::{KeyState, NUM_KEYS};
+use chip8::{Chip8, Quirks};
+use std::io::{self, Cursor, ErrorKind, Read, Write};
+
+const INTERRUPT: u8 = 0x03;
+
+const PROGRAM: &str = "
+        LD V0, 0x11
+        LD V1, 0x22
+        ADD V0, V1
+        LD I, 0x345
+        EXIT
+";
+
+const LOOP: &str = "
+loop:   ADD V0, 1
+        JP loop
+";
+
+/// A client whose packets are all sent up front, interrupts being seen by the running program.
+struct Client {
+    input: Cursor<Vec<u8>>,
+    output: Vec<u8>,
+}
+
+impl Read for Client {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        self.input.read(buffer)
+    }
+}
+
+impl Write for Client {
+    fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+        self.output.write(buffer)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Connection for Client {
+    fn read_nonblocking(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        let position = self.input.position() as usize;
+        if self.input.get_ref().get(position) == Some(&INTERRUPT) {
+            self.input.read(buffer)
+        } else {
+            Err(ErrorKind::WouldBlock.into())
+        }
+    }
+}
+
+fn packet(data: &str) -> String {
+    let checksum = data.bytes().fold(0u8, |sum, byte| sum.wrapping_add(byte));
+    format!("${}#{:02x}", data, checksum)
+}
+
+/// Serves `input` to the end, returning what the stub sent back.
+fn serve(computer: &mut Chip8, input: &str) -> String {
+    let client = Client {
+        input: Cursor::new(input.as_bytes().to_vec()),
+        output: Vec::new(),
+    };
+    let mut session = GdbSession::new(client);
+    session
+        .serve(computer, |_| [KeyState::Up; NUM_KEYS])
+        .unwrap();
+    String::from_utf8(session.into_connection().output).unwrap()
+}
+
+/// Sends each packet, checking it is acknowledged, and returns the data of the replies.
+fn replies(computer: &mut Chip8, packets: &[&str]) -> Vec<String> {
+    let input: String = packets.iter().map(|data| packet(data)).collect();
+    let output = serve(computer, &input);
+    let mut replies = Vec::new();
+    let mut rest = output.as_str();
+    while !rest.is_empty() {
+        rest = rest.strip_prefix("+$").expect("an acknowledged reply");
+        let end = rest.find('#').unwrap();
+        let data = &rest[..end];
+        assert_eq!(&rest[end..end + 3], &packet(data)[end + 1..]);
+        replies.push(data.to_string());
+        rest = &rest[end + 3..];
+    }
+    replies
+}
+
+fn machine(source: &str) -> Chip8 {
+    Chip8::new(&assemble(source).unwrap(), Quirks::default(), 0).unwrap()
+}
+
+#[test]
+fn acknowledges_packets_by_checksum() {
+    let mut computer = machine(PROGRAM);
+    let output = serve(&mut computer, &format!("$g#00+{}", packet("?")));
+    assert_eq!(output, format!("-+{}", packet("S05")));
+}
+
+#[test]
+fn reads_and_writes_registers() {
+    let mut computer = machine(PROGRAM);
+    computer.state.registers[0xF] = 0xAB;
+    computer.state.delay_timer = 7;
+    let registers = replies(&mut computer, &["g"]).remove(0);
+    // V0-VF, then I and PC little-endian, the stack depth and the timers
+    assert_eq!(
+        registers,
+        format!("{}ab{}", "00".repeat(15), "00000002000700")
+    );
+
+    let written = format!("{}{}", "01".repeat(16), "34120003000009");
+    assert_eq!(
+        replies(
+            &mut computer,
+            &[&format!("G{}", written), "p11", "P10=2301", "p10"]
+        ),
+        vec!["OK", "0003", "OK", "2301"]
+    );
+    assert_eq!(computer.state.registers, [1; 16]);
+    assert_eq!(computer.state.i, 0x0123);
+    assert_eq!(computer.state.pc, 0x300);
+    assert_eq!(computer.state.sound_timer, 9);
+}
+
+#[test]
+fn reads_and_writes_memory() {
+    let mut computer = machine(PROGRAM);
+    assert_eq!(
+        replies(&mut computer, &["m200,4", "M300,3:abcdef", "m2ff,5"]),
+        vec!["60116122", "OK", "00abcdef00"]
+    );
+    assert_eq!(
+        &computer.state.ram.as_slice()[0x300..0x303],
+        &[0xAB, 0xCD, 0xEF]
+    );
+}
+
+#[test]
+fn stops_at_breakpoints() {
+    let mut computer = machine(PROGRAM);
+    assert_eq!(
+        replies(&mut computer, &["Z0,204,2", "c", "g"])[..2],
+        ["OK", "S05"]
+    );
+    assert_eq!(computer.state.pc, 0x204);
+    assert_eq!(computer.state.registers[0], 0x11);
+
+    // once removed, the program runs to its end
+    assert_eq!(
+        replies(&mut computer, &["z0,204,2", "c"]),
+        vec!["OK", "W00"]
+    );
+    assert_eq!(computer.state.registers[0], 0x33);
+    assert_eq!(computer.state.i, 0x345);
+}
+
+#[test]
+fn steps_single_instructions() {
+    let mut computer = machine(PROGRAM);
+    assert_eq!(replies(&mut computer, &["s", "s"]), vec!["S05", "S05"]);
+    assert_eq!(computer.state.pc, 0x204);
+    assert_eq!(computer.state.registers[..2], [0x11, 0x22]);
+}
+
+#[test]
+fn interrupts_the_running_program() {
+    let mut computer = machine(LOOP);
+    let mut input = packet("c");
+    input.push(INTERRUPT as char);
+    assert_eq!(serve(&mut computer, &input), format!("+{}", packet("S02")));
+}
+
+#[test]
+fn rejects_malformed_packets() {
+    let mut computer = machine(PROGRAM);
+    assert_eq!(
+        replies(
+            &mut computer,
+            &[
+                "mzz",
+                "m2000,2",
+                "M300,2:ab",
+                "G0102",
+                "p99",
+                "P0=zz",
+                "Z0,204"
+            ]
+        ),
+        vec!["E01"; 7]
+    );
+    // unsupported packets and watchpoint kinds get an empty reply
+    assert_eq!(
+        replies(&mut computer, &["vMustReplyEmpty", "Z3,300,1"]),
+        vec!["", ""]
+    );
+}
@@ -0,0 +1,37 @@
+use chip8::ram::{MEMORY_SIZE, PROGRAM_START};
+use chip8::{Chip8, LoadError, Quirks};
+use std::env;
+use std::fs;
+use std::process::{self, Command};
+
+const MAX_ROM_SIZE: usize = MEMORY_SIZE - PROGRAM_START;
+
+#[test]
+fn loads_roms_filling_the_memory() {
+    let program = vec![0x12; MAX_ROM_SIZE];
+    let computer = Chip8::new(&program, Quirks::default(), 0).unwrap();
+    assert_eq!(computer.state.ram.as_slice()[MEMORY_SIZE - 1], 0x12);
+    assert_eq!(
+        Chip8::new(&[0; MAX_ROM_SIZE + 1], Quirks::default(), 0).err(),
+        Some(LoadError::RomTooLarge {
+            size: MAX_ROM_SIZE + 1,
+            max: MAX_ROM_SIZE,
+        })
+    );
+}
+
+#[test]
+fn reports_roms_too_large() {
+    let path = env::temp_dir().join(format!("chip8-too-large-{}.ch8", process::id()));
+    fs::write(&path, vec![0; 4000]).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_chip8-headless"))
+        .arg(&path)
+        .output()
+        .unwrap();
+    fs::remove_file(&path).unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "chip8-headless: ROM too large (4000 bytes, max 3584)\n"
+    );
+}
@@ -0,0 +1,54 @@
+use chip8::display::Framebuffer;
+use chip8::image::{parse_palette, write_png, write_ppm, ImageFormat, DEFAULT_PALETTE};
+
+fn framebuffer() -> Framebuffer {
+    let mut framebuffer = Framebuffer::new(4, 2);
+    framebuffer.set(0, 0, 1);
+    framebuffer.set(3, 1, 3);
+    framebuffer
+}
+
+#[test]
+fn scales_the_pixels_in_ppm_images() {
+    let palette = parse_palette("102030,ffffff").unwrap();
+    let mut image = Vec::new();
+    write_ppm(&mut image, &framebuffer(), 2, &palette).unwrap();
+
+    let header = b"P6\n8 4\n255\n";
+    assert_eq!(&image[..header.len()], header);
+    let pixels: Vec<&[u8]> = image[header.len()..].chunks(3).collect();
+    assert_eq!(pixels.len(), 8 * 4);
+    let pixel = |x: usize, y: usize| pixels[y * 8 + x];
+    for &(x, y) in &[(0, 0), (1, 0), (0, 1), (1, 1)] {
+        assert_eq!(pixel(x, y), &[255, 255, 255]);
+    }
+    assert_eq!(pixel(2, 0), &[0x10, 0x20, 0x30]);
+    assert_eq!(pixel(7, 3), &DEFAULT_PALETTE[3]);
+}
+
+#[test]
+fn writes_indexed_png_images() {
+    let mut image = Vec::new();
+    write_png(&mut image, &framebuffer(), 3, &DEFAULT_PALETTE).unwrap();
+
+    assert_eq!(&image[..8], b"\x89PNG\r\n\x1a\n");
+    assert_eq!(&image[12..16], b"IHDR");
+    assert_eq!(&image[16..20], &12u32.to_be_bytes());
+    assert_eq!(&image[20..24], &6u32.to_be_bytes());
+    assert_eq!(&image[24..26], &[8, 3]);
+    assert_eq!(&image[image.len() - 8..image.len() - 4], b"IEND");
+}
+
+#[test]
+fn parses_palettes() {
+    let palette = parse_palette("#000000, 00ff00").unwrap();
+    assert_eq!(palette[1], [0, 255, 0]);
+    assert_eq!(palette[2], DEFAULT_PALETTE[2]);
+    let palette = parse_palette("000000,111111,222222,333333").unwrap();
+    assert_eq!(palette[3], [0x33, 0x33, 0x33]);
+    assert_eq!(parse_palette("000000"), None);
+    assert_eq!(parse_palette("000000,fffff"), None);
+
+    assert_eq!(ImageFormat::from_path("screen.PNG"), Some(ImageFormat::Png));
+    assert_eq!(ImageFormat::from_path("screen.gif"), None);
+}
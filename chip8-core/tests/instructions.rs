@@ -0,0 +1,606 @@
+//! Executes every instruction once against a state built for it.
+
+use chip8::error::ExecError;
+use chip8::keyboard::{KeyState, Keys, NUM_KEYS};
+use chip8::quirks::MemoryQuirk;
+use chip8::ram::{BIG_FONT_START, FONT_START, XO_MEMORY_SIZE};
+use chip8::state::StateBuilder;
+use chip8::{Chip8, Quirks, HIRES_DISPLAY_WIDTH};
+
+const NO_KEYS: Keys = [KeyState::Up; NUM_KEYS];
+
+fn machine(builder: StateBuilder, quirks: Quirks) -> Chip8 {
+    Chip8::from_state(builder.build(), quirks)
+}
+
+fn execute_with(builder: StateBuilder, quirks: Quirks, opcode: u16) -> Chip8 {
+    let mut computer = machine(builder, quirks);
+    computer
+        .execute(opcode, &NO_KEYS)
+        .unwrap_or_else(|error| panic!("{:04X}: {}", opcode, error));
+    computer
+}
+
+fn execute(builder: StateBuilder, opcode: u16) -> Chip8 {
+    execute_with(builder, Quirks::default(), opcode)
+}
+
+fn execute_err(builder: StateBuilder, opcode: u16) -> ExecError {
+    machine(builder, Quirks::default())
+        .execute(opcode, &NO_KEYS)
+        .expect_err("the instruction should fail")
+}
+
+fn registers(computer: &Chip8, x: usize) -> (u8, u8) {
+    (computer.state.registers[x], computer.state.registers[0xF])
+}
+
+#[test]
+fn clear_screen() {
+    let mut computer = machine(StateBuilder::new(), Quirks::default());
+    computer.state.display_buffer.set(3, 4, 0b11);
+    computer.execute(0x00E0, &NO_KEYS).unwrap();
+    assert_eq!(computer.state.display_buffer.get(3, 4), 0b10);
+    assert!(computer.state.should_draw);
+}
+
+#[test]
+fn return_from_subroutine() {
+    let computer = execute(StateBuilder::new().stack(&[0x300, 0x400]), 0x00EE);
+    assert_eq!(computer.state.pc, 0x400);
+    assert_eq!(computer.state.stack.as_slice(), &[0x300]);
+    assert_eq!(
+        execute_err(StateBuilder::new(), 0x00EE),
+        ExecError::StackUnderflow
+    );
+}
+
+#[test]
+fn scroll() {
+    let scrolled = |opcode| {
+        let mut computer = machine(StateBuilder::new(), Quirks::default());
+        computer.state.display_buffer.set(8, 8, 1);
+        computer.execute(opcode, &NO_KEYS).unwrap();
+        let framebuffer = &computer.state.display_buffer;
+        let lit: Vec<usize> = (0..framebuffer.as_slice().len())
+            .filter(|&index| framebuffer.as_slice()[index] != 0)
+            .collect();
+        assert_eq!(lit.len(), 1, "{:04X}", opcode);
+        (lit[0] % framebuffer.width(), lit[0] / framebuffer.width())
+    };
+    assert_eq!(scrolled(0x00C3), (8, 11));
+    assert_eq!(scrolled(0x00D3), (8, 5));
+    assert_eq!(scrolled(0x00FB), (12, 8));
+    assert_eq!(scrolled(0x00FC), (4, 8));
+}
+
+#[test]
+fn exit() {
+    assert!(execute(StateBuilder::new(), 0x00FD).state.halted);
+}
+
+#[test]
+fn resolution() {
+    let computer = execute(StateBuilder::new(), 0x00FF);
+    assert!(computer.state.hires);
+    assert_eq!(computer.state.display_buffer.width(), HIRES_DISPLAY_WIDTH);
+    let computer = execute(StateBuilder::new().hires(), 0x00FE);
+    assert!(!computer.state.hires);
+    assert_eq!(computer.state.display_buffer.width(), 64);
+}
+
+#[test]
+fn jump() {
+    assert_eq!(execute(StateBuilder::new(), 0x1345).state.pc, 0x345);
+}
+
+#[test]
+fn call() {
+    let computer = execute(StateBuilder::new(), 0x2345);
+    assert_eq!(computer.state.pc, 0x345);
+    assert_eq!(computer.state.stack.as_slice(), &[0x202]);
+    assert_eq!(
+        execute_err(StateBuilder::new().stack(&[0x200; 16]), 0x2345),
+        ExecError::StackOverflow
+    );
+}
+
+#[test]
+fn skip_if_equal_to_byte() {
+    let builder = || StateBuilder::new().register(3, 0x42);
+    assert_eq!(execute(builder(), 0x3342).state.pc, 0x204);
+    assert_eq!(execute(builder(), 0x3343).state.pc, 0x202);
+}
+
+#[test]
+fn skip_if_not_equal_to_byte() {
+    let builder = || StateBuilder::new().register(3, 0x42);
+    assert_eq!(execute(builder(), 0x4342).state.pc, 0x202);
+    assert_eq!(execute(builder(), 0x4343).state.pc, 0x204);
+}
+
+#[test]
+fn skip_if_registers_equal() {
+    let builder = || StateBuilder::new().register(1, 7).register(2, 7);
+    assert_eq!(execute(builder(), 0x5120).state.pc, 0x204);
+    assert_eq!(execute(builder(), 0x5130).state.pc, 0x202);
+}
+
+#[test]
+fn skip_if_registers_differ() {
+    let builder = || StateBuilder::new().register(1, 7).register(2, 7);
+    assert_eq!(execute(builder(), 0x9120).state.pc, 0x202);
+    assert_eq!(execute(builder(), 0x9130).state.pc, 0x204);
+}
+
+#[test]
+fn skip_over_long_load() {
+    let builder = StateBuilder::new().program(&[0x30, 0x00, 0xF0, 0x00, 0x12, 0x34]);
+    assert_eq!(execute(builder, 0x3000).state.pc, 0x206);
+}
+
+#[test]
+fn skip_past_the_end_of_memory() {
+    // the skipped instruction would be read from outside of the memory, wholly or in part
+    assert_eq!(execute(StateBuilder::new().pc(0xFFE), 0x3000).state.pc, 0x1002);
+    assert_eq!(execute(StateBuilder::new().pc(0xFFD), 0x3000).state.pc, 0x1001);
+    let builder = StateBuilder::new().pc(0xFFE).register(1, 1);
+    assert_eq!(execute(builder, 0x4100).state.pc, 0x1002);
+}
+
+#[test]
+fn save_and_load_register_ranges() {
+    let builder = StateBuilder::new()
+        .i(0x300)
+        .register(1, 1)
+        .register(2, 2)
+        .register(3, 3);
+    let computer = execute(builder, 0x5132);
+    assert_eq!(&computer.state.ram.as_slice()[0x300..0x304], &[1, 2, 3, 0]);
+    assert_eq!(computer.state.i, 0x300);
+    let computer = execute(
+        StateBuilder::new().i(0x300).register(1, 1).register(3, 3),
+        0x5312,
+    );
+    assert_eq!(&computer.state.ram.as_slice()[0x300..0x303], &[3, 0, 1]);
+
+    let builder = StateBuilder::new().i(0x300).memory(0x300, &[4, 5, 6]);
+    assert_eq!(&execute(builder, 0x5463).state.registers[4..7], &[4, 5, 6]);
+    let builder = StateBuilder::new().i(0x300).memory(0x300, &[4, 5, 6]);
+    assert_eq!(&execute(builder, 0x5643).state.registers[4..7], &[6, 5, 4]);
+}
+
+#[test]
+fn load_byte() {
+    assert_eq!(
+        execute(StateBuilder::new(), 0x6A42).state.registers[0xA],
+        0x42
+    );
+}
+
+#[test]
+fn add_byte_wraps_around_leaving_vf() {
+    let computer = execute(
+        StateBuilder::new().register(2, 0xF0).register(0xF, 5),
+        0x7220,
+    );
+    assert_eq!(registers(&computer, 2), (0x10, 5));
+}
+
+#[test]
+fn load_register() {
+    let computer = execute(StateBuilder::new().register(2, 9), 0x8120);
+    assert_eq!(computer.state.registers[1], 9);
+}
+
+#[test]
+fn logic() {
+    let builder = || {
+        StateBuilder::new()
+            .register(1, 0b1100)
+            .register(2, 0b1010)
+            .register(0xF, 5)
+    };
+    let vip = |opcode| registers(&execute_with(builder(), Quirks::cosmac_vip(), opcode), 1);
+    assert_eq!(vip(0x8121), (0b1110, 0));
+    assert_eq!(vip(0x8122), (0b1000, 0));
+    assert_eq!(vip(0x8123), (0b0110, 0));
+    // only the COSMAC VIP resets VF
+    assert_eq!(registers(&execute(builder(), 0x8121), 1), (0b1110, 5));
+    let computer = execute_with(builder(), Quirks::superchip(), 0x8121);
+    assert_eq!(registers(&computer, 1), (0b1110, 5));
+}
+
+#[test]
+fn add_registers() {
+    let add = |vx, vy| {
+        registers(
+            &execute(StateBuilder::new().register(1, vx).register(2, vy), 0x8124),
+            1,
+        )
+    };
+    assert_eq!(add(0x10, 0x20), (0x30, 0));
+    assert_eq!(add(0xFF, 0x01), (0x00, 1));
+    assert_eq!(add(0xFF, 0xFF), (0xFE, 1));
+    // the flag wins when VF holds the result
+    let computer = execute(
+        StateBuilder::new().register(0xF, 200).register(1, 100),
+        0x8F14,
+    );
+    assert_eq!(computer.state.registers[0xF], 1);
+    let computer = execute(StateBuilder::new().register(0xF, 1).register(1, 2), 0x8F14);
+    assert_eq!(computer.state.registers[0xF], 0);
+}
+
+#[test]
+fn subtract() {
+    let sub = |vx, vy| {
+        registers(
+            &execute(StateBuilder::new().register(1, vx).register(2, vy), 0x8125),
+            1,
+        )
+    };
+    assert_eq!(sub(5, 3), (2, 1));
+    assert_eq!(sub(3, 5), (0xFE, 0));
+    assert_eq!(sub(5, 5), (0, 1));
+    let computer = execute(StateBuilder::new().register(0xF, 3).register(1, 5), 0x8F15);
+    assert_eq!(computer.state.registers[0xF], 0);
+    let computer = execute(StateBuilder::new().register(0xF, 5).register(1, 3), 0x8F15);
+    assert_eq!(computer.state.registers[0xF], 1);
+}
+
+#[test]
+fn subtract_from_other_register() {
+    let subn = |vx, vy| {
+        registers(
+            &execute(StateBuilder::new().register(1, vx).register(2, vy), 0x8127),
+            1,
+        )
+    };
+    assert_eq!(subn(3, 5), (2, 1));
+    assert_eq!(subn(5, 3), (0xFE, 0));
+    assert_eq!(subn(5, 5), (0, 1));
+    let computer = execute(StateBuilder::new().register(0xF, 3).register(1, 5), 0x8F17);
+    assert_eq!(computer.state.registers[0xF], 1);
+    let computer = execute(StateBuilder::new().register(0xF, 5).register(1, 3), 0x8F17);
+    assert_eq!(computer.state.registers[0xF], 0);
+}
+
+#[test]
+fn subtract_equal_registers_without_borrow() {
+    let builder = || {
+        StateBuilder::new()
+            .register(1, 0x42)
+            .register(2, 0x42)
+            .register(0xF, 7)
+    };
+    assert_eq!(registers(&execute(builder(), 0x8125), 1), (0, 1));
+    assert_eq!(registers(&execute(builder(), 0x8127), 1), (0, 1));
+    // a register subtracted from itself
+    assert_eq!(registers(&execute(builder(), 0x8115), 1), (0, 1));
+    assert_eq!(registers(&execute(builder(), 0x8117), 1), (0, 1));
+}
+
+#[test]
+fn shift_right() {
+    let builder = || StateBuilder::new().register(1, 0x10).register(2, 0x03);
+    // the COSMAC VIP shifts Vy, later interpreters Vx in place
+    let computer = execute_with(builder(), Quirks::cosmac_vip(), 0x8126);
+    assert_eq!(registers(&computer, 1), (0x01, 1));
+    assert_eq!(registers(&execute(builder(), 0x8126), 1), (0x08, 0));
+    let computer = execute(StateBuilder::new().register(0xF, 0x03), 0x8FF6);
+    assert_eq!(computer.state.registers[0xF], 1);
+    let computer = execute(StateBuilder::new().register(0xF, 0x02), 0x8FF6);
+    assert_eq!(computer.state.registers[0xF], 0);
+}
+
+#[test]
+fn shift_left() {
+    let builder = || StateBuilder::new().register(1, 0x01).register(2, 0x81);
+    let computer = execute_with(builder(), Quirks::cosmac_vip(), 0x812E);
+    assert_eq!(registers(&computer, 1), (0x02, 1));
+    assert_eq!(registers(&execute(builder(), 0x812E), 1), (0x02, 0));
+    let computer = execute(StateBuilder::new().register(0xF, 0x80), 0x8FFE);
+    assert_eq!(computer.state.registers[0xF], 1);
+    let computer = execute(StateBuilder::new().register(0xF, 0x40), 0x8FFE);
+    assert_eq!(computer.state.registers[0xF], 0);
+}
+
+#[test]
+fn load_address() {
+    assert_eq!(execute(StateBuilder::new(), 0xA345).state.i, 0x345);
+}
+
+#[test]
+fn jump_with_offset() {
+    let builder = || StateBuilder::new().register(0, 2).register(3, 4);
+    assert_eq!(execute(builder(), 0xB300).state.pc, 0x302);
+    let computer = execute_with(builder(), Quirks::superchip(), 0xB300);
+    assert_eq!(computer.state.pc, 0x304);
+}
+
+#[test]
+fn random() {
+    let computer = execute(StateBuilder::new().seed(7), 0xC10F);
+    assert_eq!(computer.state.registers[1] & 0xF0, 0);
+    assert_eq!(
+        execute(StateBuilder::new().register(1, 9), 0xC100)
+            .state
+            .registers[1],
+        0
+    );
+    let again = execute(StateBuilder::new().seed(7), 0xC10F);
+    assert_eq!(again.state.registers[1], computer.state.registers[1]);
+}
+
+#[test]
+fn draw() {
+    // the 0 glyph at (2, 1)
+    let builder = || {
+        StateBuilder::new()
+            .register(1, 2)
+            .register(2, 1)
+            .i(FONT_START as u16)
+    };
+    let computer = execute(builder(), 0xD125);
+    let framebuffer = &computer.state.display_buffer;
+    assert_eq!((framebuffer.get(2, 1), framebuffer.get(5, 1)), (1, 1));
+    assert_eq!((framebuffer.get(3, 2), framebuffer.get(6, 1)), (0, 0));
+    assert_eq!(computer.state.registers[0xF], 0);
+    assert!(computer.state.should_draw);
+
+    let mut computer = computer;
+    computer.execute(0xD125, &NO_KEYS).unwrap();
+    assert!(computer
+        .state
+        .display_buffer
+        .as_slice()
+        .iter()
+        .all(|&pixel| pixel == 0));
+    assert_eq!(computer.state.registers[0xF], 1);
+}
+
+#[test]
+fn draw_at_the_edges() {
+    let builder = || {
+        StateBuilder::new()
+            .register(1, 62)
+            .register(2, 31)
+            .i(FONT_START as u16)
+    };
+    let computer = execute_with(builder(), Quirks::cosmac_vip(), 0xD122);
+    let lit = computer
+        .state
+        .display_buffer
+        .as_slice()
+        .iter()
+        .filter(|&&pixel| pixel != 0);
+    assert_eq!(lit.count(), 2);
+    let computer = execute_with(builder(), Quirks::octo(), 0xD122);
+    let framebuffer = &computer.state.display_buffer;
+    assert_eq!(
+        (
+            framebuffer.get(62, 31),
+            framebuffer.get(1, 31),
+            framebuffer.get(1, 0)
+        ),
+        (1, 1, 1)
+    );
+    // coordinates themselves always wrap around
+    let builder = StateBuilder::new()
+        .register(1, 66)
+        .register(2, 33)
+        .i(FONT_START as u16);
+    assert_eq!(execute(builder, 0xD121).state.display_buffer.get(2, 1), 1);
+}
+
+#[test]
+fn draw_large_sprite() {
+    let builder = StateBuilder::new()
+        .hires()
+        .i(0x300)
+        .memory(0x300, &[0xFF; 32]);
+    let computer = execute(builder, 0xD120);
+    let lit = computer
+        .state
+        .display_buffer
+        .as_slice()
+        .iter()
+        .filter(|&&pixel| pixel != 0);
+    assert_eq!(lit.count(), 256);
+}
+
+#[test]
+fn draw_planes() {
+    let builder = StateBuilder::with_memory_size(XO_MEMORY_SIZE)
+        .i(0x300)
+        .memory(0x300, &[0x80, 0x40]);
+    let mut computer = machine(builder, Quirks::octo());
+    computer.execute(0xF301, &NO_KEYS).unwrap();
+    assert_eq!(computer.state.planes, 0b11);
+    computer.execute(0xD001, &NO_KEYS).unwrap();
+    let framebuffer = &computer.state.display_buffer;
+    assert_eq!((framebuffer.get(0, 0), framebuffer.get(1, 0)), (1, 2));
+}
+
+#[test]
+fn skip_on_keys() {
+    let mut keys = NO_KEYS;
+    keys[5] = KeyState::Down;
+    let skipped = |opcode| {
+        let mut computer = machine(
+            StateBuilder::new().register(1, 5).register(2, 6),
+            Quirks::default(),
+        );
+        computer.execute(opcode, &keys).unwrap();
+        computer.state.pc == 0x204
+    };
+    assert!(skipped(0xE19E));
+    assert!(!skipped(0xE29E));
+    assert!(!skipped(0xE1A1));
+    assert!(skipped(0xE2A1));
+    assert_eq!(
+        execute_err(StateBuilder::new().register(1, 0x10), 0xE19E),
+        ExecError::InvalidKey { key: 0x10 }
+    );
+}
+
+#[test]
+fn load_long_address() {
+    let builder = StateBuilder::with_memory_size(XO_MEMORY_SIZE).program(&[0xF0, 0x00, 0x12, 0x34]);
+    let computer = execute_with(builder, Quirks::octo(), 0xF000);
+    assert_eq!((computer.state.i, computer.state.pc), (0x1234, 0x204));
+}
+
+#[test]
+fn load_audio_pattern() {
+    let pattern: Vec<u8> = (0..16).collect();
+    let computer = execute(StateBuilder::new().i(0x300).memory(0x300, &pattern), 0xF002);
+    assert_eq!(
+        computer.state.audio_pattern.map(|pattern| pattern.to_vec()),
+        Some(pattern)
+    );
+}
+
+#[test]
+fn timers() {
+    let computer = execute(StateBuilder::new().delay_timer(9), 0xF107);
+    assert_eq!(computer.state.registers[1], 9);
+    let computer = execute(StateBuilder::new().register(1, 7), 0xF115);
+    assert_eq!(computer.state.delay_timer, 7);
+    let computer = execute(StateBuilder::new().register(1, 7).sound_timer(2), 0xF118);
+    assert_eq!(computer.state.sound_timer, 7);
+}
+
+#[test]
+fn wait_for_key() {
+    let mut computer = execute(StateBuilder::new(), 0xF30A);
+    assert!(computer.state.waiting_for_key);
+    computer.step(&NO_KEYS).unwrap();
+    assert_eq!(
+        (computer.state.waiting_for_key, computer.state.pc),
+        (true, 0x202)
+    );
+    let mut keys = NO_KEYS;
+    keys[0xB] = KeyState::Down;
+    computer.step(&keys).unwrap();
+    assert!(!computer.state.waiting_for_key);
+    assert_eq!(computer.state.registers[3], 0xB);
+}
+
+#[test]
+fn add_to_address() {
+    let computer = execute(
+        StateBuilder::new()
+            .i(0x300)
+            .register(1, 0x10)
+            .register(0xF, 5),
+        0xF11E,
+    );
+    assert_eq!(
+        (computer.state.i, computer.state.registers[0xF]),
+        (0x310, 5)
+    );
+
+    let quirks = Quirks {
+        add_i_sets_vf: true,
+        ..Quirks::default()
+    };
+    let computer = execute_with(StateBuilder::new().i(0xFFF).register(1, 1), quirks, 0xF11E);
+    assert_eq!(
+        (computer.state.i, computer.state.registers[0xF]),
+        (0x1000, 1)
+    );
+    let computer = execute_with(StateBuilder::new().i(0xFFE).register(1, 1), quirks, 0xF11E);
+    assert_eq!(
+        (computer.state.i, computer.state.registers[0xF]),
+        (0xFFF, 0)
+    );
+    // VF is added before being overwritten by the flag
+    let computer = execute_with(
+        StateBuilder::new().i(0x100).register(0xF, 0x10),
+        quirks,
+        0xFF1E,
+    );
+    assert_eq!(
+        (computer.state.i, computer.state.registers[0xF]),
+        (0x110, 0)
+    );
+}
+
+#[test]
+fn font_addresses() {
+    let computer = execute(StateBuilder::new().register(1, 0xA), 0xF129);
+    assert_eq!(computer.state.i as usize, FONT_START + 50);
+    let computer = execute(StateBuilder::new().register(1, 0xA), 0xF130);
+    assert_eq!(computer.state.i as usize, BIG_FONT_START + 100);
+}
+
+#[test]
+fn binary_coded_decimal() {
+    let computer = execute(StateBuilder::new().i(0x300).register(1, 254), 0xF133);
+    assert_eq!(&computer.state.ram.as_slice()[0x300..0x303], &[2, 5, 4]);
+    assert_eq!(computer.state.i, 0x300);
+}
+
+#[test]
+fn pitch() {
+    assert_eq!(
+        execute(StateBuilder::new().register(1, 99), 0xF13A)
+            .state
+            .pitch,
+        99
+    );
+}
+
+#[test]
+fn store_and_load_registers() {
+    let increments = [
+        (MemoryQuirk::Unchanged, 0x300),
+        (MemoryQuirk::IncrementByX, 0x302),
+        (MemoryQuirk::IncrementByXPlusOne, 0x303),
+    ];
+    for &(memory, i) in &increments {
+        let quirks = Quirks {
+            memory,
+            ..Quirks::default()
+        };
+        let builder = StateBuilder::new()
+            .i(0x300)
+            .registers([1, 2, 3, 4, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        let computer = execute_with(builder, quirks, 0xF255);
+        assert_eq!(&computer.state.ram.as_slice()[0x300..0x304], &[1, 2, 3, 0]);
+        assert_eq!(computer.state.i, i, "{:?}", memory);
+
+        let builder = StateBuilder::new().i(0x300).memory(0x300, &[5, 6, 7, 8]);
+        let computer = execute_with(builder, quirks, 0xF265);
+        assert_eq!(&computer.state.registers[..4], &[5, 6, 7, 0]);
+        assert_eq!(computer.state.i, i, "{:?}", memory);
+    }
+}
+
+#[test]
+fn user_flags() {
+    let mut computer = execute(
+        StateBuilder::new()
+            .register(0, 1)
+            .register(1, 2)
+            .register(2, 3),
+        0xF175,
+    );
+    assert_eq!(&computer.state.flags[..3], &[1, 2, 0]);
+    computer.state.registers = [0; 16];
+    computer.execute(0xF285, &NO_KEYS).unwrap();
+    assert_eq!(&computer.state.registers[..3], &[1, 2, 0]);
+}
+
+#[test]
+fn unknown_opcode() {
+    assert_eq!(
+        execute_err(StateBuilder::new().pc(0x240), 0x5121),
+        ExecError::UnknownOpcode {
+            opcode: 0x5121,
+            address: 0x240
+        }
+    );
+}
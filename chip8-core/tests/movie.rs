@@ -0,0 +1,86 @@
+use chip8::asm::assemble;
+use chip8::keyboard::{KeyState, NUM_KEYS};
+use chip8::movie::Movie;
+use chip8::{Chip8, Quirks};
+use std::io::ErrorKind;
+
+// draws random sprites at random places, the seed being moved along by key 5
+const PROGRAM: &str = "
+loop:   RND V0, 0x3F
+        RND V1, 0x1F
+        LD V2, 5
+        SKNP V2
+        RND V3, 0xFF
+        LD F, V3
+        DRW V0, V1, 5
+        JP loop
+";
+
+#[test]
+fn replays_a_recorded_run() {
+    let program = assemble(PROGRAM).unwrap();
+    let mut computer = Chip8::new(&program, Quirks::octo(), 1234).unwrap();
+    computer.set_cycles_per_frame(20);
+    let mut movie = Movie::new(&program, &computer, 1234);
+    for frame in 0..120 {
+        let mut keys = [KeyState::Up; NUM_KEYS];
+        if frame % 7 < 3 {
+            keys[5] = KeyState::Down;
+        }
+        movie.record(&keys);
+        computer.run_frame(&keys).unwrap();
+    }
+
+    let mut file = Vec::new();
+    movie.write(&mut file).unwrap();
+    let movie = Movie::read(file.as_slice()).unwrap();
+    assert_eq!(movie.len(), 120);
+    assert!(movie.matches(&program));
+    assert!(!movie.matches(&program[1..]));
+
+    let mut replayed = movie.machine(&program).unwrap();
+    for frame in 0..movie.len() {
+        replayed.run_frame(&movie.keys(frame).unwrap()).unwrap();
+    }
+    assert_eq!(movie.keys(movie.len()), None);
+    assert_eq!(replayed.state.display_buffer, computer.state.display_buffer);
+    assert_eq!(replayed.state.registers, computer.state.registers);
+}
+
+#[test]
+fn rejects_truncated_movies() {
+    let program = [0x12, 0x00];
+    let mut movie = Movie::new(
+        &program,
+        &Chip8::new(&program, Quirks::default(), 0).unwrap(),
+        0,
+    );
+    for _ in 0..3 {
+        movie.record(&[KeyState::Down; NUM_KEYS]);
+    }
+    let mut file = Vec::new();
+    movie.write(&mut file).unwrap();
+    assert_eq!(Movie::read(file.as_slice()).unwrap().len(), 3);
+
+    file.pop();
+    let error = Movie::read(file.as_slice()).unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::InvalidData);
+    assert_eq!(error.to_string(), "truncated movie");
+}
+
+#[test]
+fn truncates_rewound_frames() {
+    let program = [0x12, 0x00];
+    let mut movie = Movie::new(
+        &program,
+        &Chip8::new(&program, Quirks::default(), 0).unwrap(),
+        0,
+    );
+    let mut keys = [KeyState::Up; NUM_KEYS];
+    keys[0xF] = KeyState::Down;
+    movie.record(&keys);
+    movie.record(&keys);
+    movie.truncate(1);
+    assert_eq!(movie.len(), 1);
+    assert_eq!(movie.keys(0), Some(keys));
+}
@@ -0,0 +1,95 @@
+use chip8::asm::assemble;
+use chip8::keyboard::{KeyState, NUM_KEYS};
+use chip8::rewind::RewindBuffer;
+use chip8::{Chip8, Quirks};
+
+const NO_KEYS: [KeyState; NUM_KEYS] = [KeyState::Up; NUM_KEYS];
+
+// switches to high resolution on the second frame, which changes the size of the snapshots
+const PROGRAM: &str = "
+        LD V0, 1
+        LD DT, V0
+wait:   LD V0, DT
+        SE V0, 0
+        JP wait
+        HIGH
+loop:   RND V0, 0x7F
+        RND V1, 0x3F
+        RND V2, 0xFF
+        LD F, V2
+        DRW V0, V1, 5
+        JP loop
+";
+
+fn save(computer: &Chip8) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    computer.save_state(&mut bytes).unwrap();
+    bytes
+}
+
+/// Runs `frames` frames, returning the save state of the machine after each of them.
+fn run(buffer: &mut RewindBuffer, frames: usize) -> Vec<Vec<u8>> {
+    let program = assemble(PROGRAM).unwrap();
+    let mut computer = Chip8::new(&program, Quirks::octo(), 7).unwrap();
+    (0..frames)
+        .map(|_| {
+            computer.run_frame(&NO_KEYS).unwrap();
+            buffer.push(&computer);
+            save(&computer)
+        })
+        .collect()
+}
+
+#[test]
+fn restores_every_pushed_frame() {
+    let mut buffer = RewindBuffer::new(usize::MAX);
+    let states = run(&mut buffer, 20);
+    assert_eq!(buffer.len(), 20);
+    for state in states.iter().rev() {
+        assert_eq!(&save(&buffer.pop().unwrap()), state);
+    }
+    assert!(buffer.is_empty());
+    assert_eq!(buffer.size(), 0);
+    assert!(buffer.pop().is_none());
+}
+
+#[test]
+fn drops_the_oldest_frames_over_budget() {
+    let mut unlimited = RewindBuffer::new(usize::MAX);
+    let latest = run(&mut unlimited, 20).pop().unwrap().len();
+    // the latest snapshot is stored whole, and about half of the deltas fit next to it
+    let budget = latest + (unlimited.size() - latest) / 2;
+    let mut buffer = RewindBuffer::new(budget);
+    let states = run(&mut buffer, 20);
+    assert!(buffer.size() <= budget);
+    assert!(buffer.len() > 1 && buffer.len() < 20);
+
+    let kept = buffer.len();
+    for state in states[20 - kept..].iter().rev() {
+        assert_eq!(&save(&buffer.pop().unwrap()), state);
+    }
+    assert!(buffer.pop().is_none());
+}
+
+#[test]
+fn keeps_the_latest_frame_whatever_the_budget() {
+    let mut buffer = RewindBuffer::new(0);
+    let states = run(&mut buffer, 5);
+    assert_eq!(buffer.len(), 1);
+    assert_eq!(&save(&buffer.pop().unwrap()), states.last().unwrap());
+}
+
+#[test]
+fn clears_every_frame() {
+    let mut buffer = RewindBuffer::new(usize::MAX);
+    run(&mut buffer, 5);
+    buffer.clear();
+    assert!(buffer.is_empty());
+    assert_eq!(buffer.len(), 0);
+    assert_eq!(buffer.size(), 0);
+    assert!(buffer.pop().is_none());
+
+    let states = run(&mut buffer, 3);
+    assert_eq!(buffer.len(), 3);
+    assert_eq!(&save(&buffer.pop().unwrap()), states.last().unwrap());
+}
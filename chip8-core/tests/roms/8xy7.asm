@@ -0,0 +1,7 @@
+; SUBN Vx, Vy sets Vx = Vy - Vx, and VF when there is no borrow
+        LD V0, 5
+        LD V1, 8
+        SUBN V0, V1     ; V0 = 3, VF = 1
+        LD V2, 9
+        SUBN V2, V0     ; V2 = 0xFA, VF = 0
+end:    JP end
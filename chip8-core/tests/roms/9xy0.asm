@@ -0,0 +1,10 @@
+; SNE Vx, Vy skips the next instruction when the registers differ
+        LD V0, 1
+        LD V1, 2
+        SNE V0, V1      ; skipped over
+        LD V2, 0xAA
+        LD V3, 0xBB
+        LD V4, 1
+        SNE V4, V0      ; not skipped over
+        LD V5, 0xCC
+end:    JP end
@@ -0,0 +1,11 @@
+; Shared by the screen test ROMs, which include `report.asm` at their end.
+; `check` draws a tick when a register holds the expected value and a cross otherwise,
+; filling the screen with rows of ten results.
+; VC and VD hold the position of the next result and VE is scratch, so tests leave them alone.
+
+macro check register value
+        LD VE, 0
+        SNE register, value
+        LD VE, 1
+        CALL report
+endm
@@ -0,0 +1,24 @@
+; one of each kind of line the disassembler prints, for its listings in tests/golden
+start:  CLS
+        LD V1, 0x12
+        ADD V1, V2
+        DRW V1, V2, 5
+        SKNP VA
+        LD B, V3
+        JP V0, start
+        ; SUPER-CHIP
+        HIGH
+        SCR
+        DRW V1, V2, 0
+        LD HF, V4
+        LD R, V5
+        ; XO-CHIP
+        SCU 3
+        SAVE V1, V4
+        PLANE 2
+        PITCH V6
+        LD I, LONG data
+        ; not an instruction at all
+        db 0x51, 0x24
+        ; a long load cut short by the end of the program, then a byte left over
+data:   db 0xF0, 0x00, 0x12
@@ -0,0 +1,87 @@
+; Checks the results and VF of the arithmetic instructions, around their carries and borrows.
+; VF is checked first, as drawing the result of a check sets it.
+include "check.asm"
+
+        CLS
+        ; 8xy4
+        LD V0, 0xFF
+        LD V1, 0x01
+        ADD V0, V1
+        check VF, 1
+        check V0, 0x00
+        LD V0, 0x01
+        ADD V0, V1
+        check VF, 0
+        check V0, 0x02
+        ; 8xy5, no borrow when the registers are equal
+        LD V0, 5
+        LD V1, 3
+        SUB V0, V1
+        check VF, 1
+        check V0, 2
+        LD V0, 3
+        LD V1, 5
+        SUB V0, V1
+        check VF, 0
+        check V0, 0xFE
+        LD V0, 5
+        LD V1, 5
+        SUB V0, V1
+        check VF, 1
+        check V0, 0
+        ; 8xy7
+        LD V0, 3
+        LD V1, 5
+        SUBN V0, V1
+        check VF, 1
+        check V0, 2
+        LD V0, 5
+        LD V1, 3
+        SUBN V0, V1
+        check VF, 0
+        check V0, 0xFE
+        LD V0, 5
+        LD V1, 5
+        SUBN V0, V1
+        check VF, 1
+        check V0, 0
+        ; 8xy6
+        LD V0, 0x03
+        SHR V0, V0
+        check VF, 1
+        check V0, 0x01
+        LD V0, 0x02
+        SHR V0, V0
+        check VF, 0
+        check V0, 0x01
+        ; 8xyE
+        LD V0, 0x81
+        SHL V0, V0
+        check VF, 1
+        check V0, 0x02
+        LD V0, 0x01
+        SHL V0, V0
+        check VF, 0
+        check V0, 0x02
+        ; VF holds the flag rather than the result when it is the destination
+        LD VF, 200
+        LD V1, 100
+        ADD VF, V1
+        check VF, 1
+        LD VF, 3
+        LD V1, 5
+        SUB VF, V1
+        check VF, 0
+        LD VF, 3
+        SUBN VF, V1
+        check VF, 1
+        LD VF, 0x02
+        SHR VF, VF
+        check VF, 0
+        LD VF, 0x80
+        SHL VF, VF
+        check VF, 1
+
+end:    JP end
+
+include "report.asm"
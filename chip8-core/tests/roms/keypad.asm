@@ -0,0 +1,26 @@
+; Checks the key instructions, driven by `keypad.keys`.
+include "check.asm"
+
+        CLS
+        ; Fx0A, displaying the key pressed
+        LD V0, K
+        check V0, 0xA
+        LD V1, 56
+        LD V2, 26
+        LD F, V0
+        DRW V1, V2, 5
+        ; Ex9E, waiting for key 3
+        LD V1, 3
+wait:   SKP V1
+        JP wait
+        check V1, 3
+        ; ExA1, key 7 never being pressed
+        LD VE, 0
+        LD V1, 7
+        SKP V1
+        LD VE, 1
+        CALL report
+
+end:    JP end
+
+include "report.asm"
@@ -0,0 +1,134 @@
+; Checks the result of every CHIP-8 instruction whose behaviour the quirks leave alone.
+; Drawing the result of a check sets VF and I, so tests check VF first and reload I.
+include "check.asm"
+
+        CLS
+        ; 6xkk, 7xkk wrapping around without touching VF
+        LD V0, 0x2A
+        check V0, 0x2A
+        LD VF, 3
+        ADD V0, 0xF0
+        check VF, 3
+        check V0, 0x1A
+        ; 8xy0
+        LD V1, V0
+        check V1, 0x1A
+        ; 8xy1, 8xy2, 8xy3
+        LD V0, 0x0F
+        LD V1, 0xF0
+        OR V0, V1
+        check V0, 0xFF
+        LD V0, 0x3C
+        LD V1, 0x0F
+        AND V0, V1
+        check V0, 0x0C
+        LD V0, 0x3C
+        XOR V0, V1
+        check V0, 0x33
+        ; 8xy4, 8xy5, 8xy7
+        LD V0, 0x10
+        LD V1, 0x20
+        ADD V0, V1
+        check V0, 0x30
+        LD V1, 0x10
+        SUB V0, V1
+        check V0, 0x20
+        LD V1, 0x30
+        SUBN V0, V1
+        check V0, 0x10
+        ; 8xy6 and 8xyE, shifting a register into itself whatever the quirks
+        LD V0, 0x30
+        SHR V0, V0
+        check V0, 0x18
+        LD V0, 0x41
+        SHL V0, V0
+        check V0, 0x82
+
+        ; 3xkk, 4xkk, 5xy0 and 9xy0, skipping the instruction setting V2
+        LD V0, 5
+        LD V1, 5
+        LD V2, 0
+        SE V0, 5
+        LD V2, 1
+        check V2, 0
+        SE V0, 6
+        LD V2, 2
+        check V2, 2
+        LD V2, 0
+        SNE V0, 6
+        LD V2, 1
+        check V2, 0
+        SNE V0, 5
+        LD V2, 2
+        check V2, 2
+        LD V2, 0
+        SE V0, V1
+        LD V2, 1
+        check V2, 0
+        SNE V0, V1
+        LD V2, 2
+        check V2, 2
+
+        ; 2nnn and 00EE
+        LD V2, 0
+        CALL set_v2
+        check V2, 0x77
+        ; Annn, Fx33 and Fx65
+        LD V0, 123
+        LD I, scratch
+        LD B, V0
+        LD I, scratch
+        LD V2, [I]
+        check V0, 1
+        check V1, 2
+        check V2, 3
+        ; Fx55
+        LD V0, 0x11
+        LD V1, 0x22
+        LD I, scratch
+        LD [I], V1
+        LD V0, 0
+        LD V1, 0
+        LD I, scratch
+        LD V1, [I]
+        check V0, 0x11
+        check V1, 0x22
+        ; Fx1E
+        LD I, table
+        LD V0, 2
+        ADD I, V0
+        LD V0, [I]
+        check V0, 3
+        ; Fx29, the first row of the 0 glyph
+        LD V0, 0
+        LD F, V0
+        LD V0, [I]
+        check V0, 0xF0
+        ; Cxkk with an empty mask
+        RND V0, 0
+        check V0, 0
+        ; Fx15 and Fx07, waiting for the delay timer to run out
+        LD V0, 0x10
+        LD DT, V0
+wait:   LD V1, DT
+        SE V1, 0
+        JP wait
+        check V1, 0
+        ; Dxyn, erasing a sprite sets VF
+        LD V0, 40
+        LD V1, 26
+        LD I, table
+        DRW V0, V1, 4
+        check VF, 0
+        LD I, table
+        DRW V0, V1, 4
+        check VF, 1
+
+end:    JP end
+
+set_v2: LD V2, 0x77
+        RET
+
+table:  db 1, 2, 3, 4
+
+include "report.asm"
@@ -0,0 +1,59 @@
+; Checks the behaviours that differ between interpreters, a tick meaning that the
+; interpreter behaves like the COSMAC VIP.
+include "check.asm"
+
+        CLS
+        ; 8xy1 resets VF
+        LD VF, 5
+        OR V0, V1
+        check VF, 0
+        ; 8xy6 shifts Vy into Vx
+        LD V1, 4
+        LD V2, 1
+        SHR V2, V1
+        check V2, 2
+        ; Fx55 leaves I past the last byte stored
+        LD V0, 0x22
+        LD I, scratch + 2
+        LD [I], V0
+        LD I, scratch
+        LD V0, 0x10
+        LD V1, 0x11
+        LD [I], V1
+        LD V0, [I]
+        check V0, 0x22
+        ; Bnnn adds V0 rather than V2, the high nibble of the address being 2
+        LD VE, 0
+        LD V0, 2
+        LD V2, 4
+        JP V0, jumps
+jumps:  JP jumped
+        LD VE, 1
+        JP jumped
+jumped: CALL report
+        ; Dxyn clips sprites at the right edge, so that nothing wraps onto the lit pixel
+        LD V0, 0
+        LD V1, 40
+        LD V2, 62
+        LD I, pixel
+        DRW V0, V1, 1
+        LD I, row
+        DRW V2, V1, 1
+        LD V3, VF
+        DRW V2, V1, 1
+        LD I, pixel
+        DRW V0, V1, 1
+        check V3, 0
+        ; Fx1E leaves VF alone when I overflows
+        LD VF, 7
+        LD I, 0xFFF
+        LD V0, 1
+        ADD I, V0
+        check VF, 7
+
+end:    JP end
+
+pixel:  db 0x80
+row:    db 0xFF
+
+include "report.asm"
@@ -0,0 +1,16 @@
+; Draws the result of a check held in VE, then moves to the next position.
+report: LD I, pass
+        SE VE, 1
+        LD I, fail
+        DRW VC, VD, 4
+        ADD VC, 6
+        SE VC, 60
+        RET
+        LD VC, 0
+        ADD VD, 6
+        RET
+
+pass:   db 0x08, 0x10, 0xA0, 0x40
+fail:   db 0xA0, 0x40, 0xA0, 0x00
+scratch:
+        db 0, 0, 0, 0, 0, 0, 0, 0
@@ -0,0 +1,183 @@
+use chip8::asm::assemble;
+use chip8::keyboard::{KeyState, NUM_KEYS};
+use chip8::ram::XO_MEMORY_SIZE;
+use chip8::savestate::VERSION;
+use chip8::state::StateBuilder;
+use chip8::{Chip8, Framebuffer, LoadError, Quirks};
+use std::io::ErrorKind;
+
+const NO_KEYS: [KeyState; NUM_KEYS] = [KeyState::Up; NUM_KEYS];
+
+// sets up everything a save state holds, then draws random sprites from a subroutine
+const PROGRAM: &str = "
+        HIGH
+        LD I, pattern
+        AUDIO
+        LD V0, 200
+        PITCH V0
+        LD V0, 0x12
+        LD V1, 0x34
+        LD R, V1
+        LD I, long 0xF000
+        LD [I], V1
+        LD V0, 30
+        LD DT, V0
+        LD ST, V0
+loop:   CALL draw
+        JP loop
+draw:   RND V0, 0x7F
+        RND V1, 0x3F
+        LD I, pattern
+        DRW V0, V1, 8
+        RET
+pattern:
+        db 0x3C, 0x42, 0x81, 0xA5, 0x81, 0x99, 0x42, 0x3C
+        db 0x00, 0xFF, 0x00, 0xFF, 0x00, 0xFF, 0x00, 0xFF
+";
+
+fn save(computer: &Chip8) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    computer.save_state(&mut bytes).unwrap();
+    bytes
+}
+
+/// A machine saved in the middle of a subroutine, a few frames into the program.
+fn running_machine() -> Chip8 {
+    let program = assemble(PROGRAM).unwrap();
+    let mut computer = Chip8::new(&program, Quirks::octo(), 42).unwrap();
+    computer.set_cycles_per_frame(25);
+    for _ in 0..10 {
+        computer.run_frame(&NO_KEYS).unwrap();
+    }
+    while computer.state.stack.as_slice().is_empty() {
+        computer.iteration(&NO_KEYS).unwrap();
+    }
+    computer
+}
+
+fn load_error(bytes: &[u8]) -> String {
+    let error = Chip8::load_state(bytes).err().unwrap();
+    assert_eq!(error.kind(), ErrorKind::InvalidData);
+    error.to_string()
+}
+
+#[test]
+fn round_trips_a_running_machine() {
+    let mut computer = running_machine();
+    let saved = save(&computer);
+    let mut loaded = Chip8::load_state(saved.as_slice()).unwrap();
+    assert_eq!(save(&loaded), saved);
+
+    assert_eq!(loaded.quirks, Quirks::octo());
+    assert_eq!(loaded.cycles_per_frame(), 25);
+    let (state, original) = (&loaded.state, &computer.state);
+    assert_eq!(state.ram.as_slice().len(), XO_MEMORY_SIZE);
+    assert_eq!(state.ram.as_slice(), original.ram.as_slice());
+    assert_eq!(&state.ram.as_slice()[0xF000..0xF002], &[0x12, 0x34]);
+    assert_eq!(state.registers, original.registers);
+    assert_eq!(state.stack.as_slice(), original.stack.as_slice());
+    assert_eq!(state.stack.as_slice().len(), 1);
+    assert_eq!(state.display_buffer, original.display_buffer);
+    assert!(state.hires);
+    assert_eq!((state.delay_timer, state.sound_timer), (20, 20));
+    assert_eq!((state.pc, state.i), (original.pc, original.i));
+    assert!(state.audio_pattern.is_some());
+    assert_eq!(state.audio_pattern, original.audio_pattern);
+    assert_eq!(state.pitch, 200);
+    assert_eq!(&state.flags[..2], &[0x12, 0x34]);
+    assert_eq!(state.rng, original.rng);
+    assert_eq!(loaded.frame(), 10);
+    assert!(loaded.sound().is_some());
+    assert_eq!(loaded.sound(), computer.sound());
+
+    // the random sprites keep landing in the same places, and the sound stops on the same frame
+    computer.take_audio_events();
+    for _ in 0..30 {
+        computer.run_frame(&NO_KEYS).unwrap();
+        loaded.run_frame(&NO_KEYS).unwrap();
+    }
+    assert_eq!(loaded.state.display_buffer, computer.state.display_buffer);
+    let events = loaded.take_audio_events();
+    assert_eq!(events.len(), 1);
+    assert_eq!((events[0].frame, events[0].sound), (30, None));
+    assert_eq!(events, computer.take_audio_events());
+    assert_eq!(save(&loaded), save(&computer));
+}
+
+#[test]
+fn rejects_invalid_headers() {
+    let saved = save(&running_machine());
+
+    let mut bytes = saved.clone();
+    bytes[0] = b'X';
+    assert_eq!(load_error(&bytes), "not a save state");
+
+    let mut bytes = saved.clone();
+    bytes[4] = VERSION + 1;
+    assert_eq!(load_error(&bytes), "unsupported save state version");
+
+    // the quirks follow the version: shifts, then the memory quirk
+    let mut bytes = saved.clone();
+    bytes[6] = 3;
+    assert_eq!(load_error(&bytes), "invalid memory quirk");
+
+    // and, after four more flags, the memory size
+    for &size in &[100u32, XO_MEMORY_SIZE as u32 + 1] {
+        let mut bytes = saved.clone();
+        bytes[11..15].copy_from_slice(&size.to_be_bytes());
+        assert_eq!(load_error(&bytes), "invalid memory size");
+    }
+
+    let error = Chip8::load_state(&saved[..saved.len() - 1]).err().unwrap();
+    assert_eq!(error.kind(), ErrorKind::UnexpectedEof);
+}
+
+/// The error raised by loading `computer` once `tamper` changed its state.
+fn invalid_state(tamper: impl FnOnce(&mut Chip8)) -> LoadError {
+    let mut computer = Chip8::from_state(StateBuilder::new().build(), Quirks::default());
+    tamper(&mut computer);
+    let error = Chip8::load_state(save(&computer).as_slice()).err().unwrap();
+    assert_eq!(error.kind(), ErrorKind::InvalidData);
+    *error
+        .get_ref()
+        .unwrap()
+        .downcast_ref::<LoadError>()
+        .unwrap()
+}
+
+#[test]
+fn rejects_machines_which_cannot_run() {
+    let error = invalid_state(|computer| computer.state.planes = 4);
+    assert_eq!(
+        error,
+        LoadError::InvalidState {
+            reason: "unknown bitplanes selected"
+        }
+    );
+    assert_eq!(
+        error.to_string(),
+        "invalid save state: unknown bitplanes selected"
+    );
+    let error = invalid_state(|computer| {
+        computer.state.display_buffer = Framebuffer::from_pixels(64, 32, vec![4; 64 * 32])
+    });
+    assert_eq!(
+        error.to_string(),
+        "invalid save state: pixel lit on an unknown bitplane"
+    );
+    let error = invalid_state(|computer| computer.state.hires = true);
+    assert_eq!(
+        error.to_string(),
+        "invalid save state: display resolution does not match the mode"
+    );
+    let error = invalid_state(|computer| computer.state.display_buffer = Framebuffer::new(100, 50));
+    assert_eq!(
+        error.to_string(),
+        "invalid save state: invalid display resolution"
+    );
+    // both bitplanes are fine
+    let mut computer = Chip8::from_state(StateBuilder::new().hires().build(), Quirks::default());
+    computer.state.planes = 3;
+    computer.state.display_buffer = Framebuffer::from_pixels(128, 64, vec![3; 128 * 64]);
+    assert!(Chip8::load_state(save(&computer).as_slice()).is_ok());
+}
@@ -0,0 +1,197 @@
+//! Runs the test ROMs written for this emulator in `tests/roms` headlessly, checks that each
+//! of their checks drew the expected tick or cross, and compares the screen they leave with
+//! the golden images in `tests/golden`. Setting `UPDATE_GOLDEN` rewrites the golden images.
+
+use std::env;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+
+use chip8::asm::assemble_file;
+use chip8::script::KeyScript;
+use chip8::{Chip8, Framebuffer, Quirks};
+
+// the sprites `report.asm` draws for a passed and a failed check, ten to a row six pixels apart
+const TICK: [u8; 4] = [0x08, 0x10, 0xA0, 0x40];
+const CROSS: [u8; 4] = [0xA0, 0x40, 0xA0, 0x00];
+const RESULTS_PER_ROW: usize = 10;
+const RESULT_SPACING: usize = 6;
+
+fn directory() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests")
+}
+
+/// Counts the checks in the source of a ROM, `check` macros and direct calls to `report`.
+fn count_checks(rom: &str) -> usize {
+    let path = directory().join("roms").join(format!("{}.asm", rom));
+    let source = fs::read_to_string(&path)
+        .unwrap_or_else(|error| panic!("cannot read {}: {}", path.display(), error));
+    source
+        .lines()
+        .filter(|line| {
+            let code = line.split(';').next().unwrap_or("");
+            let words: Vec<&str> = code
+                .split_whitespace()
+                .skip_while(|word| word.ends_with(':'))
+                .collect();
+            match words.as_slice() {
+                [first, ..] if first.eq_ignore_ascii_case("check") => true,
+                [call, "report"] => call.eq_ignore_ascii_case("call"),
+                _ => false,
+            }
+        })
+        .count()
+}
+
+/// A tick for every check of `rom`, which all pass.
+fn ticks(rom: &str) -> String {
+    "v".repeat(count_checks(rom))
+}
+
+/// Reads the results drawn on the screen in order, `v` for a tick and `x` for a cross,
+/// up to the first empty place.
+fn read_results(framebuffer: &Framebuffer) -> String {
+    let mut results = String::new();
+    for index in 0.. {
+        let x = index % RESULTS_PER_ROW * RESULT_SPACING;
+        let y = index / RESULTS_PER_ROW * RESULT_SPACING;
+        if y + TICK.len() > framebuffer.height() {
+            break;
+        }
+        let mut sprite = [0u8; 4];
+        for (row, byte) in sprite.iter_mut().enumerate() {
+            for column in 0..RESULT_SPACING - 1 {
+                if framebuffer.get(x + column, y + row) != 0 {
+                    *byte |= 0x80 >> column;
+                }
+            }
+        }
+        match sprite {
+            TICK => results.push('v'),
+            CROSS => results.push('x'),
+            [0, 0, 0, 0] => break,
+            _ => panic!(
+                "unexpected sprite {:02X?} in place of result {}",
+                sprite, index
+            ),
+        }
+    }
+    results
+}
+
+/// Runs a ROM for a number of frames, holding the keys of its key script if it has one.
+fn run(rom: &str, quirks: Quirks, frames: usize) -> Framebuffer {
+    let roms = directory().join("roms");
+    let program = assemble_file(roms.join(format!("{}.asm", rom)))
+        .unwrap_or_else(|error| panic!("{}", error));
+    let script = match fs::read_to_string(roms.join(format!("{}.keys", rom))) {
+        Ok(source) => KeyScript::parse(&source).unwrap_or_else(|error| panic!("{}", error)),
+        Err(_) => KeyScript::default(),
+    };
+    let mut computer = Chip8::new(&program, quirks, 0).unwrap();
+    for frame in 0..frames {
+        if let Err(error) = computer.run_frame(&script.keys(frame)) {
+            panic!("{}: {} on frame {}", rom, error, frame);
+        }
+    }
+    computer.state.display_buffer
+}
+
+fn render(framebuffer: &Framebuffer) -> String {
+    let rows: Vec<String> = framebuffer
+        .rows()
+        .map(|row| {
+            row.iter()
+                .map(|&pixel| if pixel == 0 { '.' } else { '#' })
+                .collect()
+        })
+        .collect();
+    rows.join("\n")
+}
+
+/// Runs `rom`, expecting the `v` and `x` of `results` to be drawn for its checks, then
+/// compares the whole screen with its golden image.
+fn check(rom: &str, quirks: Quirks, frames: usize, golden: &str, results: &str) {
+    assert_eq!(
+        results.len(),
+        count_checks(rom),
+        "{} expects a result for each of its checks",
+        golden
+    );
+    let actual = run(rom, quirks, frames);
+    assert_eq!(read_results(&actual), results, "results of {}", golden);
+    let path = directory().join("golden").join(format!("{}.pbm", golden));
+    if env::var_os("UPDATE_GOLDEN").is_some() {
+        File::create(&path)
+            .and_then(|mut file| actual.write_pbm(&mut file))
+            .unwrap_or_else(|error| panic!("cannot write {}: {}", path.display(), error));
+        return;
+    }
+    let source = fs::read_to_string(&path)
+        .unwrap_or_else(|error| panic!("cannot read {}: {}", path.display(), error));
+    let expected = Framebuffer::read_pbm(&source)
+        .unwrap_or_else(|| panic!("{} is not a plain PBM", path.display()));
+    // the golden images only tell lit pixels apart
+    let lit: Vec<u8> = actual
+        .as_slice()
+        .iter()
+        .map(|&pixel| pixel.min(1))
+        .collect();
+    let actual = Framebuffer::from_pixels(actual.width(), actual.height(), lit);
+    assert!(
+        actual == expected,
+        "{} differs from {}\nexpected:\n{}\nactual:\n{}",
+        rom,
+        path.display(),
+        render(&expected),
+        render(&actual)
+    );
+}
+
+#[test]
+fn opcodes() {
+    check(
+        "opcodes",
+        Quirks::default(),
+        60,
+        "opcodes",
+        &ticks("opcodes"),
+    );
+}
+
+#[test]
+fn flags() {
+    check("flags", Quirks::default(), 60, "flags", &ticks("flags"));
+}
+
+#[test]
+fn keypad() {
+    check("keypad", Quirks::default(), 60, "keypad", &ticks("keypad"));
+}
+
+// a tick in the quirks ROM means behaving like the COSMAC VIP: VF reset by logic, shifts of Vy,
+// I moved by Fx55, Bnnn adding V0, clipped sprites and VF left alone by Fx1E
+#[test]
+fn quirks_vip() {
+    check(
+        "quirks",
+        Quirks::cosmac_vip(),
+        30,
+        "quirks-vip",
+        &ticks("quirks"),
+    );
+}
+
+#[test]
+fn quirks_chip48() {
+    check("quirks", Quirks::chip48(), 30, "quirks-chip48", "xxxxvv");
+}
+
+#[test]
+fn quirks_schip() {
+    check("quirks", Quirks::superchip(), 30, "quirks-schip", "xxxxvv");
+}
+
+#[test]
+fn quirks_octo() {
+    check("quirks", Quirks::octo(), 30, "quirks-octo", "xvvvxv");
+}
@@ -0,0 +1,180 @@
+use chip8::asm::assemble;
+use chip8::keyboard::{KeyState, NUM_KEYS};
+use chip8::trace::{self, TraceFormat, TraceRecord, Tracer};
+use chip8::{Chip8, Quirks};
+use std::cell::RefCell;
+use std::io::{self, ErrorKind, Write};
+use std::ops::Range;
+use std::rc::Rc;
+
+const NO_KEYS: [KeyState; NUM_KEYS] = [KeyState::Up; NUM_KEYS];
+
+const PROGRAM: &str = "
+        LD V0, 3
+        LD DT, V0
+loop:   ADD V1, 0x11
+        LD I, 0x300
+        SE V1, 0x55
+        JP loop
+end:    JP end
+";
+
+/// A writer read back by the test while the tracer holds a clone of it.
+#[derive(Clone, Default)]
+struct Shared(Rc<RefCell<Vec<u8>>>);
+
+impl Write for Shared {
+    fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().write(buffer)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Traces `cycles` instructions of the program, returning what the tracer wrote.
+fn trace(format: TraceFormat, range: Option<Range<usize>>, cycles: usize) -> Vec<u8> {
+    let output = Shared::default();
+    let mut tracer = Tracer::new(output.clone(), format);
+    if let Some(range) = range {
+        tracer.set_range(range);
+    }
+    let tracer = Rc::new(RefCell::new(tracer));
+    let mut computer = Chip8::new(&assemble(PROGRAM).unwrap(), Quirks::default(), 0).unwrap();
+    computer.set_tracer(Some(tracer.clone()));
+    for _ in 0..cycles {
+        computer.iteration(&NO_KEYS).unwrap();
+    }
+    tracer.borrow_mut().finish().unwrap();
+    let bytes = output.0.borrow().clone();
+    bytes
+}
+
+fn parse_text(bytes: &[u8]) -> Vec<TraceRecord> {
+    String::from_utf8(bytes.to_vec())
+        .unwrap()
+        .lines()
+        .map(|line| TraceRecord::parse(line).unwrap_or_else(|| panic!("{}", line)))
+        .collect()
+}
+
+fn read_binary(mut bytes: &[u8]) -> Vec<TraceRecord> {
+    trace::read_header(&mut bytes).unwrap();
+    let mut records = Vec::new();
+    while let Some(record) = TraceRecord::read_binary(&mut bytes).unwrap() {
+        records.push(record);
+    }
+    records
+}
+
+fn record() -> TraceRecord {
+    let mut registers = [0; 16];
+    for (index, register) in registers.iter_mut().enumerate() {
+        *register = index as u8 * 0x11;
+    }
+    TraceRecord {
+        cycle: 1_234_567,
+        pc: 0x2A4,
+        opcode: 0xD125,
+        registers,
+        i: 0xFEDC,
+        delay_timer: 0x3C,
+        sound_timer: 0x05,
+    }
+}
+
+#[test]
+fn parses_the_text_it_writes() {
+    let record = record();
+    let line = record.to_string();
+    assert!(line.contains(" DRW V1, V2, 5 "), "{}", line);
+    assert_eq!(TraceRecord::parse(&line), Some(record));
+    // the mnemonic and the timers may be left out
+    let line = format!(
+        "7 02A4 D125 V {} I FEDC",
+        (0..16)
+            .map(|index| format!("{:02X}", index * 0x11))
+            .collect::<Vec<_>>()
+            .join(" ")
+    );
+    let expected = TraceRecord {
+        cycle: 7,
+        delay_timer: 0,
+        sound_timer: 0,
+        ..record
+    };
+    assert_eq!(TraceRecord::parse(&line), Some(expected));
+    assert_eq!(TraceRecord::parse("7 02A4 D125 V 00 I 0000"), None);
+    assert_eq!(TraceRecord::parse(&format!("{} XX 01", line)), None);
+}
+
+#[test]
+fn reads_the_binary_it_writes() {
+    let mut bytes = Vec::new();
+    trace::write_header(&mut bytes).unwrap();
+    let other = TraceRecord {
+        cycle: u64::MAX,
+        ..record()
+    };
+    record().write_binary(&mut bytes).unwrap();
+    other.write_binary(&mut bytes).unwrap();
+    assert_eq!(bytes.len(), 5 + 2 * 32);
+    assert_eq!(read_binary(&bytes), vec![record(), other]);
+
+    let error = TraceRecord::read_binary(&mut &bytes[5..36]).unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::UnexpectedEof);
+    let mut header = bytes.clone();
+    header[0] = b'X';
+    let error = trace::read_header(&mut header.as_slice()).unwrap_err();
+    assert_eq!(error.to_string(), "not a trace");
+    header = bytes.clone();
+    header[4] = trace::VERSION + 1;
+    let error = trace::read_header(&mut header.as_slice()).unwrap_err();
+    assert_eq!(error.to_string(), "unsupported trace version");
+}
+
+#[test]
+fn records_every_instruction_in_both_formats() {
+    let records = parse_text(&trace(TraceFormat::Text, None, 30));
+    assert_eq!(read_binary(&trace(TraceFormat::Binary, None, 30)), records);
+    assert_eq!(records.len(), 30);
+    for (cycle, record) in records.iter().enumerate() {
+        assert_eq!(record.cycle, cycle as u64);
+    }
+    let pcs: Vec<u16> = records.iter().take(8).map(|record| record.pc).collect();
+    assert_eq!(
+        pcs,
+        [0x200, 0x202, 0x204, 0x206, 0x208, 0x20A, 0x204, 0x206]
+    );
+    // registers are traced before the instruction runs
+    assert_eq!(
+        (
+            records[2].opcode,
+            records[2].registers[1],
+            records[3].registers[1]
+        ),
+        (0x7111, 0x00, 0x11)
+    );
+    assert_eq!((records[2].delay_timer, records[4].i), (3, 0x300));
+    // the loop runs five times before the program reaches its end
+    assert_eq!((records[20].pc, records[21].pc), (0x208, 0x20C));
+    assert_eq!(records[29].pc, 0x20C);
+}
+
+#[test]
+fn drops_records_outside_of_the_range() {
+    let all = parse_text(&trace(TraceFormat::Text, None, 20));
+    let expected: Vec<TraceRecord> = all
+        .iter()
+        .filter(|record| (0x204..0x208).contains(&record.pc))
+        .copied()
+        .collect();
+    let text = parse_text(&trace(TraceFormat::Text, Some(0x204..0x208), 20));
+    let binary = read_binary(&trace(TraceFormat::Binary, Some(0x204..0x208), 20));
+    assert_eq!(text, expected);
+    assert_eq!(binary, expected);
+    // the cycles still count the instructions left out
+    let cycles: Vec<u64> = text.iter().map(|record| record.cycle).collect();
+    assert_eq!(&cycles[..4], &[2, 3, 6, 7]);
+}
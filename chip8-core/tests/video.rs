@@ -0,0 +1,95 @@
+use chip8::display::Framebuffer;
+use chip8::image::DEFAULT_PALETTE;
+use chip8::video::{VideoFormat, VideoRecorder};
+use chip8::{DISPLAY_HEIGHT, DISPLAY_WIDTH, HIRES_DISPLAY_HEIGHT, HIRES_DISPLAY_WIDTH};
+
+/// Delays of the frames of a GIF, walking over its blocks.
+fn gif_delays(gif: &[u8]) -> Vec<u16> {
+    assert_eq!(&gif[..6], b"GIF89a");
+    // header, screen descriptor and the global table of 4 colors
+    let mut index = 13 + 4 * 3;
+    let skip_blocks = |index: &mut usize| {
+        while gif[*index] != 0 {
+            *index += gif[*index] as usize + 1;
+        }
+        *index += 1;
+    };
+    let mut delays = Vec::new();
+    loop {
+        match gif[index] {
+            0x21 => {
+                if gif[index + 1] == 0xF9 {
+                    delays.push(u16::from_le_bytes([gif[index + 4], gif[index + 5]]));
+                }
+                index += 2;
+                skip_blocks(&mut index);
+            }
+            0x2C => {
+                index += 11;
+                skip_blocks(&mut index);
+            }
+            0x3B => return delays,
+            byte => panic!("unexpected block {:#x}", byte),
+        }
+    }
+}
+
+#[test]
+fn merges_repeated_frames_in_gifs() {
+    let mut recorder =
+        VideoRecorder::new(Vec::new(), VideoFormat::Gif, 1, DEFAULT_PALETTE).unwrap();
+    let mut framebuffer = Framebuffer::new(DISPLAY_WIDTH, DISPLAY_HEIGHT);
+    for frame in 0..120 {
+        // a second still, then a pixel blinking every 3 frames
+        if frame >= 60 && frame % 3 == 0 {
+            framebuffer.set(0, 0, framebuffer.get(0, 0) ^ 1);
+        }
+        recorder.frame(&framebuffer).unwrap();
+    }
+    assert_eq!(recorder.len(), 120);
+    let gif = recorder.finish().unwrap();
+
+    assert_eq!(&gif[6..10], &[128, 0, 64, 0]);
+    let delays = gif_delays(&gif);
+    assert_eq!(delays.len(), 1 + 20);
+    assert_eq!(delays[0], 100);
+    assert_eq!(delays.iter().map(|&delay| delay as u32).sum::<u32>(), 200);
+}
+
+#[test]
+fn drops_frames_too_short_for_gifs() {
+    let mut recorder =
+        VideoRecorder::new(Vec::new(), VideoFormat::Gif, 1, DEFAULT_PALETTE).unwrap();
+    let mut framebuffer = Framebuffer::new(DISPLAY_WIDTH, DISPLAY_HEIGHT);
+    for frame in 0..60 {
+        framebuffer.set(frame, 0, 1);
+        recorder.frame(&framebuffer).unwrap();
+    }
+    let delays = gif_delays(&recorder.finish().unwrap());
+    assert!(delays.iter().all(|&delay| delay >= 2));
+    assert_eq!(delays.iter().map(|&delay| delay as u32).sum::<u32>(), 100);
+}
+
+#[test]
+fn writes_every_frame_in_y4m_videos() {
+    let mut recorder =
+        VideoRecorder::new(Vec::new(), VideoFormat::Y4m, 2, DEFAULT_PALETTE).unwrap();
+    let mut framebuffer = Framebuffer::new(HIRES_DISPLAY_WIDTH, HIRES_DISPLAY_HEIGHT);
+    framebuffer.set(0, 0, 1);
+    for _ in 0..3 {
+        recorder.frame(&framebuffer).unwrap();
+    }
+    let video = recorder.finish().unwrap();
+
+    let header = b"YUV4MPEG2 W256 H128 F60:1 Ip A1:1 C444\n";
+    assert_eq!(&video[..header.len()], header);
+    let frame_size = b"FRAME\n".len() + 256 * 128 * 3;
+    assert_eq!(video.len(), header.len() + 3 * frame_size);
+    let frame = &video[header.len()..header.len() + frame_size];
+    assert_eq!(&frame[..6], b"FRAME\n");
+    // the lit pixel is white in the luma plane, the others black
+    let luma = &frame[6..6 + 256 * 128];
+    assert_eq!(&luma[..3], &[235, 235, 16]);
+    assert_eq!(luma[256], 235);
+    assert_eq!(luma[2 * 256], 16);
+}
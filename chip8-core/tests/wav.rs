@@ -0,0 +1,124 @@
+use chip8::asm::assemble;
+use chip8::audio::{Tone, Waveform};
+use chip8::keyboard::{KeyState, NUM_KEYS};
+use chip8::wav::AudioRecorder;
+use chip8::{Chip8, Quirks};
+
+const SAMPLES_PER_FRAME: usize = 44100 / 60;
+const BEEP: &str = "
+        LD V0, 10
+        LD ST, V0
+end:    JP end
+";
+
+/// A tone starting and stopping at once, whose samples follow the sound timer exactly.
+fn sharp_tone() -> Tone {
+    Tone {
+        attack: 0.0,
+        release: 0.0,
+        ..Tone::default()
+    }
+}
+
+fn render(source: &str, frames: usize, tone: Tone) -> AudioRecorder {
+    let program = assemble(source).unwrap();
+    let mut computer = Chip8::new(&program, Quirks::octo(), 0).unwrap();
+    let mut audio = AudioRecorder::new(44100, tone, &computer);
+    for _ in 0..frames {
+        computer.run_frame(&[KeyState::Up; NUM_KEYS]).unwrap();
+        audio.frame(&computer.take_audio_events());
+    }
+    audio
+}
+
+/// Frames during which the buzzer sounds.
+fn sounding_frames(audio: &AudioRecorder) -> Vec<usize> {
+    audio
+        .samples()
+        .chunks(SAMPLES_PER_FRAME)
+        .enumerate()
+        .filter(|(_, samples)| samples.iter().any(|&sample| sample != 0))
+        .map(|(frame, _)| frame)
+        .collect()
+}
+
+#[test]
+fn renders_the_sound_timer() {
+    let audio = render(BEEP, 30, sharp_tone());
+    assert_eq!(audio.len(), 30);
+    assert_eq!(audio.samples().len(), 30 * SAMPLES_PER_FRAME);
+    assert_eq!(sounding_frames(&audio), (0..10).collect::<Vec<_>>());
+
+    let mut wav = Vec::new();
+    audio.write(&mut wav).unwrap();
+    assert_eq!(&wav[..4], b"RIFF");
+    assert_eq!(&wav[8..16], b"WAVEfmt ");
+    assert_eq!(&wav[24..28], &44100u32.to_le_bytes());
+    assert_eq!(&wav[36..40], b"data");
+    assert_eq!(wav.len(), 44 + 30 * SAMPLES_PER_FRAME * 2);
+}
+
+#[test]
+fn renders_xo_chip_patterns() {
+    // a pattern of set bits holds the speaker up, where the square wave would swing
+    let audio = render(
+        "
+        LD I, pattern
+        AUDIO
+        LD V0, 4
+        LD ST, V0
+end:    JP end
+pattern:
+        db 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF
+        db 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF
+        ",
+        10,
+        sharp_tone(),
+    );
+    assert_eq!(sounding_frames(&audio), vec![0, 1, 2, 3]);
+    let beep = &audio.samples()[..4 * SAMPLES_PER_FRAME];
+    assert!(beep.iter().all(|&sample| sample > 0));
+}
+
+#[test]
+fn ramps_the_volume_up_and_down() {
+    let audio = render(BEEP, 30, Tone::default());
+    // the release carries over into the frame after the beep
+    assert_eq!(sounding_frames(&audio), (0..11).collect::<Vec<_>>());
+    let peak = (0.25 * i16::MAX as f32) as i16;
+    let samples = audio.samples();
+    assert!(samples[0].abs() < peak / 100);
+    assert!(samples[..SAMPLES_PER_FRAME].contains(&peak));
+    let end = 10 * SAMPLES_PER_FRAME;
+    assert!(samples[end..end + 100]
+        .iter()
+        .all(|sample| sample.abs() < peak));
+}
+
+#[test]
+fn renders_every_waveform() {
+    for &waveform in &[
+        Waveform::Square,
+        Waveform::Triangle,
+        Waveform::Sine,
+        Waveform::Noise,
+    ] {
+        let tone = Tone {
+            waveform,
+            volume: 1.0,
+            ..sharp_tone()
+        };
+        let audio = render(BEEP, 10, tone);
+        let beep = &audio.samples()[..9 * SAMPLES_PER_FRAME];
+        let mut levels = beep.to_vec();
+        levels.sort_unstable();
+        levels.dedup();
+        if waveform == Waveform::Square {
+            assert_eq!(levels, vec![-i16::MAX, i16::MAX]);
+        } else {
+            assert!(levels.len() > 2, "{:?}", waveform);
+        }
+        assert!(beep.iter().any(|&sample| sample > i16::MAX / 2));
+        assert!(beep.iter().any(|&sample| sample < -i16::MAX / 2));
+    }
+}
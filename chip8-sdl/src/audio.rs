@@ -1,30 +1,67 @@
 pub use chip8::audio::Audio;
+use chip8::audio::{AudioEvent, Synth, Tone};
+use chip8::FRAME_RATE;
 use sdl2::audio::{AudioCallback, AudioDevice, AudioSpecDesired};
+use std::collections::VecDeque;
 
-struct SquareWave {
-    phase_inc: f32,
-    phase: f32,
-    volume: f32,
+// samples filled at each callback, about 23 ms
+const BUFFER_SAMPLES: u16 = 1024;
+// events are played this many frames after the buffer they come in for, which absorbs
+// the jitter of the main loop
+const LATENCY_FRAMES: f64 = 2.0;
+// beyond this many frames early or late, the events are played from now on
+const MAX_DRIFT_FRAMES: f64 = 15.0;
+
+struct Buzzer {
+    synth: Synth,
+    events: VecDeque<AudioEvent>,
+    samples_per_frame: f64,
+    // samples between the arrival of an event and the time it is played
+    latency: u64,
+    // samples played so far
+    position: u64,
+    // the frame of the first event since the buzzer stopped, and the sample it was played at
+    anchor: Option<(u64, u64)>,
+}
+
+impl Buzzer {
+    /// The sample at which the frame of an event starts, `now` being the next sample played.
+    fn sample_of(&mut self, frame: u64, now: u64) -> u64 {
+        let start = now + self.latency;
+        let (anchor_frame, anchor_sample) = *self.anchor.get_or_insert((frame, start));
+        let offset = (frame as f64 - anchor_frame as f64) * self.samples_per_frame;
+        let sample = anchor_sample as f64 + offset;
+        let drift = (sample - start as f64) / self.samples_per_frame;
+        // the emulation went back in time, or drifted too far from the audio clock
+        if frame < anchor_frame || drift.abs() > MAX_DRIFT_FRAMES {
+            self.anchor = Some((frame, start));
+            return start;
+        }
+        sample as u64
+    }
 }
 
-impl AudioCallback for SquareWave {
+impl AudioCallback for Buzzer {
     type Channel = f32;
 
     fn callback(&mut self, out: &mut [f32]) {
-        // Generate a square wave
-        for x in out.iter_mut() {
-            *x = if self.phase <= 0.5 {
-                self.volume
-            } else {
-                -self.volume
-            };
-            self.phase = (self.phase + self.phase_inc) % 1.0;
+        for (index, x) in out.iter_mut().enumerate() {
+            let now = self.position + index as u64;
+            while let Some(&event) = self.events.front() {
+                if self.sample_of(event.frame, now) > now {
+                    break;
+                }
+                self.synth.set_sound(event.sound);
+                self.events.pop_front();
+            }
+            *x = self.synth.sample();
         }
+        self.position += out.len() as u64;
     }
 }
 
 pub struct SdlAudio {
-    device: AudioDevice<SquareWave>,
+    device: AudioDevice<Buzzer>,
 }
 
 impl SdlAudio {
@@ -34,30 +71,45 @@ impl SdlAudio {
         let desired_spec = AudioSpecDesired {
             freq: Some(44100),
             channels: Some(1), // mono
-            samples: None,     // default sample size
+            samples: Some(BUFFER_SAMPLES),
         };
 
         let device = audio_subsystem
             .open_playback(None, &desired_spec, |spec| {
                 // initialize the audio callback
-                SquareWave {
-                    phase_inc: 440.0 / spec.freq as f32,
-                    phase: 0.0,
-                    volume: 0.25,
+                let samples_per_frame = spec.freq as f64 / FRAME_RATE as f64;
+                Buzzer {
+                    synth: Synth::new(spec.freq as u32, Tone::default()),
+                    events: VecDeque::new(),
+                    samples_per_frame,
+                    latency: spec.samples as u64 + (LATENCY_FRAMES * samples_per_frame) as u64,
+                    position: 0,
+                    anchor: None,
                 }
             })
             .unwrap();
+        // the device keeps running so that stopping the buzzer fades it out
+        device.resume();
 
         Self { device }
     }
 }
 
 impl Audio for SdlAudio {
-    fn play(&self) {
-        self.device.resume();
+    fn set_tone(&mut self, tone: Tone) {
+        self.device.lock().synth.set_tone(tone);
+    }
+
+    fn queue(&mut self, events: &[AudioEvent]) {
+        if !events.is_empty() {
+            self.device.lock().events.extend(events);
+        }
     }
 
-    fn stop(&self) {
-        self.device.pause();
+    fn stop(&mut self) {
+        let mut buzzer = self.device.lock();
+        buzzer.events.clear();
+        buzzer.anchor = None;
+        buzzer.synth.set_sound(None);
     }
 }
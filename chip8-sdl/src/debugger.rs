@@ -0,0 +1,137 @@
+use crate::keyboard::Hotkey;
+use chip8::debugger::{Debugger, Stop};
+use chip8::instruction::decode;
+use chip8::Chip8;
+
+// instructions listed before and after the cursor
+const DISASSEMBLY_BEFORE: usize = 6;
+const DISASSEMBLY_AFTER: usize = 10;
+
+/// The debugger along with the cursor used to pick addresses in the disassembly.
+pub struct DebuggerView {
+    pub debugger: Debugger,
+    cursor: usize,
+    // why the debugger last paused the machine, shown in the panel
+    stop: Option<Stop>,
+}
+
+impl DebuggerView {
+    pub fn new(debugger: Debugger, computer: &Chip8) -> Self {
+        DebuggerView {
+            debugger,
+            cursor: computer.state.pc,
+            stop: None,
+        }
+    }
+
+    /// Applies a debugger hotkey, other hotkeys being ignored.
+    pub fn handle(&mut self, hotkey: Hotkey, computer: &Chip8) {
+        match hotkey {
+            Hotkey::Pause if self.debugger.is_paused() => self.debugger.resume(),
+            Hotkey::Pause => {
+                self.debugger.pause();
+                self.cursor = computer.state.pc;
+                self.stop = None;
+            }
+            Hotkey::Step => self.debugger.step(),
+            Hotkey::StepOver => self.debugger.step_over(computer),
+            Hotkey::RunToCursor => self.debugger.run_to(self.cursor),
+            Hotkey::ToggleBreakpoint => {
+                self.debugger.toggle_breakpoint(self.cursor);
+            }
+            Hotkey::CursorUp => self.cursor = self.cursor.saturating_sub(2),
+            Hotkey::CursorDown => {
+                self.cursor = (self.cursor + 2).min(computer.state.ram.len() - 2);
+            }
+            _ => {}
+        }
+    }
+
+    /// Called when the debugger paused the machine, to bring the cursor back to the program counter.
+    pub fn stopped(&mut self, stop: Stop, computer: &Chip8) {
+        self.cursor = computer.state.pc;
+        self.stop = Some(stop).filter(|&stop| stop != Stop::Step);
+    }
+
+    /// Called when the machine was replaced by a loaded state or a rewound frame.
+    pub fn replaced(&mut self, computer: &Chip8) {
+        self.debugger.reset();
+        self.cursor = computer.state.pc;
+        self.stop = None;
+    }
+
+    /// Lines of the overlay panel, along with the index of the line under the cursor.
+    pub fn overlay(&self, computer: &Chip8) -> (Vec<String>, Option<usize>) {
+        let state = &computer.state;
+        let mut lines = Vec::new();
+        lines.push(match self.stop {
+            _ if !self.debugger.is_paused() => "RUNNING".to_string(),
+            Some(stop) => format!("PAUSED, {}", stop),
+            None => "PAUSED".to_string(),
+        });
+        lines.push(format!("PC {:04X}  I {:04X}", state.pc, state.i));
+        lines.push(format!(
+            "DT {:02X}  ST {:02X}",
+            state.delay_timer, state.sound_timer
+        ));
+        for (row, registers) in state.registers.chunks(4).enumerate() {
+            let registers: Vec<String> = registers
+                .iter()
+                .enumerate()
+                .map(|(index, value)| format!("V{:X} {:02X}", row * 4 + index, value))
+                .collect();
+            lines.push(registers.join("  "));
+        }
+        let stack: Vec<String> = state
+            .stack
+            .as_slice()
+            .iter()
+            .map(|address| format!("{:03X}", address))
+            .collect();
+        lines.push(format!("STACK {}", stack.join(" ")));
+        lines.push(String::new());
+
+        // the cursor keeps the parity of the program counter, so instructions stay aligned
+        let mut address = self.cursor.saturating_sub(2 * DISASSEMBLY_BEFORE);
+        let mut highlight = None;
+        for _ in 0..DISASSEMBLY_BEFORE + 1 + DISASSEMBLY_AFTER {
+            let opcode = match state.ram.peek_u16(address) {
+                Ok(opcode) => opcode,
+                Err(_) => break,
+            };
+            if address == self.cursor {
+                highlight = Some(lines.len());
+            }
+            let breakpoint = if self.debugger.breakpoints().contains(&address) {
+                '*'
+            } else {
+                ' '
+            };
+            let current = if address == state.pc { '>' } else { ' ' };
+            let mnemonic = match decode(opcode) {
+                Ok(instruction) => instruction.to_string(),
+                Err(_) => format!("DW 0x{:04X}", opcode),
+            };
+            lines.push(format!(
+                "{}{} {:04X}  {:04X}  {}",
+                breakpoint, current, address, opcode, mnemonic
+            ));
+            address += 2;
+        }
+
+        if !self.debugger.write_breakpoints().is_empty() {
+            lines.push(String::new());
+            let watched: Vec<String> = self
+                .debugger
+                .write_breakpoints()
+                .iter()
+                .map(|address| format!("{:03X}", address))
+                .collect();
+            lines.push(format!("WATCH {}", watched.join(" ")));
+        }
+        lines.push(String::new());
+        lines.push("F5 run/pause  F6 step  F7 step over".to_string());
+        lines.push("F8 run to cursor  F9 breakpoint".to_string());
+        (lines, highlight)
+    }
+}
@@ -1,7 +1,7 @@
-
 pub use chip8::display::Display;
-use chip8::display::{DISPLAY_WIDTH, DISPLAY_HEIGHT};
-use sdl2::gfx::framerate::FPSManager;
+use chip8::display::{Framebuffer, DISPLAY_HEIGHT, DISPLAY_WIDTH};
+use chip8::image::{self, Palette};
+use sdl2::gfx::primitives::DrawRenderer;
 use sdl2::pixels::Color;
 use sdl2::rect::Rect;
 use sdl2::render::Canvas;
@@ -11,17 +11,21 @@ const SCALE_FACTOR: u32 = 20;
 const SCREEN_WIDTH: u32 = DISPLAY_WIDTH as u32 * SCALE_FACTOR;
 const SCREEN_HEIGHT: u32 = DISPLAY_HEIGHT as u32 * SCALE_FACTOR;
 
-const WHITE: Color = Color::RGB(255, 255, 255);
-const BLACK: Color = Color::RGB(0, 0, 0);
-const FRAME_RATE: u32 = 60;
+// the debugger panel covers the right side of the window, its font being 8x8 pixels
+const OVERLAY_WIDTH: u32 = 320;
+const OVERLAY_MARGIN: u32 = 8;
+const OVERLAY_LINE_HEIGHT: u32 = 12;
+const OVERLAY_BACKGROUND: Color = Color::RGBA(0, 0, 64, 208);
+const OVERLAY_TEXT: Color = Color::RGB(255, 255, 255);
+const OVERLAY_HIGHLIGHT: Color = Color::RGB(255, 255, 0);
 
 pub struct SdlDisplay {
     canvas: Canvas<Window>,
-    fps_manager: FPSManager,
+    palette: Palette,
 }
 
 impl SdlDisplay {
-    pub fn new(sdl_context: &sdl2::Sdl) -> Self {
+    pub fn new(sdl_context: &sdl2::Sdl, palette: Palette) -> Self {
         let video_subsystem = sdl_context.video().unwrap();
         let window = video_subsystem
             .window("Chip8-rs", SCREEN_WIDTH, SCREEN_HEIGHT)
@@ -30,38 +34,77 @@ impl SdlDisplay {
             .build()
             .unwrap();
 
-        let mut fps_manager = FPSManager::new();
-        fps_manager.set_framerate(FRAME_RATE).unwrap();
-
         let mut canvas = window.into_canvas().build().unwrap();
         canvas.set_draw_color(Color::RGB(0, 0, 0));
         canvas.clear();
         canvas.present();
 
-        SdlDisplay {
-            canvas,
-            fps_manager,
+        SdlDisplay { canvas, palette }
+    }
+
+    /// Draws the framebuffer with a panel of text on top of it, `highlight` being drawn in another color.
+    pub fn draw_overlay(
+        &mut self,
+        framebuffer: &Framebuffer,
+        lines: &[String],
+        highlight: Option<usize>,
+    ) {
+        self.render(framebuffer);
+        let left = (SCREEN_WIDTH - OVERLAY_WIDTH) as i16;
+        self.canvas
+            .box_(
+                left,
+                0,
+                SCREEN_WIDTH as i16 - 1,
+                SCREEN_HEIGHT as i16 - 1,
+                OVERLAY_BACKGROUND,
+            )
+            .unwrap();
+        for (index, line) in lines.iter().enumerate() {
+            let color = if highlight == Some(index) {
+                OVERLAY_HIGHLIGHT
+            } else {
+                OVERLAY_TEXT
+            };
+            let y = OVERLAY_MARGIN + index as u32 * OVERLAY_LINE_HEIGHT;
+            self.canvas
+                .string(left + OVERLAY_MARGIN as i16, y as i16, line, color)
+                .unwrap();
         }
+        self.canvas.present();
     }
-}
 
-impl Display for SdlDisplay {
-    fn draw(&mut self, pixels: &[[u8; DISPLAY_WIDTH]; DISPLAY_HEIGHT]) {
-        for y in 0..pixels.len() {
-            for x in 0..pixels[0].len() {
-                if pixels[y][x] == 1 {
-                    self.canvas.set_draw_color(WHITE);
-                } else {
-                    self.canvas.set_draw_color(BLACK);
-                }
-                let x = x as i32 * SCALE_FACTOR as i32;
-                let y = y as i32 * SCALE_FACTOR as i32;
+    /// Saves the framebuffer to a PNG or PPM image in the colors of the window,
+    /// each pixel being drawn as a `scale` x `scale` square.
+    pub fn screenshot(
+        &self,
+        framebuffer: &Framebuffer,
+        path: &str,
+        scale: usize,
+    ) -> std::io::Result<()> {
+        image::save(path, framebuffer, scale, &self.palette)
+    }
+
+    fn render(&mut self, framebuffer: &Framebuffer) {
+        // high resolution framebuffers are drawn with smaller pixels to fill the same window
+        let pixel_size = SCREEN_WIDTH / framebuffer.width() as u32;
+        for (y, row) in framebuffer.rows().enumerate() {
+            for (x, &pixel) in row.iter().enumerate() {
+                let [r, g, b] = self.palette[pixel as usize];
+                self.canvas.set_draw_color(Color::RGB(r, g, b));
+                let x = x as i32 * pixel_size as i32;
+                let y = y as i32 * pixel_size as i32;
                 self.canvas
-                    .fill_rect(Rect::new(x, y, SCALE_FACTOR, SCALE_FACTOR))
+                    .fill_rect(Rect::new(x, y, pixel_size, pixel_size))
                     .unwrap();
             }
         }
+    }
+}
+
+impl Display for SdlDisplay {
+    fn draw(&mut self, framebuffer: &Framebuffer) {
+        self.render(framebuffer);
         self.canvas.present();
-        self.fps_manager.delay();
     }
 }
@@ -1,69 +1,142 @@
 use sdl2;
 use sdl2::event::Event;
 use sdl2::keyboard::Keycode;
+use sdl2::keyboard::{Mod, Scancode};
 use chip8::keyboard::KeyState;
 use chip8::keyboard::NUM_KEYS;
+use crate::keymap::KeyMap;
 
 #[derive(Clone, Copy, Debug)]
 pub enum PollError {
     Quit,
 }
 
+/// Emulator commands bound to keys outside of the CHIP-8 keypad.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Hotkey {
+    /// F1 to F4 save the machine to the corresponding slot.
+    SaveState(usize),
+    /// Shift + F1 to F4 restore the machine from the corresponding slot.
+    LoadState(usize),
+    /// F5 pauses or resumes the debugger.
+    Pause,
+    /// F6 executes a single instruction.
+    Step,
+    /// F7 executes a single instruction, or a whole subroutine call.
+    StepOver,
+    /// F8 runs until the debugger cursor is reached.
+    RunToCursor,
+    /// F9 sets or removes a breakpoint at the debugger cursor.
+    ToggleBreakpoint,
+    /// Up and Down move the debugger cursor.
+    CursorUp,
+    CursorDown,
+    /// F11 starts or stops capturing a video.
+    Capture,
+    /// F12 saves the screen to an image.
+    Screenshot,
+}
+
+impl Hotkey {
+    /// Whether the hotkey controls the debugger, doing nothing when the debugger is disabled.
+    pub fn is_debugger(self) -> bool {
+        matches!(
+            self,
+            Hotkey::Pause
+                | Hotkey::Step
+                | Hotkey::StepOver
+                | Hotkey::RunToCursor
+                | Hotkey::ToggleBreakpoint
+                | Hotkey::CursorUp
+                | Hotkey::CursorDown
+        )
+    }
+}
+
+pub struct Input {
+    pub keys: [KeyState; NUM_KEYS],
+    pub hotkeys: Vec<Hotkey>,
+    /// Backspace is held to rewind the game.
+    pub rewind: bool,
+}
+
 pub trait Keyboard {
-    fn poll(&mut self) -> Result<[KeyState; NUM_KEYS], PollError>;
+    fn poll(&mut self) -> Result<Input, PollError>;
 }
 
 pub struct SdlKeyboard {
     event_pump: sdl2::EventPump,
+    keymap: KeyMap,
 }
 
 impl SdlKeyboard {
-    pub fn new(sdl_context: &sdl2::Sdl) -> Self {
+    pub fn new(sdl_context: &sdl2::Sdl, keymap: KeyMap) -> Self {
         SdlKeyboard {
             event_pump: sdl_context.event_pump().unwrap(),
+            keymap,
         }
     }
 }
 
 impl Keyboard for SdlKeyboard {
-    fn poll(&mut self) -> Result<[KeyState; NUM_KEYS], PollError> {
+    fn poll(&mut self) -> Result<Input, PollError> {
+        let mut hotkeys = Vec::new();
         for event in self.event_pump.poll_iter() {
-            if let Event::Quit { .. } = event {
-                return Err(PollError::Quit);
+            match event {
+                Event::Quit { .. } => return Err(PollError::Quit),
+                Event::KeyDown {
+                    keycode: Some(keycode),
+                    keymod,
+                    repeat: false,
+                    ..
+                } => hotkeys.extend(translate_hotkey(keycode, keymod)),
+                _ => {}
             }
         }
 
         let mut keys = [KeyState::Up; NUM_KEYS];
+        let rewind = self
+            .event_pump
+            .keyboard_state()
+            .is_scancode_pressed(Scancode::Backspace);
 
         self.event_pump
             .keyboard_state()
             .pressed_scancodes()
-            .filter_map(Keycode::from_scancode)
-            .filter_map(translate_key)
+            .filter_map(|scancode| self.keymap.key(scancode))
             .for_each(|key| keys[key] = KeyState::Down);
 
-        Ok(keys)
+        Ok(Input {
+            keys,
+            hotkeys,
+            rewind,
+        })
     }
 }
 
-fn translate_key(keycode: Keycode) -> Option<usize> {
+pub(crate) fn translate_hotkey(keycode: Keycode, keymod: Mod) -> Option<Hotkey> {
     match keycode {
-        Keycode::X => Some(0x0),
-        Keycode::Num1 => Some(0x1),
-        Keycode::Num2 => Some(0x2),
-        Keycode::Num3 => Some(0x3),
-        Keycode::Q => Some(0x4),
-        Keycode::W => Some(0x5),
-        Keycode::E => Some(0x6),
-        Keycode::A => Some(0x7),
-        Keycode::S => Some(0x8),
-        Keycode::D => Some(0x9),
-        Keycode::Z => Some(0xa),
-        Keycode::C => Some(0xb),
-        Keycode::Num4 => Some(0xc),
-        Keycode::R => Some(0xd),
-        Keycode::F => Some(0xe),
-        Keycode::V => Some(0xf),
-        _ => None,
+        Keycode::F5 => return Some(Hotkey::Pause),
+        Keycode::F6 => return Some(Hotkey::Step),
+        Keycode::F7 => return Some(Hotkey::StepOver),
+        Keycode::F8 => return Some(Hotkey::RunToCursor),
+        Keycode::F9 => return Some(Hotkey::ToggleBreakpoint),
+        Keycode::F11 => return Some(Hotkey::Capture),
+        Keycode::F12 => return Some(Hotkey::Screenshot),
+        Keycode::Up => return Some(Hotkey::CursorUp),
+        Keycode::Down => return Some(Hotkey::CursorDown),
+        _ => {}
     }
-}
\ No newline at end of file
+    let slot = match keycode {
+        Keycode::F1 => 1,
+        Keycode::F2 => 2,
+        Keycode::F3 => 3,
+        Keycode::F4 => 4,
+        _ => return None,
+    };
+    if keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD) {
+        Some(Hotkey::LoadState(slot))
+    } else {
+        Some(Hotkey::SaveState(slot))
+    }
+}
@@ -0,0 +1,277 @@
+use crate::keyboard::translate_hotkey;
+use chip8::keyboard::NUM_KEYS;
+use sdl2::keyboard::{Keycode, Mod, Scancode};
+use std::collections::HashMap;
+use std::fmt;
+
+// CHIP-8 keys in the order of the keypad: 1 2 3 C / 4 5 6 D / 7 8 9 E / A 0 B F
+const KEYPAD: [usize; NUM_KEYS] = [
+    0x1, 0x2, 0x3, 0xC, 0x4, 0x5, 0x6, 0xD, 0x7, 0x8, 0x9, 0xE, 0xA, 0x0, 0xB, 0xF,
+];
+
+// the 4x4 keys on the left of the keyboard, in the order of the keypad
+const LAYOUTS: &[(&str, &str)] = &[
+    ("qwerty", "1 2 3 4 Q W E R A S D F Z X C V"),
+    // `é` has no keycode, so its key is bound by position
+    ("azerty", "& scancode:2 \" ' A Z E R Q S D F W X C V"),
+    ("dvorak", "1 2 3 4 ' , . P A O E U ; Q J K"),
+];
+
+const DEFAULT_SECTION: &str = "keys";
+
+/// A key of the keyboard, bound by the character it types or by its position.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Binding {
+    Key(Keycode),
+    /// Keys which have no keycode, such as `é` on AZERTY keyboards.
+    Scancode(Scancode),
+}
+
+impl Binding {
+    /// Parses an SDL key name, e.g. `Q` or `Keypad 1`, or a position as `scancode:name`.
+    pub fn parse(name: &str) -> Option<Self> {
+        if let Some(name) = name.strip_prefix("scancode:") {
+            return Scancode::from_name(name).map(Binding::Scancode);
+        }
+        let mut chars = name.chars();
+        match (chars.next(), chars.next()) {
+            // a character is its own keycode, which `Keycode::from_name` unwraps even when SDL
+            // has no such key, e.g. `é`
+            (Some(character), None) => {
+                Keycode::from_i32(character.to_ascii_lowercase() as i32).map(Binding::Key)
+            }
+            _ => Keycode::from_name(name).map(Binding::Key),
+        }
+    }
+
+    /// The character typed by the key, which depends on the layout for positions.
+    fn keycode(self) -> Option<Keycode> {
+        match self {
+            Binding::Key(keycode) => Some(keycode),
+            Binding::Scancode(scancode) => Keycode::from_scancode(scancode),
+        }
+    }
+
+    /// Whether the key triggers a hotkey, those of the debugger only counting when it is enabled.
+    fn is_hotkey(self, debugger: bool) -> bool {
+        let keycode = match self.keycode() {
+            Some(keycode) => keycode,
+            None => return false,
+        };
+        keycode == Keycode::Backspace
+            || matches!(translate_hotkey(keycode, Mod::empty()),
+                Some(hotkey) if debugger || !hotkey.is_debugger())
+    }
+}
+
+impl fmt::Display for Binding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Binding::Key(keycode) => write!(f, "{}", keycode.name()),
+            Binding::Scancode(scancode) => write!(f, "scancode:{}", scancode.name()),
+        }
+    }
+}
+
+/// A line of a key map file which cannot be used.
+#[derive(Clone, PartialEq, Debug)]
+pub struct KeyMapError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for KeyMapError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for KeyMapError {}
+
+/// The keys of the keyboard held down for each of the 16 CHIP-8 keys.
+#[derive(Clone, Debug)]
+pub struct KeyMap {
+    bindings: [Vec<Binding>; NUM_KEYS],
+    // the line which bound each CHIP-8 key, 0 for a layout
+    lines: [usize; NUM_KEYS],
+}
+
+impl Default for KeyMap {
+    fn default() -> Self {
+        KeyMap::layout("qwerty").unwrap()
+    }
+}
+
+impl KeyMap {
+    /// One of the `qwerty`, `azerty` and `dvorak` layouts.
+    pub fn layout(name: &str) -> Option<Self> {
+        let (_, names) = LAYOUTS.iter().find(|(layout, _)| *layout == name)?;
+        let mut map = KeyMap {
+            bindings: Default::default(),
+            lines: [0; NUM_KEYS],
+        };
+        for (&key, name) in KEYPAD.iter().zip(names.split_whitespace()) {
+            map.bindings[key] = vec![Binding::parse(name)?];
+        }
+        Some(map)
+    }
+
+    /// Loads a key map in the INI format, where the `[keys]` section applies to every ROM
+    /// and a section named after the file of a ROM applies on top of it for that ROM, e.g.
+    ///
+    /// ```ini
+    /// [keys]
+    /// layout = azerty
+    /// 5 = Z, Keypad 5
+    ///
+    /// [pong.ch8]
+    /// 1 = Keypad 7
+    /// ```
+    ///
+    /// A section first sets the layout, then binds the CHIP-8 keys named by hexadecimal digits
+    /// to comma separated SDL key names, replacing their bindings.
+    /// Every error is reported, including keys bound twice or bound to hotkeys.
+    /// The keys of the debugger are only reserved when `debugger` is enabled.
+    pub fn load(source: &str, rom_name: &str, debugger: bool) -> Result<Self, Vec<KeyMapError>> {
+        let mut errors = Vec::new();
+        let mut entries = Vec::new();
+        let mut section = None;
+        for (index, line) in source.lines().enumerate() {
+            let line_number = index + 1;
+            let mut error = |message: String| {
+                errors.push(KeyMapError {
+                    line: line_number,
+                    message,
+                })
+            };
+            let line = line.trim();
+            if line.is_empty() || line.starts_with(';') || line.starts_with('#') {
+                continue;
+            }
+            if let Some(name) = line.strip_prefix('[') {
+                match name.strip_suffix(']') {
+                    Some(name) => section = Some(name.trim().to_string()),
+                    None => error(format!("invalid section {}", line)),
+                }
+                continue;
+            }
+            let (name, value) = match (line.split_once('='), &section) {
+                (Some((name, value)), Some(_)) => (name.trim(), value.trim()),
+                (Some(_), None) => {
+                    error("binding outside of a section".to_string());
+                    continue;
+                }
+                (None, _) => {
+                    error(format!("expected key = value, found {}", line));
+                    continue;
+                }
+            };
+            let entry = if name == "layout" {
+                match KeyMap::layout(value) {
+                    Some(_) => Entry::Layout(value.to_string()),
+                    None => {
+                        error(format!("unknown layout {}", value));
+                        continue;
+                    }
+                }
+            } else {
+                let key = match u8::from_str_radix(name, 16) {
+                    Ok(key) if name.len() == 1 => key as usize,
+                    _ => {
+                        error(format!("{} is not a CHIP-8 key", name));
+                        continue;
+                    }
+                };
+                let mut bindings = Vec::new();
+                for name in value
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|name| !name.is_empty())
+                {
+                    match Binding::parse(name) {
+                        Some(binding) if !bindings.contains(&binding) => bindings.push(binding),
+                        Some(_) => {}
+                        None => error(format!("unknown key {}", name)),
+                    }
+                }
+                Entry::Key(key, bindings)
+            };
+            entries.push((section.clone().unwrap(), line_number, entry));
+        }
+
+        let mut map = KeyMap::default();
+        for applied in &[DEFAULT_SECTION, rom_name] {
+            let section = entries.iter().filter(|(name, ..)| name == applied);
+            // the layout goes first, so that it does not replace the keys bound next to it
+            let (layouts, keys): (Vec<_>, Vec<_>) =
+                section.partition(|(.., entry)| matches!(entry, Entry::Layout(_)));
+            for (_, line, entry) in layouts.into_iter().chain(keys) {
+                match entry {
+                    Entry::Layout(layout) => {
+                        map = KeyMap::layout(layout).unwrap();
+                        map.lines = [*line; NUM_KEYS];
+                    }
+                    Entry::Key(key, bindings) => {
+                        map.bindings[*key] = bindings.clone();
+                        map.lines[*key] = *line;
+                    }
+                }
+            }
+        }
+        errors.extend(map.conflicts(debugger));
+        if errors.is_empty() {
+            Ok(map)
+        } else {
+            errors.sort_by_key(|error| error.line);
+            Err(errors)
+        }
+    }
+
+    pub fn bindings(&self, key: usize) -> &[Binding] {
+        &self.bindings[key]
+    }
+
+    /// The CHIP-8 key a key of the keyboard is bound to, by position or by keycode.
+    pub fn key(&self, scancode: Scancode) -> Option<usize> {
+        let keycode = Keycode::from_scancode(scancode).map(Binding::Key);
+        (0..NUM_KEYS).find(|&key| {
+            self.bindings[key]
+                .iter()
+                .any(|&binding| binding == Binding::Scancode(scancode) || Some(binding) == keycode)
+        })
+    }
+
+    /// Keys bound to several CHIP-8 keys, by character or by position, or bound to hotkeys,
+    /// reported on the line which bound them last.
+    fn conflicts(&self, debugger: bool) -> Vec<KeyMapError> {
+        let mut errors = Vec::new();
+        let mut bound: HashMap<Binding, usize> = HashMap::new();
+        for key in 0..NUM_KEYS {
+            for &binding in &self.bindings[key] {
+                if binding.is_hotkey(debugger) {
+                    errors.push(KeyMapError {
+                        line: self.lines[key],
+                        message: format!(
+                            "{} is a hotkey, and cannot be bound to {:X}",
+                            binding, key
+                        ),
+                    });
+                }
+                let typed = binding.keycode().map_or(binding, Binding::Key);
+                match bound.insert(typed, key) {
+                    Some(other) if other != key => errors.push(KeyMapError {
+                        line: self.lines[key].max(self.lines[other]),
+                        message: format!("{} is bound to both {:X} and {:X}", binding, other, key),
+                    }),
+                    _ => {}
+                }
+            }
+        }
+        errors
+    }
+}
+
+enum Entry {
+    Layout(String),
+    Key(usize, Vec<Binding>),
+}
@@ -1,3 +1,5 @@
 pub mod audio;
+pub mod debugger;
 pub mod display;
-pub mod keyboard;
\ No newline at end of file
+pub mod keyboard;
+pub mod keymap;
\ No newline at end of file
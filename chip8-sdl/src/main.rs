@@ -1,41 +1,461 @@
-use chip8::Chip8;
+use chip8::audio::{Tone, Waveform};
+use chip8::debugger::Debugger;
+use chip8::image::{self, Palette, DEFAULT_PALETTE};
+use chip8::movie::Movie;
+use chip8::rewind::RewindBuffer;
+use chip8::video::{VideoFormat, VideoRecorder};
+use chip8::wav::{AudioRecorder, DEFAULT_SAMPLE_RATE};
+use chip8::{Chip8, LoadError, Quirks, DEFAULT_CYCLES_PER_FRAME, FRAME_RATE};
 use std::env;
 use std::fs::File;
 use std::path::Path;
-use std::io::{Read, Result};
+use std::process;
+use std::time::{SystemTime, UNIX_EPOCH};
+use std::io::{BufReader, BufWriter, Read, Result, Write};
 use chip8_sdl::display::{Display, SdlDisplay};
-use chip8_sdl::keyboard::{Keyboard, SdlKeyboard};
+use chip8_sdl::keyboard::{Hotkey, Keyboard, SdlKeyboard};
+use chip8_sdl::keymap::KeyMap;
 use chip8_sdl::audio::{Audio, SdlAudio};
+use chip8_sdl::debugger::DebuggerView;
+use sdl2::gfx::framerate::FPSManager;
+
+// about a minute of gameplay for most programs
+const REWIND_BUFFER_SIZE: usize = 16 * 1024 * 1024;
+// screenshots of low resolution programs are 640x320
+const DEFAULT_SCREENSHOT_SCALE: usize = 10;
+// and so are videos, whose scale applies to high resolution pixels
+const DEFAULT_VIDEO_SCALE: usize = 5;
+
+const USAGE: &str = "usage: chip8 [--quirks vip|chip48|schip|octo] [--cycles cycles_per_frame] [--seed seed] \
+[--debug] [--break address]... [--watch address]... [--record movie_path | --play movie_path] \
+[--palette colors] [--screenshot-scale scale] [--video-format gif|y4m] [--video-scale scale] \
+[--waveform square|triangle|sine|noise] [--tone frequency] [--volume volume] \
+[--attack milliseconds] [--release milliseconds] [--keymap keymap_path] rom_path";
+
+struct Options {
+    rom_file_path: String,
+    quirks: Quirks,
+    cycles_per_frame: usize,
+    seed: u64,
+    debug: bool,
+    breakpoints: Vec<usize>,
+    write_breakpoints: Vec<usize>,
+    record_path: Option<String>,
+    play_path: Option<String>,
+    palette: Palette,
+    screenshot_scale: usize,
+    video_format: VideoFormat,
+    video_scale: usize,
+    tone: Tone,
+    keymap_path: Option<String>,
+}
+
+/// A video being captured, started and stopped by a hotkey, its sound being rendered alongside.
+struct Capture {
+    path: String,
+    video: VideoRecorder<BufWriter<File>>,
+    audio: AudioRecorder,
+}
 
 fn main() {
-    let arguments: Vec<String> = env::args().skip(1).collect();
-    if let Some(rom_file_path) = arguments.get(0) {
-        let program = read_program(rom_file_path).unwrap();
-        let mut computer = Chip8::new(program.as_slice());
+    if let Some(options) = parse_arguments(env::args().skip(1)) {
+        let program = read_program(&options.rom_file_path).unwrap();
+        let mut computer = Chip8::new(program.as_slice(), options.quirks, options.seed)
+            .unwrap_or_else(|error| exit_with_load_error(&options.rom_file_path, error));
+        computer.set_cycles_per_frame(options.cycles_per_frame);
+        let mut playback = options.play_path.as_ref().map(|path| {
+            let movie = load_movie(path).unwrap_or_else(|error| {
+                eprintln!("chip8: cannot read {}: {}", path, error);
+                process::exit(1);
+            });
+            if !movie.matches(&program) {
+                eprintln!("chip8: {} was recorded with another ROM", path);
+                process::exit(1);
+            }
+            movie
+        });
+        if let Some(movie) = &playback {
+            computer = movie
+                .machine(&program)
+                .unwrap_or_else(|error| exit_with_load_error(&options.rom_file_path, error));
+        }
+        let mut recording = options
+            .record_path
+            .as_ref()
+            .map(|_| Movie::new(&program, &computer, options.seed));
+        // frames run so far, which index the movie
+        let mut frame = 0;
         let sdl_context = sdl2::init().unwrap();
-        let mut display = SdlDisplay::new(&sdl_context);
-        let mut keyboard = SdlKeyboard::new(&sdl_context);
-        let audio = SdlAudio::new(&sdl_context);
-        while let Ok(keys) = keyboard.poll() {
-            match computer.iteration(&keys) {
-                Some(state) => {
-                    if state.should_draw {
+        let mut display = SdlDisplay::new(&sdl_context, options.palette);
+        // the keys typed by positions are only known once the display is open
+        let keymap = match &options.keymap_path {
+            Some(path) => load_keymap(path, &options.rom_file_path, is_debugging(&options)),
+            None => KeyMap::default(),
+        };
+        let mut keyboard = SdlKeyboard::new(&sdl_context, keymap);
+        let mut audio = SdlAudio::new(&sdl_context);
+        audio.set_tone(options.tone);
+        let mut fps_manager = FPSManager::new();
+        fps_manager.set_framerate(FRAME_RATE).unwrap();
+        let mut rewind_buffer = RewindBuffer::new(REWIND_BUFFER_SIZE);
+        let mut debugger_view = new_debugger(&options, &computer);
+        let mut capture = None;
+        while let Ok(input) = keyboard.poll() {
+            for hotkey in input.hotkeys {
+                if let Some(view) = &mut debugger_view {
+                    view.handle(hotkey, &computer);
+                }
+                match hotkey {
+                    Hotkey::SaveState(slot) => {
+                        let path = state_path(&options.rom_file_path, slot);
+                        match save_state(&computer, &path) {
+                            Ok(()) => println!("saved state to {}", path),
+                            Err(error) => eprintln!("chip8: cannot save {}: {}", path, error),
+                        }
+                    }
+                    Hotkey::LoadState(_) if playback.is_some() || recording.is_some() => {
+                        eprintln!("chip8: states cannot be loaded while a movie plays or records");
+                    }
+                    Hotkey::LoadState(slot) => {
+                        let path = state_path(&options.rom_file_path, slot);
+                        match load_state(&path) {
+                            Ok(loaded) => {
+                                computer = loaded;
+                                if let Some(view) = &mut debugger_view {
+                                    view.replaced(&computer);
+                                }
+                                rewind_buffer.clear();
+                                audio.stop();
+                                display.draw(&computer.state.display_buffer);
+                                println!("loaded state from {}", path);
+                            }
+                            Err(error) => eprintln!("chip8: cannot load {}: {}", path, error),
+                        }
+                    }
+                    Hotkey::Capture => match capture.take() {
+                        Some(capture) => stop_capture(capture),
+                        None => {
+                            let extension = options.video_format.extension();
+                            let path = capture_path(&options.rom_file_path, extension);
+                            match start_capture(&path, &options, &computer) {
+                                Ok(started) => {
+                                    println!("capturing video to {}", path);
+                                    capture = Some(started);
+                                }
+                                Err(error) => eprintln!("chip8: cannot create {}: {}", path, error),
+                            }
+                        }
+                    },
+                    Hotkey::Screenshot => {
+                        let path = capture_path(&options.rom_file_path, "png");
+                        let framebuffer = &computer.state.display_buffer;
+                        match display.screenshot(framebuffer, &path, options.screenshot_scale) {
+                            Ok(()) => println!("saved screenshot to {}", path),
+                            Err(error) => eprintln!("chip8: cannot save {}: {}", path, error),
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            if input.rewind {
+                // step back one frame at a time, staying on the oldest one once the buffer runs out
+                if let Some(previous) = rewind_buffer.pop() {
+                    computer = previous;
+                    if let Some(view) = &mut debugger_view {
+                        view.replaced(&computer);
+                    }
+                    display.draw(&computer.state.display_buffer);
+                    frame -= 1;
+                    if let Some(movie) = &mut recording {
+                        movie.truncate(frame);
+                    }
+                }
+                audio.stop();
+                fps_manager.delay();
+                continue;
+            }
+            if !is_paused(&debugger_view) {
+                rewind_buffer.push(&computer);
+            }
+            if matches!(&playback, Some(movie) if frame == movie.len()) {
+                println!("end of the movie, the keyboard takes over");
+                playback = None;
+            }
+            let keys = match &playback {
+                Some(movie) => movie.keys(frame).unwrap(),
+                None => input.keys,
+            };
+            if let Some(movie) = &mut recording {
+                movie.record(&keys);
+            }
+            frame += 1;
+            let result = match &mut debugger_view {
+                Some(view) => view.debugger.run_frame(&mut computer, &keys).map(|stop| {
+                    if let Some(stop) = stop {
+                        view.stopped(stop, &computer);
+                    }
+                }),
+                None => computer.run_frame(&keys).map(|_| ()),
+            };
+            match result {
+                Ok(()) => {
+                    let events = computer.take_audio_events();
+                    let state = &computer.state;
+                    if let Some(view) = &debugger_view {
+                        // the panel changes even when the program does not draw
+                        let (lines, highlight) = view.overlay(&computer);
+                        display.draw_overlay(&state.display_buffer, &lines, highlight);
+                    } else if state.should_draw {
                         display.draw(&state.display_buffer);
                     }
-                    if state.play_audio {
-                        audio.play();
-                    } else {
+                    if let Some(Capture { path, video, audio }) = &mut capture {
+                        if !is_paused(&debugger_view) {
+                            audio.frame(&events);
+                            if let Err(error) = video.frame(&state.display_buffer) {
+                                eprintln!("chip8: cannot write {}: {}", path, error);
+                                capture = None;
+                            }
+                        }
+                    }
+                    if state.halted {
+                        break;
+                    }
+                    if is_paused(&debugger_view) {
                         audio.stop();
+                    } else {
+                        audio.queue(&events);
                     }
                 }
-                None => break,
+                Err(error) => {
+                    eprintln!("chip8: {}", error);
+                    break;
+                }
             };
+            fps_manager.delay();
+        }
+        if let Some(capture) = capture {
+            stop_capture(capture);
+        }
+        if let (Some(path), Some(movie)) = (&options.record_path, &recording) {
+            match save_movie(movie, path) {
+                Ok(()) => println!("recorded {} frames to {}", movie.len(), path),
+                Err(error) => eprintln!("chip8: cannot write {}: {}", path, error),
+            }
         }
     } else {
-        println!("usage: chip8 rom_path");
+        println!("{}", USAGE);
     }
 }
 
+fn parse_arguments<I: Iterator<Item = String>>(mut arguments: I) -> Option<Options> {
+    let mut rom_file_path = None;
+    let mut quirks = Quirks::default();
+    let mut cycles_per_frame = DEFAULT_CYCLES_PER_FRAME;
+    let mut seed = None;
+    let mut debug = false;
+    let mut breakpoints = Vec::new();
+    let mut write_breakpoints = Vec::new();
+    let mut record_path = None;
+    let mut play_path = None;
+    let mut palette = DEFAULT_PALETTE;
+    let mut screenshot_scale = DEFAULT_SCREENSHOT_SCALE;
+    let mut video_format = VideoFormat::Gif;
+    let mut video_scale = DEFAULT_VIDEO_SCALE;
+    let mut tone = Tone::default();
+    let mut keymap_path = None;
+    while let Some(argument) = arguments.next() {
+        match argument.as_str() {
+            "--debug" => debug = true,
+            "--break" => breakpoints.push(parse_address(&arguments.next()?)?),
+            "--watch" => write_breakpoints.push(parse_address(&arguments.next()?)?),
+            "--quirks" => quirks = Quirks::preset(&arguments.next()?)?,
+            "--cycles" => cycles_per_frame = arguments.next()?.parse().ok()?,
+            "--seed" => seed = Some(arguments.next()?.parse().ok()?),
+            "--record" => record_path = Some(arguments.next()?),
+            "--play" => play_path = Some(arguments.next()?),
+            "--palette" => palette = image::parse_palette(&arguments.next()?)?,
+            "--screenshot-scale" => {
+                screenshot_scale = arguments.next()?.parse().ok().filter(|&scale| scale > 0)?
+            }
+            "--video-format" => {
+                video_format = match arguments.next()?.as_str() {
+                    "gif" => VideoFormat::Gif,
+                    "y4m" => VideoFormat::Y4m,
+                    _ => return None,
+                }
+            }
+            "--video-scale" => {
+                video_scale = arguments.next()?.parse().ok().filter(|&scale| scale > 0)?
+            }
+            "--waveform" => tone.waveform = Waveform::from_name(&arguments.next()?)?,
+            "--tone" => tone.frequency = arguments.next()?.parse().ok().filter(|&hz| hz > 0.0)?,
+            "--volume" => {
+                tone.volume = arguments
+                    .next()?
+                    .parse()
+                    .ok()
+                    .filter(|volume| (0.0..=1.0).contains(volume))?
+            }
+            "--attack" => tone.attack = parse_milliseconds(&arguments.next()?)?,
+            "--release" => tone.release = parse_milliseconds(&arguments.next()?)?,
+            "--keymap" => keymap_path = Some(arguments.next()?),
+            _ => rom_file_path = Some(argument),
+        }
+    }
+    // the debugger runs partial frames, which movies cannot hold
+    let debugging = debug || !breakpoints.is_empty() || !write_breakpoints.is_empty();
+    let movie = record_path.is_some() || play_path.is_some();
+    if (debugging && movie) || (record_path.is_some() && play_path.is_some()) {
+        return None;
+    }
+    Some(Options {
+        rom_file_path: rom_file_path?,
+        quirks,
+        cycles_per_frame,
+        // without a seed, every run plays differently
+        seed: seed.unwrap_or_else(|| {
+            let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+            now.as_nanos() as u64
+        }),
+        debug,
+        breakpoints,
+        write_breakpoints,
+        record_path,
+        play_path,
+        palette,
+        screenshot_scale,
+        video_format,
+        video_scale,
+        tone,
+        keymap_path,
+    })
+}
+
+/// Parses a hexadecimal address, with or without a `0x` prefix.
+fn parse_address(address: &str) -> Option<usize> {
+    let digits = address.strip_prefix("0x").unwrap_or(address);
+    usize::from_str_radix(digits, 16).ok()
+}
+
+/// Parses a duration in milliseconds, returning seconds.
+fn parse_milliseconds(milliseconds: &str) -> Option<f32> {
+    let milliseconds: f32 = milliseconds.parse().ok()?;
+    Some(milliseconds / 1000.0).filter(|&seconds| seconds >= 0.0)
+}
+
+/// The debugger is enabled by `--debug`, which starts the program paused, or by setting breakpoints.
+fn is_debugging(options: &Options) -> bool {
+    options.debug || !options.breakpoints.is_empty() || !options.write_breakpoints.is_empty()
+}
+
+fn new_debugger(options: &Options, computer: &Chip8) -> Option<DebuggerView> {
+    if !is_debugging(options) {
+        return None;
+    }
+    let mut debugger = Debugger::new();
+    for &address in &options.breakpoints {
+        debugger.toggle_breakpoint(address);
+    }
+    for &address in &options.write_breakpoints {
+        debugger.toggle_write_breakpoint(address);
+    }
+    if options.debug {
+        debugger.pause();
+    }
+    Some(DebuggerView::new(debugger, computer))
+}
+
+fn is_paused(debugger_view: &Option<DebuggerView>) -> bool {
+    matches!(debugger_view, Some(view) if view.debugger.is_paused())
+}
+
+fn state_path(rom_file_path: &str, slot: usize) -> String {
+    format!("{}.state{}", rom_file_path, slot)
+}
+
+/// The first of `rom.1.png`, `rom.2.png`... which does not exist yet, for another extension.
+fn capture_path(rom_file_path: &str, extension: &str) -> String {
+    (1..)
+        .map(|number| format!("{}.{}.{}", rom_file_path, number, extension))
+        .find(|path| !Path::new(path).exists())
+        .unwrap()
+}
+
+fn start_capture(path: &str, options: &Options, computer: &Chip8) -> Result<Capture> {
+    let writer = BufWriter::new(File::create(path)?);
+    let format = options.video_format;
+    let video = VideoRecorder::new(writer, format, options.video_scale, options.palette)?;
+    Ok(Capture {
+        path: path.to_string(),
+        video,
+        audio: AudioRecorder::new(DEFAULT_SAMPLE_RATE, options.tone, computer),
+    })
+}
+
+/// Writes the end of the video, and its sound next to it, e.g. `rom.1.wav` for `rom.1.gif`.
+fn stop_capture(capture: Capture) {
+    let Capture { path, video, audio } = capture;
+    let frames = video.len();
+    match video.finish() {
+        Ok(_) => println!("captured {} frames to {}", frames, path),
+        Err(error) => eprintln!("chip8: cannot write {}: {}", path, error),
+    }
+    let audio_path = Path::new(&path).with_extension("wav");
+    let written = File::create(&audio_path).and_then(|file| {
+        let mut writer = BufWriter::new(file);
+        audio.write(&mut writer)?;
+        writer.flush()
+    });
+    match written {
+        Ok(()) => println!("saved its sound to {}", audio_path.display()),
+        Err(error) => eprintln!("chip8: cannot write {}: {}", audio_path.display(), error),
+    }
+}
+
+fn save_state(computer: &Chip8, path: &str) -> Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    computer.save_state(&mut writer)?;
+    writer.flush()
+}
+
+fn load_state(path: &str) -> Result<Chip8> {
+    Chip8::load_state(BufReader::new(File::open(path)?))
+}
+
+fn save_movie(movie: &Movie, path: &str) -> Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    movie.write(&mut writer)?;
+    writer.flush()
+}
+
+fn load_movie(path: &str) -> Result<Movie> {
+    Movie::read(BufReader::new(File::open(path)?))
+}
+
+/// Loads the key map for a ROM, exiting with every error in the file.
+fn load_keymap(path: &str, rom_file_path: &str, debugger: bool) -> KeyMap {
+    let source = std::fs::read_to_string(path).unwrap_or_else(|error| {
+        eprintln!("chip8: cannot read {}: {}", path, error);
+        process::exit(1);
+    });
+    // ROMs are overridden by file name, wherever they are
+    let rom_name = Path::new(rom_file_path)
+        .file_name()
+        .map(|name| name.to_string_lossy())
+        .unwrap_or_default();
+    KeyMap::load(&source, &rom_name, debugger).unwrap_or_else(|errors| {
+        for error in errors {
+            eprintln!("chip8: {}: {}", path, error);
+        }
+        process::exit(1);
+    })
+}
+
+fn exit_with_load_error(rom_file_path: &str, error: LoadError) -> ! {
+    eprintln!("chip8: cannot load {}: {}", rom_file_path, error);
+    process::exit(1);
+}
+
 fn read_program<P: AsRef<Path>>(rom_file_path: P) -> Result<Vec<u8>> {
     let mut rom_file = File::open(rom_file_path.as_ref()).unwrap();
     let mut buffer: Vec<u8> = Vec::new();
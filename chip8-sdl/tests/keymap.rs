@@ -0,0 +1,154 @@
+use chip8::keyboard::NUM_KEYS;
+use chip8_sdl::keymap::{Binding, KeyMap, KeyMapError};
+use sdl2::keyboard::{Keycode, Scancode};
+
+fn keys(names: &[&str]) -> Vec<Binding> {
+    names
+        .iter()
+        .map(|name| Binding::parse(name).unwrap())
+        .collect()
+}
+
+fn errors(source: &str) -> Vec<String> {
+    KeyMap::load(source, "pong.ch8", true)
+        .unwrap_err()
+        .iter()
+        .map(KeyMapError::to_string)
+        .collect()
+}
+
+#[test]
+fn defaults_to_qwerty() {
+    let map = KeyMap::load("; nothing bound\n", "pong.ch8", false).unwrap();
+    let qwerty: [&str; NUM_KEYS] = [
+        "X", "1", "2", "3", "Q", "W", "E", "A", "S", "D", "Z", "C", "4", "R", "F", "V",
+    ];
+    for (key, name) in qwerty.iter().enumerate() {
+        assert_eq!(map.bindings(key), keys(&[name]).as_slice());
+    }
+}
+
+#[test]
+fn applies_rom_sections_over_the_keys_section() {
+    let source = "
+[keys]
+1 = Keypad 1
+2 = Keypad 2, Keypad 8
+
+[pong.ch8]
+1 = Keypad 7
+2 =
+
+[tetris.ch8]
+1 = Keypad 9
+";
+    let pong = KeyMap::load(source, "pong.ch8", false).unwrap();
+    assert_eq!(pong.bindings(1), keys(&["Keypad 7"]).as_slice());
+    assert_eq!(pong.bindings(2), &[]);
+    let other = KeyMap::load(source, "other.ch8", false).unwrap();
+    assert_eq!(other.bindings(1), keys(&["Keypad 1"]).as_slice());
+    assert_eq!(
+        other.bindings(2),
+        keys(&["Keypad 2", "Keypad 8"]).as_slice()
+    );
+    assert_eq!(other.bindings(3), keys(&["3"]).as_slice());
+}
+
+#[test]
+fn applies_the_layout_before_the_keys() {
+    let map = KeyMap::load("[keys]\n5 = Keypad 5\nlayout = dvorak\n", "pong.ch8", false).unwrap();
+    assert_eq!(map.bindings(5), keys(&["Keypad 5"]).as_slice());
+    assert_eq!(map.bindings(4), keys(&["'"]).as_slice());
+    assert_eq!(map.bindings(0xF), keys(&["K"]).as_slice());
+}
+
+#[test]
+fn binds_azerty_keys_by_position() {
+    let map = KeyMap::load("[keys]\nlayout = azerty\n", "pong.ch8", false).unwrap();
+    assert_eq!(map.bindings(1), &[Binding::Key(Keycode::Ampersand)]);
+    assert_eq!(map.bindings(2), &[Binding::Scancode(Scancode::Num2)]);
+    assert_eq!(map.bindings(4), &[Binding::Key(Keycode::A)]);
+    assert_eq!(map.bindings(0xA), &[Binding::Key(Keycode::W)]);
+    assert_eq!(map.key(Scancode::Num2), Some(2));
+}
+
+#[test]
+fn parses_key_names() {
+    assert_eq!(Binding::parse("q"), Some(Binding::Key(Keycode::Q)));
+    assert_eq!(Binding::parse("Q"), Some(Binding::Key(Keycode::Q)));
+    assert_eq!(Binding::parse("Keypad 5"), Some(Binding::Key(Keycode::Kp5)));
+    assert_eq!(
+        Binding::parse("scancode:2"),
+        Some(Binding::Scancode(Scancode::Num2))
+    );
+    // characters which SDL has no keycode for
+    assert_eq!(Binding::parse("é"), None);
+    assert_eq!(Binding::parse("{"), None);
+    assert_eq!(Binding::parse("Nope"), None);
+}
+
+#[test]
+fn reports_keys_bound_twice() {
+    assert_eq!(
+        errors("[keys]\n1 = A\n"),
+        vec!["line 2: A is bound to both 1 and 7"]
+    );
+    // the layout binds every key on its line
+    assert_eq!(
+        errors("[keys]\nlayout = azerty\n[pong.ch8]\nf = Q\n"),
+        vec!["line 4: Q is bound to both 7 and F"]
+    );
+}
+
+#[test]
+fn reports_hotkeys() {
+    assert_eq!(
+        errors("[keys]\n1 = F5\n2 = Up\n3 = Backspace, F12\n"),
+        vec![
+            "line 2: F5 is a hotkey, and cannot be bound to 1",
+            "line 3: Up is a hotkey, and cannot be bound to 2",
+            "line 4: Backspace is a hotkey, and cannot be bound to 3",
+            "line 4: F12 is a hotkey, and cannot be bound to 3",
+        ]
+    );
+}
+
+#[test]
+fn frees_the_debugger_keys_without_the_debugger() {
+    let source = "[keys]\n1 = F5\n2 = Up, Down\n3 = F6, F12\n";
+    let errors: Vec<String> = KeyMap::load(source, "pong.ch8", false)
+        .unwrap_err()
+        .iter()
+        .map(KeyMapError::to_string)
+        .collect();
+    assert_eq!(
+        errors,
+        vec!["line 4: F12 is a hotkey, and cannot be bound to 3"]
+    );
+    let map = KeyMap::load("[keys]\n1 = F5\n2 = Up, Down\n", "pong.ch8", false).unwrap();
+    assert_eq!(map.bindings(1), keys(&["F5"]).as_slice());
+    assert_eq!(map.key(Scancode::Down), Some(2));
+}
+
+#[test]
+fn reports_every_error_with_its_line() {
+    let source = "1 = Q
+[keys]
+layout = colemak
+g = Q
+c = Nope
+[pong.ch8
+e
+";
+    assert_eq!(
+        errors(source),
+        vec![
+            "line 1: binding outside of a section",
+            "line 3: unknown layout colemak",
+            "line 4: g is not a CHIP-8 key",
+            "line 5: unknown key Nope",
+            "line 6: invalid section [pong.ch8",
+            "line 7: expected key = value, found e",
+        ]
+    );
+}